@@ -67,6 +67,7 @@ export const allowedWorkspaceExtensions = [
   'files',
   'generators',
   'namedInputs',
+  'scopeExternalDependencies',
   'extends',
   'cli',
   'pluginsConfig',
@@ -85,6 +86,8 @@ export const allowedWorkspaceExtensions = [
   'sync',
   'useLegacyCache',
   'maxCacheSize',
+  'cacheEviction',
+  'taskHistory',
   'tui',
   'owners',
 ] as const;
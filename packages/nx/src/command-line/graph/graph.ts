@@ -1243,19 +1243,21 @@ function expandInputs(
       workspaceRootInputs.push(...inputs);
       return;
     }
-    const maybeProjectName = input.split(':')[0];
-    if (projectNames.includes(maybeProjectName)) {
-      projectRootInputs.push(input);
-      return;
-    }
+    // scoped external dependencies look like project:ExternalDependencies
     if (
       input === 'ProjectConfiguration' ||
       input === 'TsConfig' ||
-      input === 'AllExternalDependencies'
+      input === 'AllExternalDependencies' ||
+      input.endsWith(':ExternalDependencies')
     ) {
       otherInputs.push(input);
       return;
     }
+    const maybeProjectName = input.split(':')[0];
+    if (projectNames.includes(maybeProjectName)) {
+      projectRootInputs.push(input);
+      return;
+    }
     // there shouldn't be any other imports in here, but external ones are always going to have a modifier in front
     if (input.includes(':')) {
       externalInputs.push(input);
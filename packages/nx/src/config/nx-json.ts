@@ -688,6 +688,32 @@ export interface NxSyncConfiguration {
   disabledTaskSyncGenerators?: string[];
 }
 
+export interface NxCacheEvictionConfiguration {
+  /**
+   * Maximum size of the cached outputs of each project, e.g. `{ "my-app": "500MB" }`. Accepts the same units as `maxCacheSize`.
+   */
+  projectQuotas?: Record<string, string>;
+  /**
+   * Number of most recently used entries kept for each target of a project.
+   */
+  keepPerTarget?: number;
+  /**
+   * When the cache exceeds `maxCacheSize`, evict entries which were quick to compute before entries which took long.
+   */
+  costAware?: boolean;
+}
+
+export interface NxTaskHistoryConfiguration {
+  /**
+   * Runs which ended more than this many days ago are rolled up into daily aggregates.
+   */
+  maxAgeDays?: number;
+  /**
+   * Number of most recent runs kept for each target. Older runs are rolled up into daily aggregates.
+   */
+  maxRunsPerTarget?: number;
+}
+
 /**
  * Nx.json configuration
  *
@@ -708,6 +734,11 @@ export interface NxJsonConfiguration<T = '*' | string[]> {
    * Named inputs targets can refer to reduce duplication
    */
   namedInputs?: { [inputName: string]: (string | InputDefinition)[] };
+  /**
+   * Hash only the external dependencies a project and its workspace dependencies use,
+   * instead of all of them, for targets whose executor dependencies are unknown.
+   */
+  scopeExternalDependencies?: boolean;
   /**
    * Dependencies between different target names across all projects
    */
@@ -859,6 +890,16 @@ export interface NxJsonConfiguration<T = '*' | string[]> {
    */
   maxCacheSize?: string;
 
+  /**
+   * Policies deciding which entries are evicted from the local cache, in addition to `maxCacheSize` and the age of entries.
+   */
+  cacheEviction?: NxCacheEvictionConfiguration;
+
+  /**
+   * Retention of the task history used for flaky task detection and timing estimates.
+   */
+  taskHistory?: NxTaskHistoryConfiguration;
+
   /**
    * Settings for the Nx Terminal User Interface (TUI)
    */
@@ -125,7 +125,14 @@ export interface ProjectGraphExternalNode {
   data: {
     version: string;
     packageName: string;
+    /**
+     * The integrity checksum recorded in the lockfile, or a hash of the resolved package
+     */
     hash?: string;
+    /**
+     * Patch files applied to the package, relative to the workspace root
+     */
+    patches?: string[];
   };
 }
 
@@ -36,7 +36,17 @@ import {
   DaemonProjectGraphError,
   ProjectGraphError,
 } from '../../project-graph/error-types';
-import { IS_WASM, NxWorkspaceFiles, TaskRun, TaskTarget } from '../../native';
+import {
+  FlakinessOptions,
+  IS_WASM,
+  NxWorkspaceFiles,
+  TaskFlakiness,
+  TaskGraphEstimate,
+  TaskHistoryCompaction,
+  TaskHistoryRetention,
+  TaskRun,
+  TaskTarget,
+} from '../../native';
 import {
   HandleGlobMessage,
   HandleMultiGlobMessage,
@@ -60,10 +70,16 @@ import {
   HandleHashMultiGlobMessage,
 } from '../message-types/hash-glob';
 import {
+  COMPACT_TASK_HISTORY,
   GET_ESTIMATED_TASK_TIMINGS,
   GET_FLAKY_TASKS,
+  GET_TASK_FLAKINESS,
+  GET_TASK_GRAPH_ESTIMATE,
+  HandleCompactTaskHistoryMessage,
   HandleGetEstimatedTaskTimings,
   HandleGetFlakyTasks,
+  HandleGetTaskFlakiness,
+  HandleGetTaskGraphEstimate,
   HandleRecordTaskRunsMessage,
   RECORD_TASK_RUNS,
 } from '../message-types/task-history';
@@ -467,6 +483,32 @@ export class DaemonClient {
     return this.sendToDaemonViaQueue(message);
   }
 
+  getTaskFlakiness(
+    targets: TaskTarget[],
+    options?: FlakinessOptions
+  ): Promise<TaskFlakiness[]> {
+    const message: HandleGetTaskFlakiness = {
+      type: GET_TASK_FLAKINESS,
+      targets,
+      options,
+    };
+
+    return this.sendToDaemonViaQueue(message);
+  }
+
+  getTaskGraphEstimate(
+    taskGraph: TaskGraph,
+    parallelism?: number
+  ): Promise<TaskGraphEstimate> {
+    const message: HandleGetTaskGraphEstimate = {
+      type: GET_TASK_GRAPH_ESTIMATE,
+      taskGraph,
+      parallelism,
+    };
+
+    return this.sendToDaemonViaQueue(message);
+  }
+
   async getEstimatedTaskTimings(
     targets: TaskTarget[]
   ): Promise<Record<string, number>> {
@@ -486,6 +528,16 @@ export class DaemonClient {
     return this.sendToDaemonViaQueue(message);
   }
 
+  compactTaskHistory(
+    retention: TaskHistoryRetention
+  ): Promise<TaskHistoryCompaction> {
+    const message: HandleCompactTaskHistoryMessage = {
+      type: COMPACT_TASK_HISTORY,
+      retention,
+    };
+    return this.sendToDaemonViaQueue(message);
+  }
+
   getSyncGeneratorChanges(
     generators: string[]
   ): Promise<SyncGeneratorRunResult[]> {
@@ -1,14 +1,35 @@
-import type { TaskRun, TaskTarget } from '../../native';
+import type {
+  FlakinessOptions,
+  TaskHistoryRetention,
+  TaskRun,
+  TaskTarget,
+} from '../../native';
+import type { TaskGraph } from '../../config/task-graph';
 
 export const GET_FLAKY_TASKS = 'GET_FLAKY_TASKS' as const;
+export const GET_TASK_FLAKINESS = 'GET_TASK_FLAKINESS' as const;
+export const GET_TASK_GRAPH_ESTIMATE = 'GET_TASK_GRAPH_ESTIMATE' as const;
 export const GET_ESTIMATED_TASK_TIMINGS = 'GET_ESTIMATED_TASK_TIMINGS' as const;
 export const RECORD_TASK_RUNS = 'RECORD_TASK_RUNS' as const;
+export const COMPACT_TASK_HISTORY = 'COMPACT_TASK_HISTORY' as const;
 
 export type HandleGetFlakyTasks = {
   type: typeof GET_FLAKY_TASKS;
   hashes: string[];
 };
 
+export type HandleGetTaskFlakiness = {
+  type: typeof GET_TASK_FLAKINESS;
+  targets: TaskTarget[];
+  options?: FlakinessOptions;
+};
+
+export type HandleGetTaskGraphEstimate = {
+  type: typeof GET_TASK_GRAPH_ESTIMATE;
+  taskGraph: TaskGraph;
+  parallelism?: number;
+};
+
 export type HandleGetEstimatedTaskTimings = {
   type: typeof GET_ESTIMATED_TASK_TIMINGS;
   targets: TaskTarget[];
@@ -19,6 +40,11 @@ export type HandleRecordTaskRunsMessage = {
   taskRuns: TaskRun[];
 };
 
+export type HandleCompactTaskHistoryMessage = {
+  type: typeof COMPACT_TASK_HISTORY;
+  retention: TaskHistoryRetention;
+};
+
 export function isHandleGetFlakyTasksMessage(
   message: unknown
 ): message is HandleGetFlakyTasks {
@@ -30,6 +56,28 @@ export function isHandleGetFlakyTasksMessage(
   );
 }
 
+export function isHandleGetTaskFlakinessMessage(
+  message: unknown
+): message is HandleGetTaskFlakiness {
+  return (
+    typeof message === 'object' &&
+    message !== null &&
+    'type' in message &&
+    message['type'] === GET_TASK_FLAKINESS
+  );
+}
+
+export function isHandleGetTaskGraphEstimateMessage(
+  message: unknown
+): message is HandleGetTaskGraphEstimate {
+  return (
+    typeof message === 'object' &&
+    message !== null &&
+    'type' in message &&
+    message['type'] === GET_TASK_GRAPH_ESTIMATE
+  );
+}
+
 export function isHandleGetEstimatedTaskTimings(
   message: unknown
 ): message is HandleGetEstimatedTaskTimings {
@@ -51,3 +99,14 @@ export function isHandleWriteTaskRunsToHistoryMessage(
     message['type'] === RECORD_TASK_RUNS
   );
 }
+
+export function isHandleCompactTaskHistoryMessage(
+  message: unknown
+): message is HandleCompactTaskHistoryMessage {
+  return (
+    typeof message === 'object' &&
+    message !== null &&
+    'type' in message &&
+    message['type'] === COMPACT_TASK_HISTORY
+  );
+}
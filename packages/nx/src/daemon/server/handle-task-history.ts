@@ -1,5 +1,11 @@
 import { getTaskHistory } from '../../utils/task-history';
-import type { TaskRun, TaskTarget } from '../../native';
+import type {
+  FlakinessOptions,
+  TaskHistoryRetention,
+  TaskRun,
+  TaskTarget,
+} from '../../native';
+import type { TaskGraph } from '../../config/task-graph';
 
 export async function handleRecordTaskRuns(taskRuns: TaskRun[]) {
   const taskHistory = getTaskHistory();
@@ -10,6 +16,17 @@ export async function handleRecordTaskRuns(taskRuns: TaskRun[]) {
   };
 }
 
+export async function handleCompactTaskHistory(
+  retention: TaskHistoryRetention
+) {
+  const taskHistory = getTaskHistory();
+  const compaction = await taskHistory.compactTaskHistory(retention);
+  return {
+    response: compaction,
+    description: 'handleCompactTaskHistory',
+  };
+}
+
 export async function handleGetFlakyTasks(hashes: string[]) {
   const taskHistory = getTaskHistory();
   const history = await taskHistory.getFlakyTasks(hashes);
@@ -19,6 +36,33 @@ export async function handleGetFlakyTasks(hashes: string[]) {
   };
 }
 
+export async function handleGetTaskFlakiness(
+  targets: TaskTarget[],
+  options?: FlakinessOptions
+) {
+  const taskHistory = getTaskHistory();
+  const flakiness = await taskHistory.getTaskFlakiness(targets, options);
+  return {
+    response: flakiness,
+    description: 'handleGetTaskFlakiness',
+  };
+}
+
+export async function handleGetTaskGraphEstimate(
+  taskGraph: TaskGraph,
+  parallelism?: number
+) {
+  const taskHistory = getTaskHistory();
+  const estimate = await taskHistory.getTaskGraphEstimate(
+    taskGraph,
+    parallelism
+  );
+  return {
+    response: estimate,
+    description: 'handleGetTaskGraphEstimate',
+  };
+}
+
 export async function handleGetEstimatedTaskTimings(targets: TaskTarget[]) {
   const taskHistory = getTaskHistory();
   const history = await taskHistory.getEstimatedTaskTimings(targets);
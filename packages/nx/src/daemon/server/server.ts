@@ -92,16 +92,25 @@ import {
 } from '../message-types/hash-glob';
 import { handleHashGlob, handleHashMultiGlob } from './handle-hash-glob';
 import {
+  COMPACT_TASK_HISTORY,
   GET_ESTIMATED_TASK_TIMINGS,
   GET_FLAKY_TASKS,
+  GET_TASK_FLAKINESS,
+  GET_TASK_GRAPH_ESTIMATE,
+  isHandleCompactTaskHistoryMessage,
   isHandleGetEstimatedTaskTimings,
   isHandleGetFlakyTasksMessage,
+  isHandleGetTaskFlakinessMessage,
+  isHandleGetTaskGraphEstimateMessage,
   isHandleWriteTaskRunsToHistoryMessage,
   RECORD_TASK_RUNS,
 } from '../message-types/task-history';
 import {
   handleRecordTaskRuns,
+  handleCompactTaskHistory,
   handleGetFlakyTasks,
+  handleGetTaskFlakiness,
+  handleGetTaskGraphEstimate,
   handleGetEstimatedTaskTimings,
 } from './handle-task-history';
 import { isHandleForceShutdownMessage } from '../message-types/force-shutdown';
@@ -346,6 +355,20 @@ async function handleMessage(socket: Socket, data: string) {
       () => handleGetFlakyTasks(payload.hashes),
       mode
     );
+  } else if (isHandleGetTaskFlakinessMessage(payload)) {
+    await handleResult(
+      socket,
+      GET_TASK_FLAKINESS,
+      () => handleGetTaskFlakiness(payload.targets, payload.options),
+      mode
+    );
+  } else if (isHandleGetTaskGraphEstimateMessage(payload)) {
+    await handleResult(
+      socket,
+      GET_TASK_GRAPH_ESTIMATE,
+      () => handleGetTaskGraphEstimate(payload.taskGraph, payload.parallelism),
+      mode
+    );
   } else if (isHandleGetEstimatedTaskTimings(payload)) {
     await handleResult(
       socket,
@@ -360,6 +383,13 @@ async function handleMessage(socket: Socket, data: string) {
       () => handleRecordTaskRuns(payload.taskRuns),
       mode
     );
+  } else if (isHandleCompactTaskHistoryMessage(payload)) {
+    await handleResult(
+      socket,
+      COMPACT_TASK_HISTORY,
+      () => handleCompactTaskHistory(payload.retention),
+      mode
+    );
   } else if (isHandleForceShutdownMessage(payload)) {
     await handleResult(
       socket,
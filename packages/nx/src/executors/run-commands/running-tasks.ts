@@ -4,11 +4,16 @@ import { env as appendLocalEnv } from 'npm-run-path';
 import { isAbsolute, join } from 'path';
 import * as treeKill from 'tree-kill';
 import { ExecutorContext } from '../../config/misc-interfaces';
+import type { ProcessTreeMonitor, ProcessTreeUsage } from '../../native';
 import {
   createPseudoTerminal,
   PseudoTerminal,
   PseudoTtyProcess,
 } from '../../tasks-runner/pseudo-terminal';
+import {
+  combineProcessTreeUsage,
+  monitorProcessTree,
+} from '../../tasks-runner/running-tasks/process-tree-usage';
 import { RunningTask } from '../../tasks-runner/running-tasks/running-task';
 import {
   loadAndExpandDotEnvFile,
@@ -71,6 +76,15 @@ export class ParallelRunningTasks implements RunningTask {
     }
   }
 
+  getResourceUsage(): ProcessTreeUsage | undefined {
+    return combineProcessTreeUsage(
+      this.childProcesses.map((childProcess) =>
+        childProcess.getResourceUsage()
+      ),
+      true
+    );
+  }
+
   async kill(signal?: NodeJS.Signals) {
     await Promise.all(
       this.childProcesses.map(async (p) => {
@@ -224,6 +238,7 @@ export class SeriallyRunningTasks implements RunningTask {
   private code: number | null = 0;
   private error: any;
   private outputCallbacks: Array<(terminalOutput: string) => void> = [];
+  private resourceUsages: (ProcessTreeUsage | undefined)[] = [];
 
   constructor(
     options: NormalizedRunCommandsOptions,
@@ -265,6 +280,10 @@ export class SeriallyRunningTasks implements RunningTask {
     throw new Error('Not implemented');
   }
 
+  getResourceUsage(): ProcessTreeUsage | undefined {
+    return combineProcessTreeUsage(this.resourceUsages, false);
+  }
+
   kill(signal?: NodeJS.Signals) {
     return this.currentProcess.kill(signal);
   }
@@ -293,6 +312,7 @@ export class SeriallyRunningTasks implements RunningTask {
       });
 
       let { code, terminalOutput } = await childProcess.getResults();
+      this.resourceUsages.push(childProcess.getResourceUsage());
       this.terminalOutput += terminalOutput;
       this.code = code;
       if (code !== 0) {
@@ -360,6 +380,7 @@ class RunningNodeProcess implements RunningTask {
   private exitCallbacks: Array<(code: number, terminalOutput: string) => void> =
     [];
   private outputCallbacks: Array<(terminalOutput: string) => void> = [];
+  private processTreeMonitor: ProcessTreeMonitor | undefined;
   public command: string;
 
   constructor(
@@ -383,6 +404,7 @@ class RunningNodeProcess implements RunningTask {
       cwd,
       windowsHide: false,
     });
+    this.processTreeMonitor = monitorProcessTree(this.childProcess.pid);
 
     this.addListeners(commandConfig, streamOutput);
   }
@@ -407,6 +429,10 @@ class RunningNodeProcess implements RunningTask {
     this.childProcess.send(message);
   }
 
+  getResourceUsage(): ProcessTreeUsage | undefined {
+    return this.processTreeMonitor?.stop() ?? undefined;
+  }
+
   kill(signal?: NodeJS.Signals): Promise<void> {
     return new Promise<void>((res, rej) => {
       treeKill(this.childProcess.pid, signal, (err) => {
@@ -478,6 +504,7 @@ class RunningNodeProcess implements RunningTask {
       }
     });
     this.childProcess.on('exit', (code) => {
+      this.processTreeMonitor?.stop();
       if (!this.readyWhenStatus.length || isReady(this.readyWhenStatus)) {
         for (const cb of this.exitCallbacks) {
           cb(code, this.terminalOutput);
@@ -75,7 +75,7 @@ export class HashPlanInspector {
 
     const plansReference = this.planner.getPlansReference(taskIds, taskGraph);
 
-    return this.inspector.inspect(plansReference);
+    return this.inspector.inspect(plansReference, process.env);
   }
 
   /**
@@ -117,6 +117,6 @@ export class HashPlanInspector {
     const taskIds = Object.keys(taskGraph.tasks);
 
     const plansReference = this.planner.getPlansReference(taskIds, taskGraph);
-    return this.inspector.inspect(plansReference);
+    return this.inspector.inspect(plansReference, process.env);
   }
 }
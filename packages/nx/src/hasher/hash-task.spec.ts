@@ -0,0 +1,125 @@
+jest.mock('../tasks-runner/utils', () => ({
+  ...jest.requireActual('../tasks-runner/utils'),
+  getCustomHasher: jest.fn(() => null),
+}));
+
+import { join } from 'path';
+import { rmSync } from 'fs';
+import { randomBytes } from 'crypto';
+import { TaskDetails } from '../native';
+import { getDbConnection } from '../utils/db-connection';
+import { ProjectGraph } from '../config/project-graph';
+import { Task, TaskGraph } from '../config/task-graph';
+import { Hash, TaskHasher } from './task-hasher';
+import {
+  hashTask,
+  hashTasksThatDoNotDependOnOutputsOfOtherTasks,
+} from './hash-task';
+
+const dbOutputFolder = 'temp-db-hash-task';
+
+describe('hashTask', () => {
+  let taskDetails: TaskDetails;
+  let task: Task;
+  let taskGraph: TaskGraph;
+
+  const projectGraph: ProjectGraph = {
+    nodes: {
+      proj: {
+        name: 'proj',
+        type: 'lib',
+        data: { root: 'libs/proj', targets: { build: {} } },
+      },
+    },
+    dependencies: { proj: [] },
+  };
+
+  function hasher(source: string): TaskHasher {
+    const hash: Hash = {
+      value: `hash-${source}`,
+      details: {
+        command: 'proj:build',
+        nodes: {
+          'proj:{projectRoot}/**/*': `files-${source}`,
+          AllExternalDependencies: 'deps',
+        },
+        implicitDeps: {},
+        runtime: {},
+      },
+    };
+    return {
+      hashTask: async () => hash,
+      hashTasks: async (tasks: Task[]) => tasks.map(() => hash),
+    } as any as TaskHasher;
+  }
+
+  beforeEach(() => {
+    taskDetails = new TaskDetails(
+      getDbConnection({
+        directory: join(__dirname, dbOutputFolder),
+        dbName: `temp-db-${randomBytes(4).toString('hex')}`,
+      })
+    );
+    task = {
+      id: 'proj:build',
+      target: { project: 'proj', target: 'build' },
+      overrides: {},
+      outputs: [],
+      parallelism: true,
+    } as Task;
+    taskGraph = {
+      roots: [task.id],
+      tasks: { [task.id]: task },
+      dependencies: { [task.id]: [] },
+      continuousDependencies: { [task.id]: [] },
+    };
+  });
+
+  afterAll(() => {
+    rmSync(join(__dirname, dbOutputFolder), {
+      recursive: true,
+      force: true,
+    });
+  });
+
+  it('should record the instruction hashes of tasks hashed together', async () => {
+    await hashTasksThatDoNotDependOnOutputsOfOtherTasks(
+      hasher('a'),
+      projectGraph,
+      taskGraph,
+      {},
+      taskDetails
+    );
+    expect(task.hash).toEqual('hash-a');
+
+    await hashTasksThatDoNotDependOnOutputsOfOtherTasks(
+      hasher('b'),
+      projectGraph,
+      taskGraph,
+      {},
+      taskDetails
+    );
+
+    expect(
+      taskDetails.diffTaskHashes(task.target, 'hash-b', 'hash-a').changes
+    ).toEqual([
+      expect.objectContaining({
+        instruction: 'proj:{projectRoot}/**/*',
+        baseValue: 'files-a',
+        value: 'files-b',
+      }),
+    ]);
+  });
+
+  it('should record the instruction hashes of a single task', async () => {
+    await hashTask(hasher('a'), projectGraph, taskGraph, task, {}, taskDetails);
+    await hashTask(hasher('b'), projectGraph, taskGraph, task, {}, taskDetails);
+
+    expect(task.hash).toEqual('hash-b');
+    expect(
+      taskDetails
+        .diffTaskHashes(task.target, 'hash-b', 'hash-a')
+        .changes.map(({ instruction }) => instruction)
+    ).toEqual(['proj:{projectRoot}/**/*']);
+  });
+});
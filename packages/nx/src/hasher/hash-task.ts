@@ -1,7 +1,7 @@
 import { Task, TaskGraph } from '../config/task-graph';
 import { getCustomHasher } from '../tasks-runner/utils';
 import { readProjectsConfigurationFromProjectGraph } from '../project-graph/project-graph';
-import { getInputs, TaskHasher } from './task-hasher';
+import { getInputs, Hash, TaskHasher } from './task-hasher';
 import { ProjectGraph } from '../config/project-graph';
 import { NxJsonConfiguration } from '../config/nx-json';
 import { readNxJson } from '../config/nx-json';
@@ -59,11 +59,12 @@ export async function hashTasksThatDoNotDependOnOutputsOfOtherTasks(
   }
   if (tasksDetails?.recordTaskDetails) {
     tasksDetails.recordTaskDetails(
-      tasksToHash.map((task) => ({
+      tasksToHash.map((task, i) => ({
         hash: task.hash,
         project: task.target.project,
         target: task.target.target,
         configuration: task.target.configuration,
+        details: getRecordedHashDetails(hashes[i].details),
       }))
     );
   }
@@ -111,6 +112,7 @@ export async function hashTask(
         project: task.target.project,
         target: task.target.target,
         configuration: task.target.configuration,
+        details: getRecordedHashDetails(details),
       },
     ]);
   }
@@ -122,3 +124,16 @@ export async function hashTask(
     'hashSingleTask:end'
   );
 }
+
+/**
+ * The hash of every instruction which makes up the task hash, in the flat shape recorded
+ * with the task details
+ */
+function getRecordedHashDetails(
+  details: Hash['details'] | undefined
+): Record<string, string> | undefined {
+  if (!details?.nodes) {
+    return undefined;
+  }
+  return { ...details.nodes, command: details.command };
+}
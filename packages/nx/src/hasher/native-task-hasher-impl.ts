@@ -15,6 +15,7 @@ import { transformProjectGraphForRust } from '../native/transform-objects';
 import { PartialHash, TaskHasherImpl } from './task-hasher';
 import { readJsonFile } from '../utils/fileutils';
 import { getRootTsConfigPath } from '../plugins/js/utils/typescript';
+import { getTaskDetails } from './hash-task';
 
 export class NativeTaskHasherImpl implements TaskHasherImpl {
   hasher: TaskHasher;
@@ -29,7 +30,7 @@ export class NativeTaskHasherImpl implements TaskHasherImpl {
     nxJson: NxJsonConfiguration,
     projectGraph: ProjectGraph,
     externals: NxWorkspaceFilesExternals,
-    options: { selectivelyHashTsConfig: boolean }
+    options: HasherOptions
   ) {
     this.projectGraphRef = transferProjectGraph(
       transformProjectGraphForRust(projectGraph)
@@ -60,6 +61,10 @@ export class NativeTaskHasherImpl implements TaskHasherImpl {
       paths,
       options
     );
+    const instructionHashes = getTaskDetails()?.loadInstructionHashes();
+    if (instructionHashes) {
+      this.hasher.loadInstructionHashes(instructionHashes);
+    }
   }
 
   async hashTask(
@@ -69,6 +74,7 @@ export class NativeTaskHasherImpl implements TaskHasherImpl {
   ): Promise<PartialHash> {
     const plans = this.planner.getPlansReference([task.id], taskGraph);
     const hashes = this.hasher.hashPlans(plans, env);
+    this.recordHashingResults();
 
     return hashes[task.id];
   }
@@ -83,6 +89,19 @@ export class NativeTaskHasherImpl implements TaskHasherImpl {
       taskGraph
     );
     const hashes = this.hasher.hashPlans(plans, env);
+    this.recordHashingResults();
     return tasks.map((t) => hashes[t.id]);
   }
+
+  /**
+   * Persists the files behind the hashed file sets so cache misses can be explained later,
+   * and the hashes of file set instructions so later runs can reuse them
+   */
+  private recordHashingResults() {
+    const fileSets = this.hasher.takeFileSets();
+    const instructionHashes = this.hasher.takeInstructionHashes();
+    const taskDetails = getTaskDetails();
+    taskDetails?.recordFileSets(fileSets);
+    taskDetails?.recordInstructionHashes(instructionHashes);
+  }
 }
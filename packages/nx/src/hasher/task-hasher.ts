@@ -11,6 +11,7 @@ import { InputDefinition } from '../config/workspace-json-project-json';
 import { minimatch } from 'minimatch';
 import { NativeTaskHasherImpl } from './native-task-hasher-impl';
 import { workspaceRoot } from '../utils/workspace-root';
+import { cacheDir, workspaceDataDirectory } from '../utils/cache-directory';
 import { NxWorkspaceFilesExternals } from '../native';
 
 /**
@@ -140,6 +141,11 @@ export class InProcessTaskHasher implements TaskHasher {
       this.externalRustReferences,
       {
         selectivelyHashTsConfig: this.options?.selectivelyHashTsConfig ?? false,
+        runtimeTimeout: this.options?.runtimeTimeout,
+        runtimeHashStdoutOnly: this.options?.runtimeHashStdoutOnly,
+        runtimeCacheTtl: this.options?.runtimeCacheTtl,
+        runtimeCacheDirectory: workspaceDataDirectory,
+        cacheDirectory: cacheDir,
       }
     );
   }
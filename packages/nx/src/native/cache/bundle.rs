@@ -0,0 +1,294 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tar::{Archive, Builder};
+use tracing::trace;
+
+use super::compression::{ArchiveCompression, decoder};
+use super::inspect::CacheEntryInfo;
+use super::integrity::{digest_directory, sha256};
+use crate::native::hasher::HashAlgorithm;
+
+/// Bumped whenever the layout of a bundle changes in an incompatible way
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Name of the archive entry holding the [`BundleManifest`]
+const MANIFEST_ENTRY: &str = "bundle.json";
+const ENTRIES_DIR: &str = "entries";
+const OUTPUTS_DIR: &str = "outputs";
+const TERMINAL_OUTPUT_ENTRY: &str = "terminalOutput";
+
+/// Describes the cache entries contained in a bundle.
+///
+/// A bundle is a zstd compressed tar archive starting with this manifest,
+/// followed by `entries/<hash>/terminalOutput` and `entries/<hash>/outputs/...`
+/// for every entry.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BundleManifest {
+    pub version: u32,
+    pub entries: Vec<BundleEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleEntry {
+    pub hash: String,
+    pub code: i16,
+    pub size: i64,
+    /// Milliseconds since the unix epoch
+    pub created_at: i64,
+    pub project: Option<String>,
+    pub target: Option<String>,
+    pub configuration: Option<String>,
+    /// The algorithm which produced the hash, see [`HashAlgorithm::name`]
+    #[serde(
+        default,
+        serialize_with = "serialize_hash_algorithm",
+        deserialize_with = "deserialize_hash_algorithm"
+    )]
+    pub hash_algorithm: HashAlgorithm,
+    /// SHA-256 digest of the terminal output
+    pub terminal_output: String,
+    /// SHA-256 digests of the outputs, keyed by their path relative to the outputs directory
+    pub outputs: BTreeMap<String, String>,
+}
+
+fn serialize_hash_algorithm<S: serde::Serializer>(
+    algorithm: &HashAlgorithm,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(algorithm.name())
+}
+
+fn deserialize_hash_algorithm<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashAlgorithm, D::Error> {
+    Ok(HashAlgorithm::from_name(&String::deserialize(
+        deserializer,
+    )?))
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SkippedBundleEntry {
+    pub hash: String,
+    pub reason: String,
+}
+
+#[napi(object)]
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct BundleImportResult {
+    /// Hashes of the entries added to the cache
+    pub imported: Vec<String>,
+    /// Entries which were already cached or failed validation
+    pub skipped: Vec<SkippedBundleEntry>,
+}
+
+/// A cache entry to be written into a bundle
+pub struct BundleSource {
+    pub entry: CacheEntryInfo,
+    pub outputs_path: PathBuf,
+    pub terminal_output_path: PathBuf,
+}
+
+/// Writes `sources` into a bundle and returns the inner writer
+pub fn write_bundle<W: Write>(writer: W, sources: &[BundleSource]) -> anyhow::Result<W> {
+    let mut entries = Vec::with_capacity(sources.len());
+    for source in sources {
+        let outputs = if source.outputs_path.is_dir() {
+            digest_directory(&source.outputs_path)?
+        } else {
+            BTreeMap::new()
+        };
+        entries.push(BundleEntry {
+            hash: source.entry.hash.clone(),
+            code: source.entry.code,
+            size: source.entry.size,
+            created_at: source.entry.created_at,
+            project: source.entry.project.clone(),
+            target: source.entry.target.clone(),
+            configuration: source.entry.configuration.clone(),
+            hash_algorithm: source.entry.hash_algorithm,
+            terminal_output: sha256(&fs::read(&source.terminal_output_path)?),
+            outputs,
+        });
+    }
+
+    let enc = ArchiveCompression::zstd().encoder(writer)?;
+    let mut archive = Builder::new(enc);
+    archive.follow_symlinks(false);
+
+    // The manifest comes first so it is known before any entry is unpacked
+    let manifest = serde_json::to_vec(&BundleManifest {
+        version: BUNDLE_VERSION,
+        entries,
+    })?;
+    let mut manifest_header = tar::Header::new_gnu();
+    manifest_header.set_size(manifest.len() as u64);
+    manifest_header.set_mode(0o644);
+    manifest_header.set_cksum();
+    archive.append_data(&mut manifest_header, MANIFEST_ENTRY, &manifest[..])?;
+
+    for source in sources {
+        let entry_dir = Path::new(ENTRIES_DIR).join(&source.entry.hash);
+        trace!("Adding {} to bundle", &source.entry.hash);
+        archive.append_path_with_name(
+            &source.terminal_output_path,
+            entry_dir.join(TERMINAL_OUTPUT_ENTRY),
+        )?;
+        if source.outputs_path.is_dir() {
+            archive.append_dir_all(entry_dir.join(OUTPUTS_DIR), &source.outputs_path)?;
+        }
+    }
+
+    archive.finish()?;
+    Ok(archive.into_inner()?.finish()?)
+}
+
+/// Unpacks a bundle into `staging_dir` and returns its manifest.
+/// The unpacked entries still need to be checked with [`verify_entry`].
+pub fn read_bundle(content: impl Read, staging_dir: &Path) -> anyhow::Result<BundleManifest> {
+    let mut archive = Archive::new(decoder(content)?);
+    let mut manifest: Option<BundleManifest> = None;
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        // Never write outside of the staging directory
+        if !entry_path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            anyhow::bail!("Unexpected entry {} in bundle", entry_path.display());
+        }
+
+        if entry_path == Path::new(MANIFEST_ENTRY) {
+            manifest = Some(serde_json::from_reader(&mut entry)?);
+        } else if entry_path.starts_with(ENTRIES_DIR) {
+            entry.unpack_in(staging_dir)?;
+        } else {
+            anyhow::bail!("Unexpected entry {} in bundle", entry_path.display());
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| anyhow::anyhow!("The bundle has no manifest"))?;
+    if manifest.version != BUNDLE_VERSION {
+        anyhow::bail!(
+            "Unsupported bundle version {}, expected {}",
+            manifest.version,
+            BUNDLE_VERSION
+        );
+    }
+    Ok(manifest)
+}
+
+/// Where the outputs and terminal output of `hash` were unpacked to
+pub fn staged_paths(staging_dir: &Path, hash: &str) -> (PathBuf, PathBuf) {
+    let entry_dir = staging_dir.join(ENTRIES_DIR).join(hash);
+    (
+        entry_dir.join(OUTPUTS_DIR),
+        entry_dir.join(TERMINAL_OUTPUT_ENTRY),
+    )
+}
+
+/// Checks that the unpacked files of `entry` match the digests in the manifest.
+/// Returns why the entry cannot be imported otherwise.
+pub fn verify_entry(staging_dir: &Path, entry: &BundleEntry) -> Result<(), String> {
+    if entry.hash.is_empty() || !entry.hash.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("invalid hash".to_string());
+    }
+
+    let (outputs_path, terminal_output_path) = staged_paths(staging_dir, &entry.hash);
+    let terminal_output = fs::read(&terminal_output_path)
+        .map_err(|_| "the terminal output is missing".to_string())?;
+    if sha256(&terminal_output) != entry.terminal_output {
+        return Err("the terminal output does not match its digest".to_string());
+    }
+
+    let outputs = if outputs_path.is_dir() {
+        digest_directory(&outputs_path).map_err(|e| e.to_string())?
+    } else {
+        BTreeMap::new()
+    };
+    if outputs != entry.outputs {
+        return Err("the outputs do not match their digests".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use assert_fs::prelude::*;
+
+    fn source(temp: &TempDir, hash: &str) -> BundleSource {
+        temp.child(format!("{}/dist/main.js", hash))
+            .write_str("console.log('hi')")
+            .unwrap();
+        temp.child(format!("terminalOutputs/{}", hash))
+            .write_str("built")
+            .unwrap();
+        BundleSource {
+            entry: CacheEntryInfo {
+                hash: hash.to_string(),
+                code: 0,
+                size: 22,
+                created_at: 1_700_000_000_000,
+                accessed_at: 1_700_000_000_000,
+                project: Some("app".to_string()),
+                target: Some("build".to_string()),
+                configuration: None,
+                hash_algorithm: HashAlgorithm::Blake3,
+            },
+            outputs_path: temp.path().join(hash),
+            terminal_output_path: temp.path().join("terminalOutputs").join(hash),
+        }
+    }
+
+    #[test]
+    fn should_roundtrip_entries() {
+        let temp = TempDir::new().unwrap();
+        let bundle = write_bundle(Vec::new(), &[source(&temp, "123")]).unwrap();
+
+        let staging = TempDir::new().unwrap();
+        let manifest = read_bundle(&bundle[..], staging.path()).unwrap();
+
+        assert_eq!(manifest.entries.len(), 1);
+        let entry = &manifest.entries[0];
+        assert_eq!(entry.project.as_deref(), Some("app"));
+        assert_eq!(entry.created_at, 1_700_000_000_000);
+        assert_eq!(entry.hash_algorithm, HashAlgorithm::Blake3);
+        assert_eq!(verify_entry(staging.path(), entry), Ok(()));
+        assert_eq!(
+            fs::read_to_string(staged_paths(staging.path(), "123").0.join("dist/main.js")).unwrap(),
+            "console.log('hi')"
+        );
+    }
+
+    #[test]
+    fn should_reject_tampered_entries() {
+        let temp = TempDir::new().unwrap();
+        let bundle = write_bundle(Vec::new(), &[source(&temp, "123")]).unwrap();
+
+        let staging = TempDir::new().unwrap();
+        let mut manifest = read_bundle(&bundle[..], staging.path()).unwrap();
+        staging
+            .child("entries/123/outputs/dist/main.js")
+            .write_str("tampered")
+            .unwrap();
+
+        assert!(verify_entry(staging.path(), &manifest.entries[0]).is_err());
+
+        manifest.entries[0].hash = "../123".to_string();
+        assert_eq!(
+            verify_entry(staging.path(), &manifest.entries[0]),
+            Err("invalid hash".to_string())
+        );
+    }
+}
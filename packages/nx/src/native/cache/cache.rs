@@ -1,17 +1,33 @@
-use std::fs::{create_dir_all, read_to_string, write};
+use std::fs::{File, create_dir_all, read_to_string, remove_file, rename, write};
+use std::io::{BufReader, BufWriter};
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tracing::{debug, trace};
 
 use fs_extra::remove_items;
 use napi::bindgen_prelude::*;
 use regex::Regex;
-use rusqlite::params;
+use rusqlite::{params, params_from_iter};
 use sysinfo::Disks;
 
+use crate::native::cache::bundle::{
+    BundleImportResult, BundleManifest, BundleSource, SkippedBundleEntry, read_bundle,
+    staged_paths, verify_entry, write_bundle,
+};
+use crate::native::cache::content_store::ContentStore;
+use crate::native::cache::eviction::{
+    CacheEntry, CacheEvictionOptions, EvictionCandidate, EvictionPolicy, KeepLastPerTarget,
+    MAX_AGE_SECONDS, MaxAge, ProjectQuota, Ranking, SizeLimit, fill_in_costs, plan_eviction,
+};
 use crate::native::cache::expand_outputs::_expand_outputs;
-use crate::native::cache::file_ops::_copy;
+use crate::native::cache::file_ops::{_copy, _restore, RestoreResult};
+use crate::native::cache::inspect::{
+    CacheEntryDetails, CacheEntryFilter, CacheEntryInfo, entry_from_row, list_entries_query,
+    list_files,
+};
 use crate::native::db::connection::NxDbConnection;
+use crate::native::db::migrations::{Migration, migrate};
+use crate::native::hasher::HashAlgorithm;
 use crate::native::utils::Normalize;
 
 #[napi(object)]
@@ -28,9 +44,11 @@ pub struct NxCache {
     pub cache_directory: String,
     workspace_root: PathBuf,
     cache_path: PathBuf,
+    content_store: ContentStore,
     db: External<NxDbConnection>,
     link_task_details: bool,
     max_cache_size: i64,
+    eviction_options: CacheEvictionOptions,
 }
 
 #[napi]
@@ -42,6 +60,7 @@ impl NxCache {
         db_connection: External<NxDbConnection>,
         link_task_details: Option<bool>,
         max_cache_size: Option<i64>,
+        eviction_options: Option<CacheEvictionOptions>,
     ) -> anyhow::Result<Self> {
         let cache_path = PathBuf::from(&cache_path);
 
@@ -50,13 +69,15 @@ impl NxCache {
 
         let max_cache_size = max_cache_size.unwrap_or(0);
 
-        let r = Self {
+        let mut r = Self {
             db: db_connection,
             workspace_root: PathBuf::from(workspace_root),
             cache_directory: cache_path.to_normalized_string(),
+            content_store: ContentStore::new(&cache_path)?,
             cache_path,
             link_task_details: link_task_details.unwrap_or(true),
             max_cache_size,
+            eviction_options: eviction_options.unwrap_or_default(),
         };
 
         r.setup()?;
@@ -64,14 +85,15 @@ impl NxCache {
         Ok(r)
     }
 
-    fn setup(&self) -> anyhow::Result<()> {
-        let query = if self.link_task_details {
+    fn setup(&mut self) -> anyhow::Result<()> {
+        let create_cache_outputs = if self.link_task_details {
             "CREATE TABLE IF NOT EXISTS cache_outputs (
                     hash    TEXT PRIMARY KEY NOT NULL,
                     code   INTEGER NOT NULL,
                     size   INTEGER NOT NULL,
                     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
                     accessed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    hash_algorithm TEXT NOT NULL DEFAULT 'Xxh3',
                     FOREIGN KEY (hash) REFERENCES task_details (hash)
               );
             "
@@ -81,13 +103,34 @@ impl NxCache {
                     code   INTEGER NOT NULL,
                     size   INTEGER NOT NULL,
                     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                    accessed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                    accessed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    hash_algorithm TEXT NOT NULL DEFAULT 'Xxh3'
                 );
                 "
         };
 
-        self.db.execute(query, []).map_err(anyhow::Error::from)?;
-        Ok(())
+        migrate(
+            &mut self.db,
+            "cache",
+            &[
+                Migration {
+                    version: 1,
+                    description: "Create cache_outputs",
+                    sql: create_cache_outputs,
+                },
+                // Every manifest entry pointing at a blob holds one reference to it
+                Migration {
+                    version: 2,
+                    description: "Create cache_blobs",
+                    sql: "CREATE TABLE IF NOT EXISTS cache_blobs (
+                            blob    TEXT PRIMARY KEY NOT NULL,
+                            size   INTEGER NOT NULL,
+                            ref_count   INTEGER NOT NULL DEFAULT 0
+                        );
+                    ",
+                },
+            ],
+        )
     }
 
     #[napi]
@@ -140,6 +183,9 @@ impl NxCache {
         trace!("PUT {}", &hash);
         let task_dir = self.cache_path.join(&hash);
 
+        // Release the blobs of a previous entry for this hash
+        self.release_blobs(&hash)?;
+
         // Remove the task directory
         //
         trace!("Removing task directory: {:?}", &task_dir);
@@ -183,6 +229,7 @@ impl NxCache {
             copied_files, total_size
         );
 
+        self.store_blobs(&hash, &task_dir)?;
         self.record_to_cache(hash.clone(), code, total_size)?;
         debug!("PUT {} {:?}", &hash, start.elapsed());
         Ok(())
@@ -209,6 +256,12 @@ impl NxCache {
         }
         write(self.get_task_outputs_path(hash.clone()), terminal_output)?;
 
+        let task_dir = self.cache_path.join(&hash);
+        if task_dir.exists() {
+            self.release_blobs(&hash)?;
+            self.store_blobs(&hash, &task_dir)?;
+        }
+
         let code: i16 = result.code;
         self.record_to_cache(hash, code, size)?;
         Ok(())
@@ -224,20 +277,70 @@ impl NxCache {
             .to_normalized_string()
     }
 
+    /// Moves the files of `task_dir` into the content store and takes a
+    /// reference on every blob listed in the resulting manifest
+    fn store_blobs(&self, hash: &str, task_dir: &Path) -> anyhow::Result<()> {
+        let manifest = self.content_store.deduplicate(task_dir)?;
+        for entry in manifest.stored_files() {
+            self.db.execute(
+                "INSERT INTO cache_blobs (blob, size, ref_count) VALUES (?1, ?2, 1)
+                    ON CONFLICT(blob) DO UPDATE SET ref_count = ref_count + 1",
+                params![entry.blob, entry.size as i64],
+            )?;
+        }
+        self.content_store.write_manifest(hash, &manifest)?;
+        trace!(
+            "Stored {} blobs for {}",
+            manifest.stored_files().count(),
+            hash
+        );
+        Ok(())
+    }
+
+    /// Drops the references held by the manifest of `hash` and removes
+    /// any blob which is no longer referenced by another task hash
+    fn release_blobs(&self, hash: &str) -> anyhow::Result<()> {
+        let Some(manifest) = self.content_store.read_manifest(hash) else {
+            return Ok(());
+        };
+        for entry in manifest.stored_files() {
+            // Each statement updates the count atomically, so references taken by other
+            // processes in the meantime are never lost
+            let ref_count = self.db.query_row(
+                "UPDATE cache_blobs SET ref_count = ref_count - 1 WHERE blob = ?1 RETURNING ref_count",
+                params![entry.blob],
+                |row| row.get::<_, i64>(0),
+            )?;
+            if ref_count.is_none_or(|ref_count| ref_count > 0) {
+                continue;
+            }
+            let unreferenced_blob = self.db.query_row(
+                "DELETE FROM cache_blobs WHERE blob = ?1 AND ref_count = 0 RETURNING blob",
+                params![entry.blob],
+                |row| row.get::<_, String>(0),
+            )?;
+            if let Some(blob) = unreferenced_blob {
+                self.content_store.remove_blob(&blob)?;
+            }
+        }
+        self.content_store.remove_manifest(hash)
+    }
+
     fn record_to_cache(&self, hash: String, code: i16, size: i64) -> anyhow::Result<()> {
         trace!("Recording to cache: {}, {}, {}", &hash, code, size);
         self.db.execute(
-            "INSERT OR REPLACE INTO cache_outputs (hash, code, size) VALUES (?1, ?2, ?3)",
-            params![hash, code, size],
+            "INSERT OR REPLACE INTO cache_outputs (hash, code, size, hash_algorithm) VALUES (?1, ?2, ?3, ?4)",
+            params![hash, code, size, HashAlgorithm::current().name()],
         )?;
-        if self.max_cache_size != 0 {
-            self.ensure_cache_size_within_limit()?
-        }
-        Ok(())
+        self.ensure_cache_size_within_limit()
     }
 
     #[napi]
     pub fn get_cache_size(&self) -> anyhow::Result<i64> {
+        Ok(self.get_outputs_size()? - self.get_shared_blobs_size()?)
+    }
+
+    fn get_outputs_size(&self) -> anyhow::Result<i64> {
         self.db
             .query_row("SELECT SUM(size) FROM cache_outputs", [], |row| {
                 row.get::<_, Option<i64>>(0)
@@ -255,43 +358,361 @@ impl NxCache {
             .unwrap_or(Ok(0))
     }
 
+    /// The sizes recorded in `cache_outputs` count every file of every entry.
+    /// Blobs referenced more than once are only stored once on disk.
+    fn get_shared_blobs_size(&self) -> anyhow::Result<i64> {
+        Ok(self
+            .db
+            .query_row(
+                "SELECT COALESCE(SUM(size * (ref_count - 1)), 0) FROM cache_blobs WHERE ref_count > 1",
+                [],
+                |row| row.get::<_, i64>(0),
+            )?
+            .unwrap_or(0))
+    }
+
+    /// Lists the entries in the cache matching `filter`
+    #[napi]
+    pub fn list_entries(
+        &self,
+        filter: Option<CacheEntryFilter>,
+    ) -> anyhow::Result<Vec<CacheEntryInfo>> {
+        let (query, params) =
+            list_entries_query(&filter.unwrap_or_default(), self.has_table("task_details")?);
+        Ok(self
+            .db
+            .prepare(&query)?
+            .query_map(params_from_iter(params.iter()), entry_from_row)?
+            .filter_map(anyhow::Result::ok)
+            .collect())
+    }
+
+    /// Returns the metadata and the stored files of the entry for `hash`
+    #[napi]
+    pub fn get_entry_details(&self, hash: String) -> anyhow::Result<Option<CacheEntryDetails>> {
+        let Some(entry) = self
+            .list_entries(Some(CacheEntryFilter {
+                hashes: Some(vec![hash.clone()]),
+                ..Default::default()
+            }))?
+            .pop()
+        else {
+            return Ok(None);
+        };
+
+        let task_dir = self.cache_path.join(&hash);
+        let manifest = self.content_store.read_manifest(&hash);
+        Ok(Some(CacheEntryDetails {
+            entry,
+            outputs_path: task_dir.to_normalized_string(),
+            terminal_output_path: self
+                .get_task_outputs_path_internal(&hash)
+                .to_normalized_string(),
+            files: list_files(&task_dir, manifest.as_ref()),
+        }))
+    }
+
+    /// Removes the entries matching `filter` and returns them
+    #[napi]
+    pub fn remove_entries(&self, filter: CacheEntryFilter) -> anyhow::Result<Vec<CacheEntryInfo>> {
+        let entries = self.list_entries(Some(filter))?;
+        for entry in entries.iter() {
+            self.remove_entry(&entry.hash)?;
+        }
+        debug!("Removed {} cache entries", entries.len());
+        Ok(entries)
+    }
+
+    /// Writes the entries matching `filter` into a bundle at `bundle_path`,
+    /// which can be imported into the cache of another workspace
+    #[napi]
+    pub fn export_bundle(
+        &self,
+        bundle_path: String,
+        filter: Option<CacheEntryFilter>,
+    ) -> anyhow::Result<Vec<CacheEntryInfo>> {
+        let start = Instant::now();
+        let sources = self
+            .list_entries(filter)?
+            .into_iter()
+            .map(|entry| BundleSource {
+                outputs_path: self.cache_path.join(&entry.hash),
+                terminal_output_path: self.get_task_outputs_path_internal(&entry.hash),
+                entry,
+            })
+            // Entries without a terminal output cannot be restored
+            .filter(|source| source.terminal_output_path.is_file())
+            .collect::<Vec<_>>();
+
+        // Write next to the bundle so a partial bundle is never left behind
+        let bundle_path = PathBuf::from(bundle_path);
+        let mut tmp_path = bundle_path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let written = File::create(&tmp_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| write_bundle(BufWriter::new(file), &sources))
+            .and_then(|writer| Ok(writer.into_inner()?.sync_all()?));
+        if let Err(e) = written {
+            let _ = remove_file(&tmp_path);
+            return Err(e);
+        }
+        rename(&tmp_path, &bundle_path)?;
+
+        debug!(
+            "Exported {} cache entries to {:?} in {:?}",
+            sources.len(),
+            &bundle_path,
+            start.elapsed()
+        );
+        Ok(sources.into_iter().map(|source| source.entry).collect())
+    }
+
+    /// Adds the entries of the bundle at `bundle_path` to the cache.
+    /// Entries which are already cached or fail validation are skipped.
+    #[napi]
+    pub fn import_bundle(&self, bundle_path: String) -> anyhow::Result<BundleImportResult> {
+        let start = Instant::now();
+        let staging_dir = self
+            .cache_path
+            .join(format!(".bundle-{}-{}", std::process::id(), now()));
+        create_dir_all(&staging_dir)?;
+
+        let result = File::open(&bundle_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| read_bundle(BufReader::new(file), &staging_dir))
+            .and_then(|manifest| self.import_bundle_entries(&staging_dir, manifest));
+        remove_items(&[&staging_dir])?;
+        let result = result?;
+
+        self.ensure_cache_size_within_limit()?;
+        debug!(
+            "Imported {} cache entries from {}, skipped {} in {:?}",
+            result.imported.len(),
+            &bundle_path,
+            result.skipped.len(),
+            start.elapsed()
+        );
+        Ok(result)
+    }
+
+    fn import_bundle_entries(
+        &self,
+        staging_dir: &Path,
+        manifest: BundleManifest,
+    ) -> anyhow::Result<BundleImportResult> {
+        let has_task_details = self.has_table("task_details")?;
+        let mut result = BundleImportResult::default();
+
+        for entry in manifest.entries {
+            let skip = |reason: &str| SkippedBundleEntry {
+                hash: entry.hash.clone(),
+                reason: reason.to_string(),
+            };
+            if let Err(reason) = verify_entry(staging_dir, &entry) {
+                trace!("Skipping {}: {}", &entry.hash, &reason);
+                result.skipped.push(skip(&reason));
+                continue;
+            }
+            let is_cached = self
+                .db
+                .query_row(
+                    "SELECT EXISTS (SELECT 1 FROM cache_outputs WHERE hash = ?1)",
+                    params![entry.hash],
+                    |row| row.get::<_, bool>(0),
+                )?
+                .unwrap_or(false);
+            if is_cached {
+                result.skipped.push(skip("already cached"));
+                continue;
+            }
+
+            // Directories left behind without a record are replaced
+            let task_dir = self.cache_path.join(&entry.hash);
+            self.release_blobs(&entry.hash)?;
+            remove_items(&[&task_dir])?;
+
+            let (outputs_path, terminal_output_path) = staged_paths(staging_dir, &entry.hash);
+            if outputs_path.is_dir() {
+                rename(&outputs_path, &task_dir)?;
+            } else {
+                create_dir_all(&task_dir)?;
+            }
+            rename(
+                &terminal_output_path,
+                self.get_task_outputs_path_internal(&entry.hash),
+            )?;
+
+            if let (true, Some(project), Some(target)) =
+                (has_task_details, &entry.project, &entry.target)
+            {
+                self.db.execute(
+                    "INSERT OR IGNORE INTO task_details (hash, project, target, configuration) VALUES (?1, ?2, ?3, ?4)",
+                    params![entry.hash, project, target, entry.configuration],
+                )?;
+            }
+            self.store_blobs(&entry.hash, &task_dir)?;
+            self.db.execute(
+                "INSERT INTO cache_outputs (hash, code, size, created_at, hash_algorithm) VALUES (?1, ?2, ?3, datetime(?4 / 1000, 'unixepoch'), ?5)",
+                params![
+                    entry.hash,
+                    entry.code,
+                    entry.size,
+                    entry.created_at,
+                    entry.hash_algorithm.name()
+                ],
+            )?;
+            result.imported.push(entry.hash);
+        }
+
+        Ok(result)
+    }
+
+    /// Only evicts when the cache exceeds its size limit. Project quotas and entries
+    /// kept per target are enforced once per command by `remove_old_cache_records`,
+    /// since checking them loads every cache entry.
     fn ensure_cache_size_within_limit(&self) -> anyhow::Result<()> {
         // 0 is equivalent to being unlimited.
-        if self.max_cache_size == 0 {
+        if self.max_cache_size == 0 || self.get_cache_size()? <= self.max_cache_size {
             return Ok(());
         }
-        let user_specified_max_cache_size = self.max_cache_size;
-        let buffer_amount = (0.1 * user_specified_max_cache_size as f64) as i64;
-        let target_cache_size = user_specified_max_cache_size - buffer_amount;
-
-        let full_cache_size = self.get_cache_size()?;
-        if user_specified_max_cache_size < full_cache_size {
-            let mut cache_size = full_cache_size;
-            let mut stmt = self.db.prepare(
-                "SELECT hash, size FROM cache_outputs ORDER BY accessed_at ASC LIMIT 100",
-            )?;
-            'outer: while cache_size > target_cache_size {
-                let rows = stmt.query_map([], |r| {
-                    let hash: String = r.get(0)?;
-                    let size: i64 = r.get(1)?;
-                    Ok((hash, size))
-                })?;
-                for row in rows {
-                    if let Ok((hash, size)) = row {
-                        cache_size -= size;
-                        self.db
-                            .execute("DELETE FROM cache_outputs WHERE hash = ?1", params![hash])?;
-                        remove_items(&[self.cache_path.join(&hash)])?;
-                    }
-                    // We've deleted enough cache entries to be under the
-                    // target cache size, stop looking for more.
-                    if cache_size < target_cache_size {
-                        break 'outer;
-                    }
-                }
+
+        self.evict_with(&self.eviction_policies(now()), false)?;
+        Ok(())
+    }
+
+    /// The configured policies, followed by the size limit
+    fn eviction_policies(&self, now: i64) -> Vec<Box<dyn EvictionPolicy>> {
+        let mut policies: Vec<Box<dyn EvictionPolicy>> = vec![];
+        if let Some(quotas) = &self.eviction_options.project_quotas {
+            policies.push(Box::new(ProjectQuota {
+                quotas: quotas.clone(),
+            }));
+        }
+        if let Some(count) = self.eviction_options.keep_per_target {
+            policies.push(Box::new(KeepLastPerTarget {
+                count: count as usize,
+            }));
+        }
+        if self.max_cache_size != 0 {
+            let buffer_amount = (0.1 * self.max_cache_size as f64) as i64;
+            policies.push(Box::new(SizeLimit {
+                max_size: self.max_cache_size,
+                target_size: self.max_cache_size - buffer_amount,
+                ranking: if self.eviction_options.cost_aware.unwrap_or(false) {
+                    Ranking::CostAware { now }
+                } else {
+                    Ranking::LeastRecentlyUsed
+                },
+            }));
+        }
+        policies
+    }
+
+    /// Evicts entries which have not been used for a week, followed by the configured
+    /// policies and the size limit. In a dry run nothing is removed.
+    /// Returns the entries which were, or would be, evicted.
+    #[napi]
+    pub fn evict(&self, dry_run: Option<bool>) -> anyhow::Result<Vec<EvictionCandidate>> {
+        let now = now();
+        let mut policies: Vec<Box<dyn EvictionPolicy>> = vec![Box::new(MaxAge {
+            now,
+            max_age: MAX_AGE_SECONDS,
+        })];
+        policies.extend(self.eviction_policies(now));
+        self.evict_with(&policies, dry_run.unwrap_or(false))
+    }
+
+    fn evict_with(
+        &self,
+        policies: &[Box<dyn EvictionPolicy>],
+        dry_run: bool,
+    ) -> anyhow::Result<Vec<EvictionCandidate>> {
+        let mut evicted = vec![];
+        loop {
+            let candidates =
+                plan_eviction(policies, &self.get_cache_entries()?, self.get_cache_size()?);
+            if dry_run || candidates.is_empty() {
+                evicted.extend(candidates);
+                return Ok(evicted);
+            }
+
+            for candidate in candidates.iter() {
+                trace!("Evicting {} ({})", &candidate.hash, &candidate.reason);
+                self.remove_entry(&candidate.hash)?;
             }
+            debug!("Evicted {} cache entries", candidates.len());
+            evicted.extend(candidates);
+            // Blobs shared with other entries are not freed, so the
+            // size limit may need to evict more entries
         }
-        Ok(())
+    }
+
+    fn remove_entry(&self, hash: &str) -> anyhow::Result<()> {
+        self.db
+            .execute("DELETE FROM cache_outputs WHERE hash = ?1", params![hash])?;
+        remove_items(&[
+            self.cache_path.join(hash),
+            self.get_task_outputs_path_internal(hash),
+        ])?;
+        self.release_blobs(hash)
+    }
+
+    /// Every entry with the task it belongs to, ordered from the most to the least recently used
+    fn get_cache_entries(&self) -> anyhow::Result<Vec<CacheEntry>> {
+        // Task details and history are recorded by other tables which may not exist
+        let (details, join) = if self.has_table("task_details")? {
+            (
+                "task_details.project, task_details.target",
+                "LEFT JOIN task_details ON task_details.hash = cache_outputs.hash",
+            )
+        } else {
+            ("NULL, NULL", "")
+        };
+        // Costs are only used to rank entries for cost aware eviction
+        let cost = if self.eviction_options.cost_aware.unwrap_or(false)
+            && self.has_table("task_history")?
+        {
+            "(SELECT MAX(end - start) FROM task_history
+                WHERE task_history.hash = cache_outputs.hash AND status IN ('success', 'failure'))"
+        } else {
+            "NULL"
+        };
+
+        let mut entries = self
+            .db
+            .prepare(&format!(
+                "SELECT cache_outputs.hash, cache_outputs.size,
+                    CAST(strftime('%s', cache_outputs.accessed_at) AS INTEGER), {}, {}
+                    FROM cache_outputs {}
+                    ORDER BY cache_outputs.accessed_at DESC, cache_outputs.hash",
+                details, cost, join
+            ))?
+            .query_map([], |row| {
+                Ok(CacheEntry {
+                    hash: row.get(0)?,
+                    size: row.get(1)?,
+                    accessed_at: row.get(2)?,
+                    project: row.get(3)?,
+                    target: row.get(4)?,
+                    cost: row.get(5)?,
+                })
+            })?
+            .filter_map(anyhow::Result::ok)
+            .collect::<Vec<_>>();
+        fill_in_costs(&mut entries);
+        Ok(entries)
+    }
+
+    fn has_table(&self, name: &str) -> anyhow::Result<bool> {
+        Ok(self
+            .db
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+                params![name],
+                |row| row.get::<_, bool>(0),
+            )?
+            .unwrap_or(false))
     }
 
     #[napi]
@@ -300,6 +721,16 @@ impl NxCache {
         cached_result: CachedResult,
         outputs: Vec<String>,
     ) -> anyhow::Result<i64> {
+        self.restore_files_from_cache(cached_result, outputs)
+            .map(|result| result.size)
+    }
+
+    #[napi]
+    pub fn restore_files_from_cache(
+        &self,
+        cached_result: CachedResult,
+        outputs: Vec<String>,
+    ) -> anyhow::Result<RestoreResult> {
         let outputs_path = Path::new(&cached_result.outputs_path);
 
         let expanded_outputs = _expand_outputs(outputs_path, outputs)?;
@@ -314,52 +745,41 @@ impl NxCache {
         )?;
 
         trace!(
-            "Copying Files from Cache {:?} -> {:?}",
+            "Restoring Files from Cache {:?} -> {:?}",
             &outputs_path, &self.workspace_root
         );
-        let sz = _copy(outputs_path, &self.workspace_root);
+        let restored = _restore(outputs_path, &self.workspace_root);
 
-        match sz {
+        match restored {
             Err(e) => {
                 let kind = underlying_io_error_kind(&e);
                 match kind {
                     Some(std::io::ErrorKind::NotFound) => {
                         trace!("No artifacts to copy: {:?}", e);
-                        Ok(0)
+                        Ok(RestoreResult::default())
                     }
                     _ => {
                         return Err(anyhow::anyhow!("Error copying files from cache: {:?}", e));
                     }
                 }
             }
-            Ok(sz) => {
-                trace!("Copied {} bytes from cache", sz);
-                Ok(sz)
+            Ok(result) => {
+                debug!(
+                    "Restored {} bytes from cache ({:?}: {} reflinked, {} hardlinked, {} copied)",
+                    result.size,
+                    result.strategy,
+                    result.reflinked_files,
+                    result.hardlinked_files,
+                    result.copied_files
+                );
+                Ok(result)
             }
         }
     }
 
     #[napi]
     pub fn remove_old_cache_records(&self) -> anyhow::Result<()> {
-        let outdated_cache = self
-            .db
-            .prepare(
-                "DELETE FROM cache_outputs WHERE accessed_at < datetime('now', '-7 days') RETURNING hash",
-            )?
-            .query_map(params![], |row| {
-                let hash: String = row.get(0)?;
-
-                Ok(vec![
-                    self.cache_path.join(&hash),
-                    self.get_task_outputs_path_internal(&hash),
-                ])
-            })?
-            .filter_map(anyhow::Result::ok)
-            .flatten()
-            .collect::<Vec<_>>();
-
-        remove_items(&outdated_cache)?;
-
+        self.evict(None)?;
         Ok(())
     }
 
@@ -377,7 +797,9 @@ impl NxCache {
             .unwrap_or(false);
 
         if !cache_records_exist {
-            let hash_regex = Regex::new(r"^\d+$").expect("Hash regex is invalid");
+            // Xxh3 hashes are decimal, Xxh128 and Blake3 hashes are hex encoded
+            let hash_regex =
+                Regex::new(r"^(\d+|[0-9a-f]{32}|[0-9a-f]{64})$").expect("Hash regex is invalid");
             let fs_entries = std::fs::read_dir(&self.cache_path).map_err(anyhow::Error::from)?;
 
             for entry in fs_entries {
@@ -400,6 +822,13 @@ impl NxCache {
     }
 }
 
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
 #[napi]
 fn get_default_max_cache_size(cache_path: String) -> i64 {
     let disks = Disks::new_with_refreshed_list();
@@ -0,0 +1,207 @@
+use std::{
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+};
+
+use flate2::Compression;
+
+use super::errors::HttpRemoteCacheErrors;
+
+const GZIP_MAGIC: u8 = 0x1f;
+const ZSTD_MAGIC: u8 = 0x28;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Gzip,
+    Zstd,
+}
+
+/// How cache artifacts are compressed when they are stored.
+///
+/// Artifacts are decompressed based on their leading magic bytes, so gzip artifacts
+/// created by older versions can always be read regardless of this setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveCompression {
+    pub codec: Codec,
+    pub level: i32,
+}
+
+impl Default for ArchiveCompression {
+    fn default() -> Self {
+        Self::gzip()
+    }
+}
+
+impl ArchiveCompression {
+    pub fn gzip() -> Self {
+        Self {
+            codec: Codec::Gzip,
+            level: Compression::default().level() as i32,
+        }
+    }
+
+    pub fn zstd() -> Self {
+        Self {
+            codec: Codec::Zstd,
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+
+    pub fn from_env() -> Result<Self, HttpRemoteCacheErrors> {
+        let compression = match env::var("NX_SELF_HOSTED_REMOTE_CACHE_COMPRESSION")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "gzip" => Self::gzip(),
+            "zstd" => Self::zstd(),
+            other => {
+                return Err(HttpRemoteCacheErrors::Misconfigured(format!(
+                    "Unsupported compression {}, expected gzip or zstd",
+                    other
+                )));
+            }
+        };
+
+        match env::var("NX_SELF_HOSTED_REMOTE_CACHE_COMPRESSION_LEVEL") {
+            Ok(level) => {
+                let level = level.trim().parse().map_err(|_| {
+                    HttpRemoteCacheErrors::Misconfigured(format!(
+                        "Invalid compression level {}",
+                        level
+                    ))
+                })?;
+                compression.with_level(level)
+            }
+            Err(_) => Ok(compression),
+        }
+    }
+
+    pub fn with_level(self, level: i32) -> Result<Self, HttpRemoteCacheErrors> {
+        let levels = match self.codec {
+            Codec::Gzip => 0..=9,
+            Codec::Zstd => 1..=22,
+        };
+        if !levels.contains(&level) {
+            return Err(HttpRemoteCacheErrors::Misconfigured(format!(
+                "Compression level {} is out of range for {:?}, expected {} to {}",
+                level,
+                self.codec,
+                levels.start(),
+                levels.end()
+            )));
+        }
+        Ok(Self { level, ..self })
+    }
+
+    /// The media type of artifacts compressed with this codec
+    pub fn content_type(&self) -> &'static str {
+        match self.codec {
+            // Kept for servers which predate zstd support
+            Codec::Gzip => "application/octet-stream",
+            Codec::Zstd => "application/zstd",
+        }
+    }
+
+    pub fn encoder<W: Write>(&self, writer: W) -> io::Result<ArchiveEncoder<W>> {
+        Ok(match self.codec {
+            Codec::Gzip => ArchiveEncoder::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                Compression::new(self.level as u32),
+            )),
+            Codec::Zstd => {
+                let mut encoder = zstd::Encoder::new(writer, self.level)?;
+                encoder.multithread(num_cpus())?;
+                ArchiveEncoder::Zstd(encoder)
+            }
+        })
+    }
+}
+
+fn num_cpus() -> u32 {
+    std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(1)
+}
+
+pub enum ArchiveEncoder<W: Write> {
+    Gzip(flate2::write::GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> ArchiveEncoder<W> {
+    /// Writes the remaining compressed data and returns the inner writer
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Self::Gzip(encoder) => encoder.finish(),
+            Self::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for ArchiveEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Returns a reader which decompresses `content` with the codec it was compressed with
+pub fn decoder<'a>(content: impl Read + 'a) -> io::Result<Box<dyn Read + 'a>> {
+    let mut content = BufReader::new(content);
+    let magic = content.fill_buf()?.first().copied();
+    Ok(match magic {
+        Some(ZSTD_MAGIC) => Box::new(zstd::Decoder::with_buffer(content)?),
+        Some(GZIP_MAGIC) | None => Box::new(flate2::read::GzDecoder::new(content)),
+        Some(other) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown cache artifact format (0x{:02x})", other),
+            ));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(compression: ArchiveCompression) -> Vec<u8> {
+        let mut encoder = compression.encoder(Vec::new()).unwrap();
+        encoder.write_all(b"content").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decompressed = Vec::new();
+        decoder(&compressed[..])
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        decompressed
+    }
+
+    #[test]
+    fn should_detect_the_codec_when_decompressing() {
+        assert_eq!(roundtrip(ArchiveCompression::gzip()), b"content");
+        assert_eq!(roundtrip(ArchiveCompression::zstd()), b"content");
+        assert_eq!(
+            roundtrip(ArchiveCompression::zstd().with_level(19).unwrap()),
+            b"content"
+        );
+    }
+
+    #[test]
+    fn should_validate_compression_levels() {
+        assert!(ArchiveCompression::gzip().with_level(9).is_ok());
+        assert!(ArchiveCompression::gzip().with_level(10).is_err());
+        assert!(ArchiveCompression::zstd().with_level(0).is_err());
+    }
+}
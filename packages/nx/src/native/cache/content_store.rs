@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, trace};
+use walkdir::WalkDir;
+
+use crate::native::hasher::{HashAlgorithm, hash_file_path};
+use crate::native::utils::Normalize;
+
+const MANIFESTS_DIR: &str = "manifests";
+
+/// A single file recorded in a cache manifest
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// The hash of the file, which names its blob
+    pub blob: String,
+    pub size: u64,
+    /// Whether the file is a hardlink to its blob. Files which could not be linked keep
+    /// their own content and hold no reference on the blob.
+    #[serde(default = "is_stored_by_default")]
+    pub stored: bool,
+}
+
+fn is_stored_by_default() -> bool {
+    true
+}
+
+/// Describes which blobs make up the outputs stored for a task hash.
+/// Keys are paths relative to the task directory.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct CacheManifest {
+    pub files: BTreeMap<String, ManifestEntry>,
+    /// The algorithm which hashed the files, see [`HashAlgorithm::name`]
+    #[serde(default)]
+    pub hash_algorithm: Option<String>,
+    /// Whether every file of the task directory is listed, so the manifest can stand in
+    /// for the files themselves
+    #[serde(default)]
+    pub complete: bool,
+}
+
+impl CacheManifest {
+    /// The files which are hardlinks to a blob, and so hold a reference on it
+    pub fn stored_files(&self) -> impl Iterator<Item = &ManifestEntry> {
+        self.files.values().filter(|entry| entry.stored)
+    }
+}
+
+/// Content-addressed storage for cached output files.
+///
+/// Every file is stored once under `blobs/<prefix>/<file hash>` and the task
+/// directories contain hardlinks to those blobs, so byte-identical outputs
+/// across task hashes only take up space once.
+pub struct ContentStore {
+    blobs_path: PathBuf,
+    manifests_path: PathBuf,
+}
+
+impl ContentStore {
+    pub fn new(cache_path: &Path) -> anyhow::Result<Self> {
+        let blobs_path = cache_path.join("blobs");
+        let manifests_path = cache_path.join(MANIFESTS_DIR);
+
+        fs::create_dir_all(&blobs_path)?;
+        fs::create_dir_all(&manifests_path)?;
+
+        Ok(Self {
+            blobs_path,
+            manifests_path,
+        })
+    }
+
+    pub fn blob_path(&self, blob: &str) -> PathBuf {
+        let prefix = &blob[..blob.len().min(2)];
+        self.blobs_path.join(prefix).join(blob)
+    }
+
+    fn manifest_path(&self, hash: &str) -> PathBuf {
+        self.manifests_path.join(format!("{}.json", hash))
+    }
+
+    /// Moves every regular file in `task_dir` into the blob store and replaces it
+    /// with a hardlink to the stored blob. Files that cannot be linked are left
+    /// untouched and are recorded in the manifest as not stored.
+    pub fn deduplicate(&self, task_dir: &Path) -> anyhow::Result<CacheManifest> {
+        let mut manifest = CacheManifest {
+            hash_algorithm: Some(HashAlgorithm::current().name().to_string()),
+            complete: true,
+            ..Default::default()
+        };
+
+        for entry in WalkDir::new(task_dir).follow_links(false) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let path = entry.path();
+            let Some(blob) = hash_file_path(path) else {
+                manifest.complete = false;
+                continue;
+            };
+            let metadata = entry.metadata()?;
+            let blob_path = self.blob_path(&blob);
+
+            let linked = if blob_path.exists() {
+                // Hardlinks share permissions, so only reuse a blob if they match
+                if fs::metadata(&blob_path)?.permissions() != metadata.permissions() {
+                    trace!("Permissions differ for {:?}, not deduplicating", path);
+                    false
+                } else {
+                    fs::remove_file(path)?;
+                    fs::hard_link(&blob_path, path)
+                        .or_else(|_| fs::copy(&blob_path, path).map(|_| ()))?;
+                    true
+                }
+            } else {
+                fs::create_dir_all(blob_path.parent().unwrap_or(&self.blobs_path))?;
+                fs::hard_link(path, &blob_path)
+                    .inspect_err(|e| trace!("Unable to link {:?} into blob store: {:?}", path, e))
+                    .is_ok()
+            };
+
+            let relative_path = path.strip_prefix(task_dir)?.to_normalized_string();
+            manifest.files.insert(
+                relative_path,
+                ManifestEntry {
+                    blob,
+                    size: metadata.len(),
+                    stored: linked,
+                },
+            );
+        }
+
+        debug!(
+            "Deduplicated {} of {} files from {:?}",
+            manifest.stored_files().count(),
+            manifest.files.len(),
+            task_dir
+        );
+        Ok(manifest)
+    }
+
+    pub fn write_manifest(&self, hash: &str, manifest: &CacheManifest) -> anyhow::Result<()> {
+        fs::write(self.manifest_path(hash), serde_json::to_vec(manifest)?)?;
+        Ok(())
+    }
+
+    pub fn read_manifest(&self, hash: &str) -> Option<CacheManifest> {
+        read_manifest_file(&self.manifest_path(hash), hash)
+    }
+
+    pub fn remove_manifest(&self, hash: &str) -> anyhow::Result<()> {
+        let path = self.manifest_path(hash);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_blob(&self, blob: &str) -> anyhow::Result<()> {
+        let path = self.blob_path(blob);
+        if path.exists() {
+            trace!("Removing unreferenced blob {:?}", &path);
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the manifest of `hash` from the cache at `cache_path`, without setting up a store
+pub fn read_cached_manifest(cache_path: &Path, hash: &str) -> Option<CacheManifest> {
+    read_manifest_file(
+        &cache_path
+            .join(MANIFESTS_DIR)
+            .join(format!("{}.json", hash)),
+        hash,
+    )
+}
+
+fn read_manifest_file(path: &Path, hash: &str) -> Option<CacheManifest> {
+    let content = fs::read(path).ok()?;
+    serde_json::from_slice(&content)
+        .inspect_err(|e| trace!("Invalid manifest for {}: {:?}", hash, e))
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_fs::TempDir;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn should_store_identical_files_once() {
+        let temp = TempDir::new().unwrap();
+        let store = ContentStore::new(temp.path()).unwrap();
+        temp.child("1/dist/main.js").write_str("content").unwrap();
+        temp.child("2/dist/main.js").write_str("content").unwrap();
+
+        let first = store.deduplicate(&temp.join("1")).unwrap();
+        let second = store.deduplicate(&temp.join("2")).unwrap();
+
+        let blob = &first.files["dist/main.js"].blob;
+        assert_eq!(blob, &second.files["dist/main.js"].blob);
+        assert!(store.blob_path(blob).exists());
+
+        let blob_dir = store.blob_path(blob).parent().unwrap().to_path_buf();
+        assert_eq!(fs::read_dir(blob_dir).unwrap().count(), 1);
+        assert_eq!(
+            fs::read_to_string(temp.join("2/dist/main.js")).unwrap(),
+            "content"
+        );
+    }
+
+    #[test]
+    fn should_round_trip_manifests() {
+        let temp = TempDir::new().unwrap();
+        let store = ContentStore::new(temp.path()).unwrap();
+        temp.child("1/main.js").write_str("content").unwrap();
+
+        let manifest = store.deduplicate(&temp.join("1")).unwrap();
+        store.write_manifest("1", &manifest).unwrap();
+
+        assert_eq!(store.read_manifest("1"), Some(manifest));
+
+        store.remove_manifest("1").unwrap();
+        assert_eq!(store.read_manifest("1"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_list_files_which_are_not_stored() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let store = ContentStore::new(temp.path()).unwrap();
+        temp.child("1/main.js").write_str("content").unwrap();
+        temp.child("2/main.js").write_str("content").unwrap();
+        fs::set_permissions(temp.join("2/main.js"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        store.deduplicate(&temp.join("1")).unwrap();
+        let manifest = store.deduplicate(&temp.join("2")).unwrap();
+
+        assert!(manifest.complete);
+        assert!(!manifest.files["main.js"].stored);
+        assert_eq!(manifest.stored_files().count(), 0);
+    }
+}
@@ -12,6 +12,8 @@ pub enum HttpRemoteCacheErrors {
     Misconfigured(String),
     #[error("Failed to send request: {0}")]
     RequestError(String),
+    #[error("Cache artifact failed integrity verification: {0}")]
+    IntegrityError(String),
 }
 
 pub type AsyncHttpRemoteCacheErrors = Pin<Box<dyn Future<Output = HttpRemoteCacheErrors>>>;
@@ -59,6 +61,7 @@ impl AsRef<str> for HttpRemoteCacheErrors {
             HttpRemoteCacheErrors::Unauthorized(_) => "Unauthorized",
             HttpRemoteCacheErrors::Misconfigured(_) => "Misconfigured",
             HttpRemoteCacheErrors::RequestError(_) => "RequestError",
+            HttpRemoteCacheErrors::IntegrityError(_) => "IntegrityError",
             // _ => "Error",
         }
     }
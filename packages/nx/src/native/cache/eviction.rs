@@ -0,0 +1,371 @@
+use std::collections::{HashMap, HashSet};
+
+/// How long an entry is kept after it was last used
+pub const MAX_AGE_SECONDS: i64 = 7 * 24 * 60 * 60;
+
+#[napi(object)]
+#[derive(Default, Clone, Debug)]
+pub struct CacheEvictionOptions {
+    /// Maximum size in bytes of the cached outputs of each project
+    pub project_quotas: Option<HashMap<String, i64>>,
+    /// Number of most recently used entries kept for each target of a project
+    pub keep_per_target: Option<u32>,
+    /// When the cache is over its size limit, evict entries which were quick
+    /// to compute before entries which took long, based on the task history
+    pub cost_aware: Option<bool>,
+}
+
+/// A cache entry which is evicted, or would be evicted in a dry run
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvictionCandidate {
+    pub hash: String,
+    pub size: i64,
+    pub project: Option<String>,
+    pub target: Option<String>,
+    /// The name of the policy which selected the entry
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CacheEntry {
+    pub hash: String,
+    pub size: i64,
+    /// Seconds since the unix epoch
+    pub accessed_at: i64,
+    pub project: Option<String>,
+    pub target: Option<String>,
+    /// How long the task took to compute in milliseconds, if it is known
+    pub cost: Option<f64>,
+}
+
+/// Decides which cache entries are evicted
+pub trait EvictionPolicy {
+    fn name(&self) -> &'static str;
+
+    /// Returns the indices of the entries to evict. `entries` are ordered from the
+    /// most to the least recently used, `cache_size` is the current size of the cache.
+    fn select(&self, entries: &[&CacheEntry], cache_size: i64) -> Vec<usize>;
+}
+
+/// Evicts entries which have not been used for a while
+pub struct MaxAge {
+    pub now: i64,
+    pub max_age: i64,
+}
+
+impl EvictionPolicy for MaxAge {
+    fn name(&self) -> &'static str {
+        "max-age"
+    }
+
+    fn select(&self, entries: &[&CacheEntry], _: i64) -> Vec<usize> {
+        let cutoff = self.now - self.max_age;
+        entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.accessed_at < cutoff)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+/// Keeps the most recently used entries of each project within its quota
+pub struct ProjectQuota {
+    pub quotas: HashMap<String, i64>,
+}
+
+impl EvictionPolicy for ProjectQuota {
+    fn name(&self) -> &'static str {
+        "project-quota"
+    }
+
+    fn select(&self, entries: &[&CacheEntry], _: i64) -> Vec<usize> {
+        let mut used: HashMap<&str, i64> = HashMap::new();
+        let mut selected = vec![];
+        for (i, entry) in entries.iter().enumerate() {
+            let Some(project) = entry.project.as_deref() else {
+                continue;
+            };
+            let Some(quota) = self.quotas.get(project) else {
+                continue;
+            };
+            let used = used.entry(project).or_default();
+            if *used + entry.size > *quota {
+                selected.push(i);
+            } else {
+                *used += entry.size;
+            }
+        }
+        selected
+    }
+}
+
+/// Keeps the last `count` entries of every target
+pub struct KeepLastPerTarget {
+    pub count: usize,
+}
+
+impl EvictionPolicy for KeepLastPerTarget {
+    fn name(&self) -> &'static str {
+        "keep-per-target"
+    }
+
+    fn select(&self, entries: &[&CacheEntry], _: i64) -> Vec<usize> {
+        let mut kept: HashMap<(&str, &str), usize> = HashMap::new();
+        let mut selected = vec![];
+        for (i, entry) in entries.iter().enumerate() {
+            let (Some(project), Some(target)) = (entry.project.as_deref(), entry.target.as_deref())
+            else {
+                continue;
+            };
+            let kept = kept.entry((project, target)).or_default();
+            if *kept >= self.count {
+                selected.push(i);
+            } else {
+                *kept += 1;
+            }
+        }
+        selected
+    }
+}
+
+/// The order in which [`SizeLimit`] evicts entries
+pub enum Ranking {
+    LeastRecentlyUsed,
+    /// Ranks entries by how long they took to compute, discounted by how long ago they were used
+    CostAware {
+        now: i64,
+    },
+}
+
+/// Shrinks the cache to `target_size` once it grows beyond `max_size`
+pub struct SizeLimit {
+    pub max_size: i64,
+    pub target_size: i64,
+    pub ranking: Ranking,
+}
+
+impl EvictionPolicy for SizeLimit {
+    fn name(&self) -> &'static str {
+        match self.ranking {
+            Ranking::LeastRecentlyUsed => "size-limit",
+            Ranking::CostAware { .. } => "size-limit-cost-aware",
+        }
+    }
+
+    fn select(&self, entries: &[&CacheEntry], cache_size: i64) -> Vec<usize> {
+        if cache_size <= self.max_size {
+            return vec![];
+        }
+
+        let mut ranked = (0..entries.len()).rev().collect::<Vec<_>>();
+        if let Ranking::CostAware { now } = self.ranking {
+            let value = |entry: &CacheEntry| {
+                let hours_since_use = (now - entry.accessed_at).max(0) as f64 / 3600.0;
+                entry.cost.unwrap_or(0.0) / (1.0 + hours_since_use)
+            };
+            // Stable, so entries of equal value are still evicted least recently used first
+            ranked.sort_by(|a, b| value(entries[*a]).total_cmp(&value(entries[*b])));
+        }
+
+        let mut size = cache_size;
+        ranked
+            .into_iter()
+            .take_while(|i| {
+                if size <= self.target_size {
+                    return false;
+                }
+                size -= entries[*i].size;
+                true
+            })
+            .collect()
+    }
+}
+
+/// Applies `policies` in order. Entries selected by a policy are not seen by the later ones.
+pub fn plan_eviction(
+    policies: &[Box<dyn EvictionPolicy>],
+    entries: &[CacheEntry],
+    cache_size: i64,
+) -> Vec<EvictionCandidate> {
+    let mut remaining = entries.iter().collect::<Vec<_>>();
+    // Blobs shared with other entries are not freed, so this is an estimate
+    let mut cache_size = cache_size;
+    let mut candidates = vec![];
+
+    for policy in policies {
+        let selected = policy
+            .select(&remaining, cache_size)
+            .into_iter()
+            .collect::<HashSet<_>>();
+        if selected.is_empty() {
+            continue;
+        }
+
+        let mut index = 0;
+        remaining.retain(|entry| {
+            let evict = selected.contains(&index);
+            index += 1;
+            if evict {
+                cache_size -= entry.size;
+                candidates.push(EvictionCandidate {
+                    hash: entry.hash.clone(),
+                    size: entry.size,
+                    project: entry.project.clone(),
+                    target: entry.target.clone(),
+                    reason: policy.name().to_string(),
+                });
+            }
+            !evict
+        });
+    }
+
+    candidates
+}
+
+/// Entries without a recorded duration are assumed to cost as much as
+/// the average entry of the same target
+pub fn fill_in_costs(entries: &mut [CacheEntry]) {
+    let mut totals: HashMap<(String, String), (f64, usize)> = HashMap::new();
+    for entry in entries.iter() {
+        if let (Some(project), Some(target), Some(cost)) =
+            (&entry.project, &entry.target, entry.cost)
+        {
+            let total = totals.entry((project.clone(), target.clone())).or_default();
+            total.0 += cost;
+            total.1 += 1;
+        }
+    }
+
+    for entry in entries.iter_mut().filter(|entry| entry.cost.is_none()) {
+        if let (Some(project), Some(target)) = (&entry.project, &entry.target) {
+            entry.cost = totals
+                .get(&(project.clone(), target.clone()))
+                .map(|(total, count)| total / *count as f64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3600;
+
+    fn entry(hash: &str, target: &str, size: i64, accessed_at: i64, cost: f64) -> CacheEntry {
+        CacheEntry {
+            hash: hash.to_string(),
+            size,
+            accessed_at,
+            project: Some("app".to_string()),
+            target: Some(target.to_string()),
+            cost: Some(cost),
+        }
+    }
+
+    fn evicted(candidates: Vec<EvictionCandidate>) -> Vec<String> {
+        candidates.into_iter().map(|c| c.hash).collect()
+    }
+
+    #[test]
+    fn should_keep_the_last_entries_per_target() {
+        let entries = vec![
+            entry("1", "build", 10, 3 * HOUR, 0.0),
+            entry("2", "lint", 10, 2 * HOUR, 0.0),
+            entry("3", "build", 10, HOUR, 0.0),
+            entry("4", "build", 10, 0, 0.0),
+        ];
+        let policies: Vec<Box<dyn EvictionPolicy>> = vec![Box::new(KeepLastPerTarget { count: 1 })];
+
+        assert_eq!(
+            evicted(plan_eviction(&policies, &entries, 40)),
+            vec!["3", "4"]
+        );
+    }
+
+    #[test]
+    fn should_keep_projects_within_their_quota() {
+        let entries = vec![
+            entry("1", "build", 60, 2 * HOUR, 0.0),
+            entry("2", "build", 60, HOUR, 0.0),
+            entry("3", "build", 30, 0, 0.0),
+        ];
+        let policies: Vec<Box<dyn EvictionPolicy>> = vec![Box::new(ProjectQuota {
+            quotas: HashMap::from([("app".to_string(), 100)]),
+        })];
+
+        assert_eq!(evicted(plan_eviction(&policies, &entries, 150)), vec!["2"]);
+    }
+
+    #[test]
+    fn should_prefer_keeping_expensive_entries() {
+        let entries = vec![
+            entry("lint", "lint", 10, 3 * HOUR, 1_000.0),
+            entry("build", "build", 10, 2 * HOUR, 600_000.0),
+            entry("old-lint", "lint", 10, HOUR, 1_000.0),
+        ];
+        let lru: Vec<Box<dyn EvictionPolicy>> = vec![Box::new(SizeLimit {
+            max_size: 25,
+            target_size: 15,
+            ranking: Ranking::LeastRecentlyUsed,
+        })];
+        let cost_aware: Vec<Box<dyn EvictionPolicy>> = vec![Box::new(SizeLimit {
+            max_size: 25,
+            target_size: 15,
+            ranking: Ranking::CostAware { now: 4 * HOUR },
+        })];
+
+        assert_eq!(
+            evicted(plan_eviction(&lru, &entries, 30)),
+            vec!["build", "old-lint"]
+        );
+        assert_eq!(
+            evicted(plan_eviction(&cost_aware, &entries, 30)),
+            vec!["lint", "old-lint"]
+        );
+        // Nothing is evicted while the cache is within its limit
+        assert!(plan_eviction(&cost_aware, &entries, 20).is_empty());
+    }
+
+    #[test]
+    fn should_not_count_entries_twice() {
+        let entries = vec![
+            entry("1", "build", 10, 10 * 24 * HOUR, 0.0),
+            entry("2", "build", 10, 0, 0.0),
+        ];
+        let policies: Vec<Box<dyn EvictionPolicy>> = vec![
+            Box::new(MaxAge {
+                now: 10 * 24 * HOUR,
+                max_age: MAX_AGE_SECONDS,
+            }),
+            Box::new(SizeLimit {
+                max_size: 15,
+                target_size: 10,
+                ranking: Ranking::LeastRecentlyUsed,
+            }),
+        ];
+
+        let candidates = plan_eviction(&policies, &entries, 20);
+
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].hash, "2");
+        assert_eq!(candidates[0].reason, "max-age");
+    }
+
+    #[test]
+    fn should_fill_in_costs_from_the_same_target() {
+        let mut entries = vec![
+            entry("1", "build", 10, 0, 100.0),
+            entry("2", "build", 10, 0, 300.0),
+            CacheEntry {
+                cost: None,
+                ..entry("3", "build", 10, 0, 0.0)
+            },
+        ];
+
+        fill_in_costs(&mut entries);
+
+        assert_eq!(entries[2].cost, Some(200.0));
+    }
+}
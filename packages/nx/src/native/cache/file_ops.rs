@@ -4,6 +4,41 @@ use std::{fs, io};
 use fs_extra::error::ErrorKind;
 use tracing::{debug, trace};
 
+/// How a file was restored from the cache
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RestoreStrategy {
+    /// A copy-on-write clone sharing the cached file's data blocks
+    Reflink,
+    /// A hardlink to the cached file, only used for read-only outputs
+    Hardlink,
+    /// A full byte copy
+    Copy,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct RestoreResult {
+    pub size: i64,
+    /// The slowest strategy any file of this restore had to fall back to, if any file was restored
+    pub strategy: Option<RestoreStrategy>,
+    pub reflinked_files: u32,
+    pub hardlinked_files: u32,
+    pub copied_files: u32,
+}
+
+impl RestoreResult {
+    fn record(&mut self, size: u64, strategy: RestoreStrategy) {
+        self.size += size as i64;
+        self.strategy = self.strategy.max(Some(strategy));
+        match strategy {
+            RestoreStrategy::Reflink => self.reflinked_files += 1,
+            RestoreStrategy::Hardlink => self.hardlinked_files += 1,
+            RestoreStrategy::Copy => self.copied_files += 1,
+        }
+    }
+}
+
 #[napi]
 pub fn remove(src: String) -> anyhow::Result<()> {
     fs_extra::remove_items(&[src]).map_err(|err| match err.kind {
@@ -60,6 +95,114 @@ where
     Ok(size as i64)
 }
 
+/// Restores `src` to `dest` like [`_copy`], but avoids copying bytes where the
+/// filesystem allows it: reflinks are tried first, then hardlinks for read-only
+/// files, before falling back to a regular copy.
+pub fn _restore<P>(src: P, dest: P) -> anyhow::Result<RestoreResult>
+where
+    P: AsRef<Path>,
+{
+    let dest: PathBuf = remove_trailing_single_dot(dest);
+    let dest_parent = dest.parent().unwrap_or(&dest);
+    let src: PathBuf = src.as_ref().into();
+
+    trace!("Restoring {:?} -> {:?}", &src, &dest);
+
+    if !dest_parent.exists() {
+        fs::create_dir_all(dest_parent)?;
+    }
+
+    let mut result = RestoreResult::default();
+    if src.is_dir() {
+        restore_dir_all(&src, &dest, &mut result)?;
+    } else if src.is_symlink() {
+        symlink(fs::read_link(&src)?, &dest)?;
+    } else {
+        let (size, strategy) = restore_file(&src, &dest)?;
+        result.record(size, strategy);
+    }
+
+    debug!(
+        "Restore completed: {:?} -> {:?} ({:?}, {} bytes)",
+        &src, &dest, result.strategy, result.size
+    );
+    Ok(result)
+}
+
+fn restore_dir_all(src: &Path, dst: &Path, result: &mut RestoreResult) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let ty = entry.file_type()?;
+        let dest_path = dst.join(entry.file_name());
+
+        if ty.is_dir() {
+            restore_dir_all(&entry.path(), &dest_path, result)?;
+        } else if ty.is_symlink() {
+            symlink(fs::read_link(entry.path())?, dest_path)?;
+        } else {
+            let (size, strategy) = restore_file(&entry.path(), &dest_path)?;
+            result.record(size, strategy);
+        }
+    }
+    Ok(())
+}
+
+fn restore_file(src: &Path, dest: &Path) -> io::Result<(u64, RestoreStrategy)> {
+    // Never write through an existing file, it may be linked to the cache
+    if dest.exists() {
+        fs::remove_file(dest)?;
+    }
+
+    let metadata = fs::metadata(src)?;
+
+    if reflink(src, dest).is_ok() {
+        trace!("Reflinked file: {:?}", src);
+        return Ok((metadata.len(), RestoreStrategy::Reflink));
+    }
+
+    // Writable files cannot be linked, writing to them would modify the cache
+    if metadata.permissions().readonly() && fs::hard_link(src, dest).is_ok() {
+        trace!("Hardlinked file: {:?}", src);
+        return Ok((metadata.len(), RestoreStrategy::Hardlink));
+    }
+
+    let size = fs::copy(src, dest)?;
+    trace!("Copied file: {:?} ({} bytes)", src, size);
+    Ok((size, RestoreStrategy::Copy))
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dest: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    // FICLONE = _IOW(0x94, 9, int)
+    nix::ioctl_write_int!(ficlone, 0x94, 9);
+
+    let src_file = fs::File::open(src)?;
+    let dest_file = fs::File::create(dest)?;
+
+    // SAFETY: both file descriptors are valid for the duration of the call
+    let cloned = unsafe { ficlone(dest_file.as_raw_fd(), src_file.as_raw_fd() as _) };
+    match cloned {
+        Ok(_) => {
+            dest_file.set_permissions(src_file.metadata()?.permissions())?;
+            Ok(())
+        }
+        Err(errno) => {
+            drop(dest_file);
+            fs::remove_file(dest)?;
+            Err(io::Error::from(errno))
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_src: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
 fn remove_trailing_single_dot(path: impl AsRef<Path>) -> PathBuf {
     let mut components = path.as_ref().components().collect::<Vec<_>>();
 
@@ -205,6 +348,41 @@ mod test {
         );
     }
 
+    #[test]
+    fn should_restore_read_only_files_with_links() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.child("cache").child("dist").child("main.js");
+        file.write_str("content").unwrap();
+        let mut permissions = fs::metadata(file.path()).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(file.path(), permissions).unwrap();
+
+        let result = _restore(temp.join("cache"), temp.join("workspace")).unwrap();
+
+        assert_eq!(result.size, 7);
+        assert!(result.strategy.is_some());
+        assert_ne!(result.strategy, Some(RestoreStrategy::Copy));
+        assert_eq!(
+            fs::read_to_string(temp.join("workspace/dist/main.js")).unwrap(),
+            "content"
+        );
+    }
+
+    #[test]
+    fn should_restore_writable_files_without_hardlinks() {
+        let temp = TempDir::new().unwrap();
+        temp.child("cache")
+            .child("main.js")
+            .write_str("content")
+            .unwrap();
+
+        let result = _restore(temp.join("cache"), temp.join("workspace")).unwrap();
+
+        assert_eq!(result.hardlinked_files, 0);
+        assert_eq!(result.reflinked_files + result.copied_files, 1);
+        assert!(temp.child("workspace/main.js").exists());
+    }
+
     #[test]
     fn should_copy_directories_with_symlinks() {
         let temp = TempDir::new().unwrap();
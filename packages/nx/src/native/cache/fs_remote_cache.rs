@@ -0,0 +1,262 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    process,
+};
+
+use tracing::trace;
+
+use super::{
+    cache::CachedResult,
+    compression::ArchiveCompression,
+    errors::{HttpRemoteCacheErrors, report_request_error},
+    integrity::ArtifactIntegrity,
+    remote_cache::{RemoteCache, extract_archive, write_archive},
+};
+
+/// Stores cache artifacts as `<hash>.tar.gz` files in a shared directory, e.g. an NFS mount
+pub struct FsRemoteCache {
+    directory: PathBuf,
+    integrity: ArtifactIntegrity,
+    compression: ArchiveCompression,
+}
+
+impl FsRemoteCache {
+    pub fn new(
+        directory: impl Into<PathBuf>,
+        integrity: ArtifactIntegrity,
+        compression: ArchiveCompression,
+    ) -> Self {
+        Self {
+            directory: directory.into(),
+            integrity,
+            compression,
+        }
+    }
+
+    fn artifact_path(&self, hash: &str) -> PathBuf {
+        self.directory.join(format!("{}.tar.gz", hash))
+    }
+}
+
+fn to_request_error(err: io::Error) -> napi::Error {
+    HttpRemoteCacheErrors::RequestError(report_request_error(&err)).into()
+}
+
+impl RemoteCache for FsRemoteCache {
+    async fn retrieve(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+    ) -> napi::Result<Option<CachedResult>> {
+        let artifact_path = self.artifact_path(hash);
+        let file = match fs::File::open(&artifact_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(to_request_error(e)),
+        };
+        trace!("Extracting {:?}", &artifact_path);
+
+        Ok(Some(extract_archive(
+            io::BufReader::new(file),
+            cache_directory,
+            hash,
+            &self.integrity,
+        )?))
+    }
+
+    async fn store(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+        terminal_output: &str,
+        code: u32,
+    ) -> napi::Result<bool> {
+        if !self.integrity.can_store() {
+            trace!("Not storing {} without a signing key", hash);
+            return Ok(false);
+        }
+
+        let artifact_path = self.artifact_path(hash);
+        // Cache entry already exists, silently do not store new data
+        if artifact_path.exists() {
+            return Ok(false);
+        }
+
+        if !self.directory.is_dir() {
+            return Err(HttpRemoteCacheErrors::Misconfigured(format!(
+                "Remote cache directory does not exist: {}",
+                self.directory.display()
+            ))
+            .into());
+        }
+
+        // Write to a temporary file first so other machines never read a partial artifact
+        let temp_path = artifact_path.with_extension(format!("{}.tmp", process::id()));
+        let written = fs::File::create(&temp_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| {
+                write_archive(
+                    io::BufWriter::new(file),
+                    hash,
+                    cache_directory,
+                    terminal_output,
+                    code,
+                    &self.integrity,
+                    self.compression,
+                )?
+                .flush()?;
+                Ok(())
+            });
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
+        if let Err(e) = fs::rename(&temp_path, &artifact_path) {
+            let _ = fs::remove_file(&temp_path);
+            if artifact_path.exists() {
+                return Ok(false);
+            }
+            return Err(to_request_error(e));
+        }
+        trace!("Stored artifact at {:?}", &artifact_path);
+
+        Ok(true)
+    }
+
+    async fn exists(&self, hash: &str) -> napi::Result<bool> {
+        Ok(self.artifact_path(hash).exists())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use assert_fs::prelude::*;
+
+    #[tokio::test]
+    async fn should_store_and_retrieve_artifacts() {
+        let temp = TempDir::new().unwrap();
+        let remote = temp.child("remote");
+        remote.create_dir_all().unwrap();
+        temp.child("local/123/dist/main.js")
+            .write_str("content")
+            .unwrap();
+        let remote_cache = FsRemoteCache::new(
+            remote.path(),
+            ArtifactIntegrity::default(),
+            ArchiveCompression::default(),
+        );
+        let local = temp.join("local").display().to_string();
+
+        assert!(!remote_cache.exists("123").await.unwrap());
+        assert!(
+            remote_cache
+                .store("123", &local, "terminal output", 0)
+                .await
+                .unwrap()
+        );
+        assert!(remote_cache.exists("123").await.unwrap());
+        assert_eq!(
+            remote_cache
+                .query(&["123".to_string(), "456".to_string()])
+                .await
+                .unwrap(),
+            vec!["123".to_string()]
+        );
+        // Existing artifacts are never overwritten
+        assert!(
+            !remote_cache
+                .store("123", &local, "terminal output", 0)
+                .await
+                .unwrap()
+        );
+
+        let other = temp.join("other").display().to_string();
+        let result = remote_cache.retrieve("123", &other).await.unwrap().unwrap();
+
+        assert_eq!(result.code, 0);
+        assert_eq!(result.terminal_output.as_deref(), Some("terminal output"));
+        temp.child("other/123/dist/main.js").assert("content");
+    }
+
+    #[tokio::test]
+    async fn should_not_retrieve_missing_artifacts() {
+        let temp = TempDir::new().unwrap();
+        let remote_cache = FsRemoteCache::new(
+            temp.path(),
+            ArtifactIntegrity::default(),
+            ArchiveCompression::default(),
+        );
+
+        let result = remote_cache
+            .retrieve("123", &temp.path().display().to_string())
+            .await
+            .unwrap();
+
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn should_reject_tampered_artifacts() {
+        let temp = TempDir::new().unwrap();
+        let remote = temp.child("remote");
+        remote.create_dir_all().unwrap();
+        temp.child("local/123/dist/main.js")
+            .write_str("content")
+            .unwrap();
+        let local = temp.join("local").display().to_string();
+        let other = temp.join("other").display().to_string();
+
+        let signed = FsRemoteCache::new(
+            remote.path(),
+            ArtifactIntegrity::hmac(b"secret"),
+            ArchiveCompression::default(),
+        );
+        assert!(signed.store("123", &local, "", 0).await.unwrap());
+        assert!(signed.retrieve("123", &other).await.unwrap().is_some());
+
+        // The same artifact presented for another hash fails the signature check
+        fs::copy(
+            remote.child("123.tar.gz").path(),
+            remote.child("456.tar.gz").path(),
+        )
+        .unwrap();
+        assert!(signed.retrieve("456", &other).await.is_err());
+        assert!(!temp.child("other/456").path().exists());
+
+        let other_key = FsRemoteCache::new(
+            remote.path(),
+            ArtifactIntegrity::hmac(b"other"),
+            ArchiveCompression::default(),
+        );
+        assert!(other_key.retrieve("123", &other).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_retrieve_artifacts_regardless_of_compression() {
+        let temp = TempDir::new().unwrap();
+        temp.child("local/123/dist/main.js")
+            .write_str("content")
+            .unwrap();
+        let local = temp.join("local").display().to_string();
+        let other = temp.join("other").display().to_string();
+
+        let zstd = FsRemoteCache::new(
+            temp.path(),
+            ArtifactIntegrity::default(),
+            ArchiveCompression::zstd(),
+        );
+        assert!(zstd.store("123", &local, "", 0).await.unwrap());
+
+        let gzip = FsRemoteCache::new(
+            temp.path(),
+            ArtifactIntegrity::default(),
+            ArchiveCompression::gzip(),
+        );
+        assert!(gzip.retrieve("123", &other).await.unwrap().is_some());
+        temp.child("other/123/dist/main.js").assert("content");
+    }
+}
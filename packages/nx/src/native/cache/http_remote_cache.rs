@@ -1,29 +1,36 @@
 use std::{
     env,
-    fs::{self},
-    io::Read,
-    path::Path,
+    future::Future,
+    io::{self, BufWriter, Read, Write},
+    sync::Arc,
+    time::Duration,
 };
 
 use super::{
     cache::CachedResult,
+    compression::{ArchiveCompression, Codec},
     errors::{HttpRemoteCacheErrors, convert_response_to_error, report_request_error},
+    integrity::ArtifactIntegrity,
+    remote_cache::{RemoteCache, extract_archive, query_each, write_archive},
 };
-use flate2::Compression;
-use reqwest::{Client, ClientBuilder, StatusCode, header};
-use tar::{Archive, Builder};
-use tracing::trace;
+use futures::stream;
+use reqwest::{Body, Client, ClientBuilder, Response, StatusCode, header};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::mpsc, task, time};
+use tracing::{debug, trace};
 
 #[napi]
 pub struct HttpRemoteCache {
     client: Client,
     url: String,
+    integrity: Arc<ArtifactIntegrity>,
+    compression: ArchiveCompression,
 }
 
 #[napi]
 impl HttpRemoteCache {
     #[napi(constructor)]
-    pub fn new() -> Self {
+    pub fn new() -> napi::Result<Self> {
         let mut headers = header::HeaderMap::new();
         let auth_token = env::var("NX_SELF_HOSTED_REMOTE_CACHE_ACCESS_TOKEN");
         if let Ok(token) = auth_token {
@@ -48,13 +55,15 @@ impl HttpRemoteCache {
             }
         }
 
-        HttpRemoteCache {
+        Ok(HttpRemoteCache {
             client: client_builder
                 .build()
                 .expect("Failed to create HTTP client"),
             url: env::var("NX_SELF_HOSTED_REMOTE_CACHE_SERVER")
                 .expect("NX_REMOTE_CACHE_URL must be set"),
-        }
+            integrity: Arc::new(ArtifactIntegrity::from_env()?),
+            compression: ArchiveCompression::from_env()?,
+        })
     }
 
     #[napi]
@@ -63,34 +72,7 @@ impl HttpRemoteCache {
         hash: String,
         cache_directory: String,
     ) -> napi::Result<Option<CachedResult>> {
-        let span = tracing::trace_span!("retrieve", hash = %hash);
-        let _guard = span.enter();
-
-        let url: String = format!("{}/v1/cache/{}", self.url, hash);
-        let response = self
-            .client
-            .get(&url)
-            .header("Accept", "application/octet-stream")
-            .send()
-            .await;
-        if let Ok(resp) = response {
-            trace!("HTTP response status: {}", resp.status());
-            let status = resp.status();
-
-            match status {
-                StatusCode::OK => {
-                    Ok(Some(
-                        // response is an application/octet-stream containing a tarball
-                        // we need to extract the tarball and return the path to the extracted files
-                        Self::download_and_extract_from_result(resp, cache_directory, hash).await?,
-                    ))
-                }
-                StatusCode::NOT_FOUND => Ok(None),
-                _ => Err(convert_response_to_error(resp).await.into()),
-            }
-        } else {
-            Err(HttpRemoteCacheErrors::RequestError(response.unwrap_err().to_string()).into())
-        }
+        RemoteCache::retrieve(self, &hash, &cache_directory).await
     }
 
     #[napi]
@@ -101,64 +83,33 @@ impl HttpRemoteCache {
         terminal_output: String,
         code: u32,
     ) -> napi::Result<bool> {
-        let span = tracing::trace_span!("store", hash = %hash);
-        let _guard = span.enter();
+        RemoteCache::store(self, &hash, &cache_directory, &terminal_output, code).await
+    }
 
-        // We can change the creation of the tar in a future version without
-        // worrying about breaking existing user cache's, because when the
-        // user updates their task's hashes will be changed... so users
-        // retrieving old hashes will not be affected, and new entries
-        // will have distinct hashes.
-
-        // create a tarball in memory from the cache dir
-        let tar_gz: Vec<u8> = Vec::new();
-        let enc = flate2::write::GzEncoder::new(tar_gz, Compression::default());
-        let mut archive = Builder::new(enc);
-        archive.follow_symlinks(false);
-        trace!("Created tar file for writing");
-
-        let cache_path = Path::new(&cache_directory);
-        let outputs_path = cache_path.join(&hash);
-
-        trace!("Adding cache artifacts to tarball");
-        archive.append_dir_all("", &outputs_path)?;
-        trace!("Added cache directory to tarball");
-
-        trace!("Adding terminal output to tarball");
-        let mut terminal_output_header = tar::Header::new_old();
-        let terminal_output_bytes = terminal_output.as_bytes();
-        terminal_output_header.set_size(terminal_output_bytes.len() as u64);
-        terminal_output_header.set_cksum(); // Ensure the checksum is set correctly
-        archive.append_data(
-            &mut terminal_output_header,
-            "terminalOutput",
-            terminal_output_bytes,
-        )?;
-        trace!("Added terminal output to tarball");
-
-        trace!("Adding code to tarball");
-        let mut code_header = tar::Header::new_old();
-        code_header.set_size(4);
-        code_header.set_cksum(); // Ensure the checksum is set correctly
-        archive.append_data(&mut code_header, "code", &code.to_be_bytes()[..])?;
-        trace!("Added code to tarball");
-
-        trace!("Finishing tarball");
-        archive
-            .finish() // Finish the archive to get the inner bytes
-            .map_err(|e| anyhow::anyhow!(format!("Failed to finish tarball: {}", e)))?;
-        trace!("Finished tarball");
-
-        trace!("Reading tarball into memory");
-        let archive_bytes = archive.into_inner()?;
-        let buffer = archive_bytes.finish()?;
-        trace!("read tarball into memory");
+    #[napi]
+    pub async fn exists(&self, hash: String) -> napi::Result<bool> {
+        RemoteCache::exists(self, &hash).await
+    }
 
-        let url: String = format!("{}/v1/cache/{}", self.url, hash);
+    #[napi]
+    pub async fn query(&self, hashes: Vec<String>) -> napi::Result<Vec<String>> {
+        RemoteCache::query(self, &hashes).await
+    }
+
+    /// Checks which `hashes` exist through `POST /v1/cache/query`.
+    /// Returns `None` when the server does not implement the endpoint.
+    async fn query_batch(&self, hashes: &[String]) -> napi::Result<Option<Vec<String>>> {
+        let url: String = format!("{}/v1/cache/query", self.url);
+        let body = serde_json::to_vec(&CacheQuery {
+            hashes: hashes.to_vec(),
+        })
+        .map_err(anyhow::Error::from)?;
         let response = self
             .client
-            .put(&url)
-            .body(buffer) // Convert the bytes to a Vec<u8> for the request body
+            .post(&url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/json")
+            .body(body)
             .send()
             .await
             .map_err(|e| {
@@ -168,84 +119,479 @@ impl HttpRemoteCache {
             })?;
 
         match response.status() {
-            StatusCode::OK => Ok(true),
-            // Cache entry already exists, silently do not store new data
-            StatusCode::CONFLICT => Ok(false),
-            // User is authorized but server does not allow
-            // cache storage for whatever reason (e.g. read-only token.)
-            StatusCode::FORBIDDEN => Ok(false),
+            StatusCode::OK => {
+                let content = response
+                    .bytes()
+                    .await
+                    .map_err(|e| HttpRemoteCacheErrors::RequestError(report_request_error(&e)))?;
+                let result: CacheQuery = serde_json::from_slice(&content).map_err(|e| {
+                    HttpRemoteCacheErrors::Misconfigured(format!(
+                        "Invalid response from {}: {}",
+                        url, e
+                    ))
+                })?;
+                Ok(Some(result.hashes))
+            }
+            StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED => Ok(None),
             _ => Err(convert_response_to_error(response).await.into()),
         }
     }
+}
 
-    async fn download_and_extract_from_result(
-        response: reqwest::Response,
-        cache_directory: String,
-        hash: String,
-    ) -> anyhow::Result<CachedResult> {
-        let content = response.bytes().await.unwrap();
-        trace!("Downloaded {} bytes from remote cache", content.len());
-        let tar = flate2::read::GzDecoder::new(content.as_ref());
-        let mut archive = Archive::new(tar);
-        let entries = archive
-            .entries() // Get the entries in the archive
-            .map_err(|_| anyhow::anyhow!("Failed to read entries from tarball"))?;
-
-        let mut code: Option<i16> = None;
-        let mut terminal_output: Option<String> = None;
-        let mut size: i64 = 0;
-
-        let output_dir = Path::new(&cache_directory).join(&hash);
-
-        // Extract the archive to the specified cache directory
-        for entry in entries {
-            let mut entry =
-                entry.map_err(|_| anyhow::anyhow!("Failed to read entry from tarball"))?;
-
-            let entry_path = entry
-                .path()
-                .map(|p| p.to_string_lossy().into_owned())
-                .unwrap();
-
-            if entry_path == "code" {
-                let code_file_bytes = entry.bytes().collect::<Result<Vec<u8>, _>>()?;
-                code = Some(i16::from_be_bytes([code_file_bytes[0], code_file_bytes[1]]));
-                trace!("Retrieved exit code from cache: {}", code.unwrap());
-            } else if entry_path == "terminalOutput" {
-                let terminal_output_bytes = entry.bytes().collect::<Result<Vec<u8>, _>>()?;
-                let terminal_output_size = terminal_output_bytes.len();
-
-                terminal_output = Some(String::from_utf8(terminal_output_bytes)?);
-                size += terminal_output_size as i64;
-
-                trace!(
-                    "Retrieved terminal output from cache: {} bytes",
-                    terminal_output_size
-                );
-            } else {
-                let path_on_disk = output_dir.join(entry_path);
-                trace!("Extracting entry to {}", path_on_disk.display());
-                fs::create_dir_all(path_on_disk.parent().expect("This will have a parent, we just joined it above so there is at least one dir."))?;
-                // Ensure the directory exists before extracting
-                match entry.unpack(&path_on_disk) {
-                    Err(e) => {
-                        return Err(anyhow::anyhow!("Failed to unpack entry: {}", e));
+impl HttpRemoteCache {
+    /// Extracts the archive while it is downloaded, so it never has to fit in memory
+    async fn download_archive(
+        &self,
+        mut response: Response,
+        cache_directory: &str,
+        hash: &str,
+    ) -> Attempt<CachedResult> {
+        let (sender, receiver) = mpsc::channel(PIPE_CAPACITY);
+        let extraction = {
+            let cache_directory = cache_directory.to_string();
+            let hash = hash.to_string();
+            let integrity = self.integrity.clone();
+            task::spawn_blocking(move || {
+                extract_archive(
+                    ChannelReader::new(receiver),
+                    &cache_directory,
+                    &hash,
+                    &integrity,
+                )
+            })
+        };
+
+        let mut size = 0;
+        let mut download_error = None;
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    size += chunk.len();
+                    // The extraction stopped early, it reports its own error
+                    if sender.send(Ok(chunk)).await.is_err() {
+                        break;
                     }
-                    Ok(f) => match f {
-                        tar::Unpacked::File(f) => size += f.metadata()?.len() as i64,
-                        _ => (),
-                    },
                 }
+                Ok(None) => break,
+                Err(e) => {
+                    let error = request_error(&e);
+                    let _ = sender.send(Err(io::Error::other(error.to_string()))).await;
+                    download_error = Some(error);
+                    break;
+                }
+            }
+        }
+        drop(sender);
+        trace!("Downloaded {} bytes from remote cache", size);
+
+        match (extraction.await, download_error) {
+            (Ok(Ok(result)), _) => Attempt::Done(Ok(result)),
+            (_, Some(error)) => Attempt::Retry(error),
+            (Ok(Err(e)), None) => Attempt::Done(Err(e)),
+            (Err(e), None) => Attempt::Done(Err(anyhow::Error::from(e).into())),
+        }
+    }
+
+    /// Uploads the archive compressed with `compression`. Returns `None` when
+    /// the server does not accept the media type of the compression.
+    async fn put_archive(
+        &self,
+        url: &str,
+        hash: &str,
+        cache_directory: &str,
+        terminal_output: &str,
+        code: u32,
+        compression: ArchiveCompression,
+    ) -> napi::Result<Option<bool>> {
+        with_retries("store", move || async move {
+            // The archive is recreated for every attempt, a streamed body cannot be replayed
+            let (body, archive) =
+                self.upload_archive(hash, cache_directory, terminal_output, code, compression);
+            let response = self
+                .client
+                .put(url)
+                .header(header::CONTENT_TYPE, compression.content_type())
+                .body(body)
+                .send()
+                .await;
+            match archive.await {
+                Ok(Ok(())) => {}
+                // The request ended before the whole archive was sent
+                Ok(Err(e)) if is_broken_pipe(&e) => {}
+                Ok(Err(e)) => return Attempt::Done(Err(e.into())),
+                Err(e) => return Attempt::Done(Err(anyhow::Error::from(e).into())),
+            }
+            let response = match response {
+                Ok(response) => response,
+                Err(e) => return Attempt::Retry(request_error(&e)),
+            };
+
+            match response.status() {
+                StatusCode::OK => Attempt::Done(Ok(Some(true))),
+                // Cache entry already exists, silently do not store new data
+                StatusCode::CONFLICT => Attempt::Done(Ok(Some(false))),
+                // User is authorized but server does not allow
+                // cache storage for whatever reason (e.g. read-only token.)
+                StatusCode::FORBIDDEN => Attempt::Done(Ok(Some(false))),
+                StatusCode::UNSUPPORTED_MEDIA_TYPE if compression.codec != Codec::Gzip => {
+                    Attempt::Done(Ok(None))
+                }
+                status if is_transient(status) => {
+                    Attempt::Retry(convert_response_to_error(response).await)
+                }
+                _ => Attempt::Done(Err(convert_response_to_error(response).await.into())),
             }
+        })
+        .await
+    }
+
+    /// Artifacts are accepted in any format, they are decompressed based on their content
+    fn accept(&self) -> &'static str {
+        match self.compression.codec {
+            Codec::Gzip => "application/octet-stream",
+            Codec::Zstd => "application/zstd, application/octet-stream",
         }
+    }
 
-        trace!("Extracted tarball to {}", output_dir.display());
+    /// Returns a request body which streams the archive as it is created on a blocking thread
+    fn upload_archive(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+        terminal_output: &str,
+        code: u32,
+        compression: ArchiveCompression,
+    ) -> (Body, task::JoinHandle<anyhow::Result<()>>) {
+        let (sender, receiver) = mpsc::channel::<io::Result<Vec<u8>>>(PIPE_CAPACITY);
+        let archive = {
+            let hash = hash.to_string();
+            let cache_directory = cache_directory.to_string();
+            let terminal_output = terminal_output.to_string();
+            let integrity = self.integrity.clone();
+            task::spawn_blocking(move || {
+                let writer = BufWriter::with_capacity(CHUNK_SIZE, ChannelWriter(sender.clone()));
+                let result = write_archive(
+                    writer,
+                    &hash,
+                    &cache_directory,
+                    &terminal_output,
+                    code,
+                    &integrity,
+                    compression,
+                )
+                .and_then(|mut writer| Ok(writer.flush()?));
+                // Fail the request instead of ending the body, so a partial
+                // archive is never stored
+                if let Err(e) = &result {
+                    let _ = sender.blocking_send(Err(io::Error::other(e.to_string())));
+                }
+                result
+            })
+        };
 
-        Ok(CachedResult {
-            terminal_output,
-            code: code.expect("Exit code not found in cache"),
-            outputs_path: output_dir.to_string_lossy().into_owned(),
-            size: Some(size),
+        let body = stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|chunk| (chunk, receiver))
+        });
+        (Body::wrap_stream(body), archive)
+    }
+}
+
+/// Request and response body of `POST /v1/cache/query`
+#[derive(Serialize, Deserialize)]
+struct CacheQuery {
+    hashes: Vec<String>,
+}
+
+impl RemoteCache for HttpRemoteCache {
+    async fn retrieve(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+    ) -> napi::Result<Option<CachedResult>> {
+        let span = tracing::trace_span!("retrieve", hash = %hash);
+        let _guard = span.enter();
+
+        let url = &format!("{}/v1/cache/{}", self.url, hash);
+        with_retries("retrieve", move || async move {
+            let response = match self
+                .client
+                .get(url)
+                .header(header::ACCEPT, self.accept())
+                .send()
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => return Attempt::Retry(request_error(&e)),
+            };
+            trace!("HTTP response status: {}", response.status());
+
+            match response.status() {
+                // response is an application/octet-stream containing a tarball
+                // which is extracted while it is downloaded
+                StatusCode::OK => self
+                    .download_archive(response, cache_directory, hash)
+                    .await
+                    .map(Some),
+                StatusCode::NOT_FOUND => Attempt::Done(Ok(None)),
+                status if is_transient(status) => {
+                    Attempt::Retry(convert_response_to_error(response).await)
+                }
+                _ => Attempt::Done(Err(convert_response_to_error(response).await.into())),
+            }
         })
+        .await
+    }
+
+    async fn store(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+        terminal_output: &str,
+        code: u32,
+    ) -> napi::Result<bool> {
+        let span = tracing::trace_span!("store", hash = %hash);
+        let _guard = span.enter();
+
+        // Artifacts stored without the signing key would be rejected by every reader
+        if !self.integrity.can_store() {
+            trace!("Not storing {} without a signing key", hash);
+            return Ok(false);
+        }
+
+        let url = &format!("{}/v1/cache/{}", self.url, hash);
+        let stored = self
+            .put_archive(
+                url,
+                hash,
+                cache_directory,
+                terminal_output,
+                code,
+                self.compression,
+            )
+            .await?;
+        match stored {
+            Some(stored) => Ok(stored),
+            None => {
+                debug!("Remote cache does not accept zstd artifacts, falling back to gzip");
+                let stored = self
+                    .put_archive(
+                        url,
+                        hash,
+                        cache_directory,
+                        terminal_output,
+                        code,
+                        ArchiveCompression::gzip(),
+                    )
+                    .await?;
+                Ok(stored.unwrap_or(false))
+            }
+        }
+    }
+
+    async fn exists(&self, hash: &str) -> napi::Result<bool> {
+        let url: String = format!("{}/v1/cache/{}", self.url, hash);
+        let response = self.client.head(&url).send().await.map_err(|e| {
+            napi::Error::from(HttpRemoteCacheErrors::RequestError(report_request_error(
+                &e,
+            )))
+        })?;
+
+        match response.status() {
+            StatusCode::OK => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            _ => Err(convert_response_to_error(response).await.into()),
+        }
+    }
+
+    async fn query(&self, hashes: &[String]) -> napi::Result<Vec<String>> {
+        if let Some(found) = self.query_batch(hashes).await? {
+            return Ok(found);
+        }
+
+        trace!("Remote cache does not support batch queries, checking hashes individually");
+        query_each(self, hashes).await
+    }
+}
+
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Size of the chunks sent through the upload pipe
+const CHUNK_SIZE: usize = 256 * 1024;
+/// Number of chunks buffered between the network and the archive
+const PIPE_CAPACITY: usize = 16;
+
+/// The outcome of a single attempt at a request
+enum Attempt<T> {
+    Done(napi::Result<T>),
+    /// The attempt failed in a way which may succeed when repeated
+    Retry(HttpRemoteCacheErrors),
+}
+
+impl<T> Attempt<T> {
+    fn map<U>(self, f: impl FnOnce(T) -> U) -> Attempt<U> {
+        match self {
+            Attempt::Done(result) => Attempt::Done(result.map(f)),
+            Attempt::Retry(e) => Attempt::Retry(e),
+        }
+    }
+}
+
+/// Repeats `attempt` with exponential backoff until it is done or runs out of attempts
+async fn with_retries<T, F, Fut>(operation: &str, mut attempt: F) -> napi::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Attempt<T>>,
+{
+    let mut attempts = 0;
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        attempts += 1;
+        match attempt().await {
+            Attempt::Done(result) => return result,
+            Attempt::Retry(e) if attempts < MAX_ATTEMPTS => {
+                // Jitter the backoff so that runners which failed together do not retry together
+                let delay = backoff.mul_f64(1.0 + rand::random::<f64>() * 0.5);
+                debug!(
+                    "Remote cache {} failed (attempt {}/{}), retrying in {:?}: {}",
+                    operation, attempts, MAX_ATTEMPTS, delay, e
+                );
+                time::sleep(delay).await;
+                backoff *= 2;
+            }
+            Attempt::Retry(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+fn request_error(err: &reqwest::Error) -> HttpRemoteCacheErrors {
+    HttpRemoteCacheErrors::RequestError(report_request_error(err))
+}
+
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// Sends everything written to it through a channel, blocking while the channel is full
+struct ChannelWriter(mpsc::Sender<io::Result<Vec<u8>>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(buf.to_vec()))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Upload was closed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads the chunks received from a channel, blocking until the next chunk arrives
+struct ChannelReader<B> {
+    receiver: mpsc::Receiver<io::Result<B>>,
+    chunk: Option<B>,
+    position: usize,
+}
+
+impl<B> ChannelReader<B> {
+    fn new(receiver: mpsc::Receiver<io::Result<B>>) -> Self {
+        Self {
+            receiver,
+            chunk: None,
+            position: 0,
+        }
+    }
+}
+
+impl<B: AsRef<[u8]>> Read for ChannelReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(chunk) = &self.chunk {
+                let remaining = &chunk.as_ref()[self.position..];
+                if !remaining.is_empty() {
+                    let len = remaining.len().min(buf.len());
+                    buf[..len].copy_from_slice(&remaining[..len]);
+                    self.position += len;
+                    return Ok(len);
+                }
+            }
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = Some(chunk?);
+                    self.position = 0;
+                }
+                // The sender finished, the channel is at its end
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use assert_fs::prelude::*;
+    use std::thread;
+
+    #[test]
+    fn should_stream_archives_through_a_channel() {
+        let temp = TempDir::new().unwrap();
+        temp.child("local/123/dist/main.js")
+            .write_str("content")
+            .unwrap();
+        let local = temp.join("local").display().to_string();
+        let other = temp.join("other").display().to_string();
+        let integrity = ArtifactIntegrity::default();
+
+        // A single slot forces the writer to wait for the reader
+        let (sender, receiver) = mpsc::channel(1);
+        let writer = thread::spawn(move || {
+            let writer = BufWriter::with_capacity(16, ChannelWriter(sender));
+            write_archive(
+                writer,
+                "123",
+                &local,
+                "output",
+                1,
+                &ArtifactIntegrity::default(),
+                ArchiveCompression::zstd(),
+            )
+            .unwrap()
+            .flush()
+            .unwrap();
+        });
+        let result =
+            extract_archive(ChannelReader::new(receiver), &other, "123", &integrity).unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(result.code, 1);
+        assert_eq!(result.terminal_output.as_deref(), Some("output"));
+        temp.child("other/123/dist/main.js").assert("content");
+    }
+
+    #[test]
+    fn should_close_the_writer_when_the_reader_is_dropped() {
+        let (sender, receiver) = mpsc::channel(1);
+        drop(receiver);
+
+        let err = ChannelWriter(sender).write(b"content").unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
     }
 }
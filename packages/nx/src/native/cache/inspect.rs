@@ -0,0 +1,247 @@
+use std::path::Path;
+use std::rc::Rc;
+
+use rusqlite::ToSql;
+use rusqlite::types::Value;
+use walkdir::WalkDir;
+
+use crate::native::cache::content_store::CacheManifest;
+use crate::native::hasher::HashAlgorithm;
+use crate::native::utils::Normalize;
+
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheEntrySort {
+    /// Most recently used first
+    AccessedAt,
+    /// Most recently created first
+    CreatedAt,
+    /// Largest first
+    Size,
+}
+
+/// Narrows down the entries returned by `NxCache.listEntries`.
+/// Timestamps are milliseconds since the unix epoch.
+#[napi(object)]
+#[derive(Default, Clone, Debug)]
+pub struct CacheEntryFilter {
+    pub hashes: Option<Vec<String>>,
+    pub project: Option<String>,
+    pub target: Option<String>,
+    /// Only entries created at or after this time
+    pub since: Option<i64>,
+    /// Only entries created before this time
+    pub until: Option<i64>,
+    /// Minimum size in bytes
+    pub min_size: Option<i64>,
+    /// Maximum size in bytes
+    pub max_size: Option<i64>,
+    pub code: Option<i16>,
+    pub sort_by: Option<CacheEntrySort>,
+    pub limit: Option<u32>,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheEntryInfo {
+    pub hash: String,
+    pub code: i16,
+    pub size: i64,
+    /// Milliseconds since the unix epoch
+    pub created_at: i64,
+    /// Milliseconds since the unix epoch
+    pub accessed_at: i64,
+    pub project: Option<String>,
+    pub target: Option<String>,
+    pub configuration: Option<String>,
+    /// The algorithm which produced the hash
+    pub hash_algorithm: HashAlgorithm,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheFileInfo {
+    /// Path relative to the outputs of the entry
+    pub path: String,
+    pub size: i64,
+    /// The content-addressed blob holding the file, if it is deduplicated
+    pub blob: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct CacheEntryDetails {
+    pub entry: CacheEntryInfo,
+    pub outputs_path: String,
+    pub terminal_output_path: String,
+    pub files: Vec<CacheFileInfo>,
+}
+
+/// Builds the query listing the entries matching `filter`.
+/// `with_task_details` joins the project and target each entry was created for.
+pub fn list_entries_query(
+    filter: &CacheEntryFilter,
+    with_task_details: bool,
+) -> (String, Vec<Box<dyn ToSql>>) {
+    let (details, join) = if with_task_details {
+        (
+            "task_details.project, task_details.target, task_details.configuration",
+            "LEFT JOIN task_details ON task_details.hash = cache_outputs.hash",
+        )
+    } else {
+        ("NULL, NULL, NULL", "")
+    };
+
+    let mut conditions: Vec<&str> = vec![];
+    let mut params: Vec<Box<dyn ToSql>> = vec![];
+    if let Some(hashes) = &filter.hashes {
+        conditions.push("cache_outputs.hash IN rarray(?)");
+        params.push(Box::new(Rc::new(
+            hashes.iter().cloned().map(Value::from).collect::<Vec<_>>(),
+        )));
+    }
+    if with_task_details {
+        if let Some(project) = &filter.project {
+            conditions.push("task_details.project = ?");
+            params.push(Box::new(project.clone()));
+        }
+        if let Some(target) = &filter.target {
+            conditions.push("task_details.target = ?");
+            params.push(Box::new(target.clone()));
+        }
+    } else if filter.project.is_some() || filter.target.is_some() {
+        // Entries are not linked to tasks, so none of them match
+        conditions.push("0");
+    }
+    if let Some(since) = filter.since {
+        conditions.push("cache_outputs.created_at >= datetime(? / 1000, 'unixepoch')");
+        params.push(Box::new(since));
+    }
+    if let Some(until) = filter.until {
+        conditions.push("cache_outputs.created_at < datetime(? / 1000, 'unixepoch')");
+        params.push(Box::new(until));
+    }
+    if let Some(min_size) = filter.min_size {
+        conditions.push("cache_outputs.size >= ?");
+        params.push(Box::new(min_size));
+    }
+    if let Some(max_size) = filter.max_size {
+        conditions.push("cache_outputs.size <= ?");
+        params.push(Box::new(max_size));
+    }
+    if let Some(code) = filter.code {
+        conditions.push("cache_outputs.code = ?");
+        params.push(Box::new(code));
+    }
+
+    let condition = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let order = match filter.sort_by.unwrap_or(CacheEntrySort::AccessedAt) {
+        CacheEntrySort::AccessedAt => "cache_outputs.accessed_at DESC",
+        CacheEntrySort::CreatedAt => "cache_outputs.created_at DESC",
+        CacheEntrySort::Size => "cache_outputs.size DESC",
+    };
+    let limit = filter
+        .limit
+        .map(|limit| format!("LIMIT {}", limit))
+        .unwrap_or_default();
+
+    (
+        format!(
+            "SELECT cache_outputs.hash, cache_outputs.code, cache_outputs.size,
+                CAST(strftime('%s', cache_outputs.created_at) AS INTEGER) * 1000,
+                CAST(strftime('%s', cache_outputs.accessed_at) AS INTEGER) * 1000,
+                {}, cache_outputs.hash_algorithm
+                FROM cache_outputs {}
+                {}
+                ORDER BY {}, cache_outputs.hash
+                {}",
+            details, join, condition, order, limit
+        ),
+        params,
+    )
+}
+
+pub fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<CacheEntryInfo> {
+    Ok(CacheEntryInfo {
+        hash: row.get(0)?,
+        code: row.get(1)?,
+        size: row.get(2)?,
+        created_at: row.get(3)?,
+        accessed_at: row.get(4)?,
+        project: row.get(5)?,
+        target: row.get(6)?,
+        configuration: row.get(7)?,
+        hash_algorithm: HashAlgorithm::from_name(&row.get::<_, String>(8)?),
+    })
+}
+
+/// Lists the files stored for an entry, with the blobs recorded in its manifest
+pub fn list_files(task_dir: &Path, manifest: Option<&CacheManifest>) -> Vec<CacheFileInfo> {
+    WalkDir::new(task_dir)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let path = entry
+                .path()
+                .strip_prefix(task_dir)
+                .ok()?
+                .to_normalized_string();
+            let size = entry.metadata().ok()?.len() as i64;
+            let blob = manifest
+                .and_then(|manifest| manifest.files.get(&path))
+                .filter(|file| file.stored)
+                .map(|file| file.blob.clone());
+            Some(CacheFileInfo { path, size, blob })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_only_filter_by_given_fields() {
+        let (query, params) = list_entries_query(&CacheEntryFilter::default(), true);
+
+        assert!(!query.contains("WHERE"));
+        assert!(query.contains("ORDER BY cache_outputs.accessed_at DESC"));
+        assert!(params.is_empty());
+
+        let (query, params) = list_entries_query(
+            &CacheEntryFilter {
+                project: Some("app".to_string()),
+                min_size: Some(1024),
+                sort_by: Some(CacheEntrySort::Size),
+                limit: Some(10),
+                ..Default::default()
+            },
+            true,
+        );
+
+        assert!(query.contains("WHERE task_details.project = ? AND cache_outputs.size >= ?"));
+        assert!(query.contains("ORDER BY cache_outputs.size DESC"));
+        assert!(query.contains("LIMIT 10"));
+        assert_eq!(params.len(), 2);
+    }
+
+    #[test]
+    fn should_not_match_projects_without_task_details() {
+        let (query, params) = list_entries_query(
+            &CacheEntryFilter {
+                project: Some("app".to_string()),
+                ..Default::default()
+            },
+            false,
+        );
+
+        assert!(query.contains("WHERE 0"));
+        assert!(params.is_empty());
+    }
+}
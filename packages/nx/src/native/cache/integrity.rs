@@ -0,0 +1,333 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+};
+
+use ring::{
+    digest, hmac,
+    signature::{self, Ed25519KeyPair, KeyPair, UnparsedPublicKey},
+};
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+use walkdir::WalkDir;
+
+use super::errors::HttpRemoteCacheErrors;
+
+/// Name of the archive entry holding the [`IntegrityManifest`]
+pub const INTEGRITY_ENTRY: &str = "integrity";
+
+const HMAC_PREFIX: &str = "hmac-sha256:";
+const ED25519_PREFIX: &str = "ed25519:";
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct IntegrityManifest {
+    /// SHA-256 digests of every entry in the archive, keyed by entry path
+    pub digests: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+enum SigningKey {
+    Hmac(hmac::Key),
+    Ed25519 {
+        key_pair: Option<Ed25519KeyPair>,
+        public_key: Vec<u8>,
+    },
+}
+
+/// Signs artifacts when they are stored and verifies them when they are retrieved.
+///
+/// Without a key only the content digests are checked, which catches corrupted
+/// artifacts. With a key every artifact must carry a valid signature.
+#[derive(Default)]
+pub struct ArtifactIntegrity {
+    key: Option<SigningKey>,
+}
+
+impl ArtifactIntegrity {
+    pub fn from_env() -> Result<Self, HttpRemoteCacheErrors> {
+        if let Ok(secret) = env::var("NX_SELF_HOSTED_REMOTE_CACHE_HMAC_KEY") {
+            return Ok(Self::hmac(secret.as_bytes()));
+        }
+        if let Ok(seed) = env::var("NX_SELF_HOSTED_REMOTE_CACHE_SIGNING_KEY") {
+            return Self::ed25519_signer(&from_hex(&seed)?);
+        }
+        if let Ok(public_key) = env::var("NX_SELF_HOSTED_REMOTE_CACHE_VERIFY_KEY") {
+            return Ok(Self::ed25519_verifier(&from_hex(&public_key)?));
+        }
+        Ok(Self::default())
+    }
+
+    pub fn hmac(secret: &[u8]) -> Self {
+        Self {
+            key: Some(SigningKey::Hmac(hmac::Key::new(hmac::HMAC_SHA256, secret))),
+        }
+    }
+
+    /// Signs and verifies with the ed25519 key derived from a 32 byte `seed`
+    pub fn ed25519_signer(seed: &[u8]) -> Result<Self, HttpRemoteCacheErrors> {
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(seed).map_err(|_| {
+            HttpRemoteCacheErrors::Misconfigured("Invalid ed25519 signing key".to_string())
+        })?;
+        Ok(Self {
+            key: Some(SigningKey::Ed25519 {
+                public_key: key_pair.public_key().as_ref().to_vec(),
+                key_pair: Some(key_pair),
+            }),
+        })
+    }
+
+    /// Only verifies signatures, artifacts cannot be stored without the signing key
+    pub fn ed25519_verifier(public_key: &[u8]) -> Self {
+        Self {
+            key: Some(SigningKey::Ed25519 {
+                key_pair: None,
+                public_key: public_key.to_vec(),
+            }),
+        }
+    }
+
+    /// Whether artifacts stored by this machine would pass verification elsewhere
+    pub fn can_store(&self) -> bool {
+        !matches!(self.key, Some(SigningKey::Ed25519 { key_pair: None, .. }))
+    }
+
+    pub fn seal(&self, hash: &str, digests: BTreeMap<String, String>) -> IntegrityManifest {
+        let payload = signed_payload(hash, &digests);
+        let signature = match &self.key {
+            Some(SigningKey::Hmac(key)) => Some(format!(
+                "{}{}",
+                HMAC_PREFIX,
+                to_hex(hmac::sign(key, &payload).as_ref())
+            )),
+            Some(SigningKey::Ed25519 {
+                key_pair: Some(key_pair),
+                ..
+            }) => Some(format!(
+                "{}{}",
+                ED25519_PREFIX,
+                to_hex(key_pair.sign(&payload).as_ref())
+            )),
+            _ => None,
+        };
+        IntegrityManifest { digests, signature }
+    }
+
+    pub fn verify(
+        &self,
+        hash: &str,
+        manifest: Option<&IntegrityManifest>,
+        digests: &BTreeMap<String, String>,
+    ) -> Result<(), HttpRemoteCacheErrors> {
+        let Some(manifest) = manifest else {
+            return match self.key {
+                Some(_) => Err(HttpRemoteCacheErrors::IntegrityError(format!(
+                    "artifact for {} is not signed",
+                    hash
+                ))),
+                None => {
+                    trace!("Artifact for {} has no integrity manifest", hash);
+                    Ok(())
+                }
+            };
+        };
+
+        if let Some(path) = manifest
+            .digests
+            .keys()
+            .chain(digests.keys())
+            .find(|path| manifest.digests.get(*path) != digests.get(*path))
+        {
+            return Err(HttpRemoteCacheErrors::IntegrityError(format!(
+                "content digest mismatch for {} in artifact {}",
+                path, hash
+            )));
+        }
+
+        let Some(key) = &self.key else {
+            return Ok(());
+        };
+        let payload = signed_payload(hash, digests);
+        let sig = manifest.signature.as_deref().unwrap_or_default();
+        let valid = match key {
+            SigningKey::Hmac(key) => sig
+                .strip_prefix(HMAC_PREFIX)
+                .and_then(|sig| from_hex(sig).ok())
+                .is_some_and(|sig| hmac::verify(key, &payload, &sig).is_ok()),
+            SigningKey::Ed25519 { public_key, .. } => sig
+                .strip_prefix(ED25519_PREFIX)
+                .and_then(|sig| from_hex(sig).ok())
+                .is_some_and(|sig| {
+                    UnparsedPublicKey::new(&signature::ED25519, public_key)
+                        .verify(&payload, &sig)
+                        .is_ok()
+                }),
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(HttpRemoteCacheErrors::IntegrityError(format!(
+                "invalid signature for artifact {}",
+                hash
+            )))
+        }
+    }
+}
+
+/// The signature covers the task hash, so an artifact cannot be replayed for another task
+fn signed_payload(hash: &str, digests: &BTreeMap<String, String>) -> Vec<u8> {
+    let mut payload = format!("{}\n", hash).into_bytes();
+    payload.extend(serde_json::to_vec(digests).expect("Digests are always serializable"));
+    payload
+}
+
+/// Digests every file and symlink below `outputs_path`, keyed by its relative path
+pub fn digest_directory(outputs_path: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut digests = BTreeMap::new();
+    for entry in WalkDir::new(outputs_path).min_depth(1) {
+        let entry = entry?;
+        let relative_path = entry
+            .path()
+            .strip_prefix(outputs_path)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        if entry.path_is_symlink() {
+            let target = fs::read_link(entry.path())?;
+            digests.insert(relative_path, symlink_digest(&target));
+        } else if entry.file_type().is_file() {
+            let (digest, _) = sha256_reader(File::open(entry.path())?)?;
+            digests.insert(relative_path, digest);
+        }
+    }
+    Ok(digests)
+}
+
+pub fn symlink_digest(target: &Path) -> String {
+    sha256(format!("symlink:{}", target.to_string_lossy()).as_bytes())
+}
+
+pub fn sha256(content: &[u8]) -> String {
+    to_hex(digest::digest(&digest::SHA256, content).as_ref())
+}
+
+/// Digests everything `reader` yields a chunk at a time, returning the digest and the
+/// number of bytes read
+pub fn sha256_reader(mut reader: impl Read) -> io::Result<(String, u64)> {
+    let mut context = digest::Context::new(&digest::SHA256);
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        context.update(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok((to_hex(context.finish().as_ref()), size))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(input: &str) -> Result<Vec<u8>, HttpRemoteCacheErrors> {
+    let input = input.trim();
+    if !input.is_ascii() || !input.len().is_multiple_of(2) {
+        return Err(HttpRemoteCacheErrors::Misconfigured(
+            "Keys must be hex encoded".to_string(),
+        ));
+    }
+    (0..input.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&input[i..i + 2], 16).map_err(|_| {
+                HttpRemoteCacheErrors::Misconfigured("Keys must be hex encoded".to_string())
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digests() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("dist/main.js".to_string(), sha256(b"content")),
+            ("code".to_string(), sha256(&0u32.to_be_bytes())),
+        ])
+    }
+
+    #[test]
+    fn should_digest_readers_in_chunks() {
+        let content = vec![7u8; 200 * 1024 + 3];
+        assert_eq!(
+            sha256_reader(&content[..]).unwrap(),
+            (sha256(&content), content.len() as u64)
+        );
+    }
+
+    #[test]
+    fn should_verify_digests_without_a_key() {
+        let integrity = ArtifactIntegrity::default();
+        let manifest = integrity.seal("123", digests());
+
+        assert_eq!(manifest.signature, None);
+        assert!(integrity.verify("123", Some(&manifest), &digests()).is_ok());
+        assert!(integrity.verify("123", None, &digests()).is_ok());
+
+        let mut tampered = digests();
+        tampered.insert("dist/main.js".to_string(), sha256(b"tampered"));
+        assert!(matches!(
+            integrity.verify("123", Some(&manifest), &tampered),
+            Err(HttpRemoteCacheErrors::IntegrityError(_))
+        ));
+    }
+
+    #[test]
+    fn should_verify_hmac_signatures() {
+        let integrity = ArtifactIntegrity::hmac(b"secret");
+        let manifest = integrity.seal("123", digests());
+
+        assert!(integrity.verify("123", Some(&manifest), &digests()).is_ok());
+        // Unsigned artifacts and artifacts signed for another hash are rejected
+        assert!(integrity.verify("123", None, &digests()).is_err());
+        assert!(
+            integrity
+                .verify("456", Some(&manifest), &digests())
+                .is_err()
+        );
+        assert!(
+            ArtifactIntegrity::hmac(b"other")
+                .verify("123", Some(&manifest), &digests())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn should_verify_ed25519_signatures() {
+        let signer = ArtifactIntegrity::ed25519_signer(&[7; 32]).unwrap();
+        let manifest = signer.seal("123", digests());
+        let public_key = Ed25519KeyPair::from_seed_unchecked(&[7; 32])
+            .unwrap()
+            .public_key()
+            .as_ref()
+            .to_vec();
+        let verifier = ArtifactIntegrity::ed25519_verifier(&public_key);
+
+        assert!(verifier.verify("123", Some(&manifest), &digests()).is_ok());
+        assert!(!verifier.can_store());
+        assert!(
+            verifier
+                .verify("123", Some(&verifier.seal("123", digests())), &digests())
+                .is_err()
+        );
+    }
+}
@@ -2,9 +2,27 @@ pub mod expand_outputs;
 pub mod file_ops;
 pub mod validate_outputs;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bundle;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod cache;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod compression;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod content_store;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod errors;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod eviction;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod fs_remote_cache;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod http_remote_cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod inspect;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod integrity;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod remote_cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod s3_remote_cache;
@@ -0,0 +1,670 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    env,
+    fs::{self},
+    future::Future,
+    io::{Read, Write},
+    path::{Component, Path},
+    sync::Arc,
+};
+
+use dashmap::DashMap;
+use futures::{StreamExt, future::try_join_all, stream};
+use tar::{Archive, Builder};
+use tokio::sync::OnceCell;
+use tracing::{debug, trace};
+
+use super::{
+    cache::CachedResult,
+    compression::{ArchiveCompression, decoder},
+    errors::HttpRemoteCacheErrors,
+    fs_remote_cache::FsRemoteCache,
+    http_remote_cache::HttpRemoteCache,
+    integrity::{
+        ArtifactIntegrity, INTEGRITY_ENTRY, IntegrityManifest, digest_directory, sha256,
+        sha256_reader, symlink_digest,
+    },
+    s3_remote_cache::S3RemoteCache,
+};
+use crate::native::tasks::types::TaskGraph;
+
+const DEFAULT_PREFETCH_CONCURRENCY: u32 = 8;
+
+/// A store for cache artifacts shared between machines.
+///
+/// Every backend exchanges the same compressed tar archives, created with [`create_archive`]
+/// and unpacked with [`extract_archive`], so artifacts stay compatible across backends.
+/// Archives carry an integrity manifest which is verified before an artifact is used.
+pub trait RemoteCache {
+    /// Downloads the artifact for `hash` and extracts it into `cache_directory`
+    fn retrieve(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+    ) -> impl Future<Output = napi::Result<Option<CachedResult>>>;
+
+    /// Uploads the cached outputs of `hash`. Returns `false` if the backend
+    /// declined to store the artifact, e.g. because it already exists.
+    fn store(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+        terminal_output: &str,
+        code: u32,
+    ) -> impl Future<Output = napi::Result<bool>>;
+
+    fn exists(&self, hash: &str) -> impl Future<Output = napi::Result<bool>>;
+
+    /// Returns the subset of `hashes` which have an artifact in the remote cache.
+    /// Backends without a batch lookup check every hash concurrently.
+    fn query(&self, hashes: &[String]) -> impl Future<Output = napi::Result<Vec<String>>> {
+        query_each(self, hashes)
+    }
+}
+
+/// Checks every hash with [`RemoteCache::exists`] concurrently
+pub async fn query_each<C: RemoteCache + ?Sized>(
+    cache: &C,
+    hashes: &[String],
+) -> napi::Result<Vec<String>> {
+    let exists = try_join_all(hashes.iter().map(|hash| cache.exists(hash))).await?;
+    Ok(hashes
+        .iter()
+        .zip(exists)
+        .filter_map(|(hash, exists)| exists.then(|| hash.clone()))
+        .collect())
+}
+
+enum RemoteCacheBackend {
+    Http(HttpRemoteCache),
+    FileSystem(FsRemoteCache),
+    S3(S3RemoteCache),
+}
+
+impl RemoteCache for RemoteCacheBackend {
+    async fn retrieve(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+    ) -> napi::Result<Option<CachedResult>> {
+        match self {
+            Self::Http(cache) => RemoteCache::retrieve(cache, hash, cache_directory).await,
+            Self::FileSystem(cache) => RemoteCache::retrieve(cache, hash, cache_directory).await,
+            Self::S3(cache) => RemoteCache::retrieve(cache, hash, cache_directory).await,
+        }
+    }
+
+    async fn store(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+        terminal_output: &str,
+        code: u32,
+    ) -> napi::Result<bool> {
+        match self {
+            Self::Http(cache) => {
+                RemoteCache::store(cache, hash, cache_directory, terminal_output, code).await
+            }
+            Self::FileSystem(cache) => {
+                RemoteCache::store(cache, hash, cache_directory, terminal_output, code).await
+            }
+            Self::S3(cache) => {
+                RemoteCache::store(cache, hash, cache_directory, terminal_output, code).await
+            }
+        }
+    }
+
+    async fn exists(&self, hash: &str) -> napi::Result<bool> {
+        match self {
+            Self::Http(cache) => RemoteCache::exists(cache, hash).await,
+            Self::FileSystem(cache) => RemoteCache::exists(cache, hash).await,
+            Self::S3(cache) => RemoteCache::exists(cache, hash).await,
+        }
+    }
+
+    async fn query(&self, hashes: &[String]) -> napi::Result<Vec<String>> {
+        match self {
+            Self::Http(cache) => RemoteCache::query(cache, hashes).await,
+            Self::FileSystem(cache) => RemoteCache::query(cache, hashes).await,
+            Self::S3(cache) => RemoteCache::query(cache, hashes).await,
+        }
+    }
+}
+
+type Retrieval = Arc<OnceCell<Option<CachedResult>>>;
+
+/// A self-hosted remote cache whose backend is picked from the environment:
+/// - `NX_SELF_HOSTED_REMOTE_CACHE_DIRECTORY` for a shared directory
+/// - `NX_SELF_HOSTED_REMOTE_CACHE_S3_BUCKET` for an S3-compatible bucket
+/// - `NX_SELF_HOSTED_REMOTE_CACHE_SERVER` for the HTTP protocol
+#[napi]
+pub struct SelfHostedRemoteCache {
+    backend: RemoteCacheBackend,
+    /// Downloads shared between `prefetch` and `retrieve`, keyed by hash
+    retrievals: DashMap<String, Retrieval>,
+}
+
+#[napi]
+impl SelfHostedRemoteCache {
+    #[napi(constructor)]
+    pub fn new() -> napi::Result<Self> {
+        let backend = if let Ok(directory) = env::var("NX_SELF_HOSTED_REMOTE_CACHE_DIRECTORY") {
+            trace!("Using shared directory remote cache: {}", &directory);
+            RemoteCacheBackend::FileSystem(FsRemoteCache::new(
+                directory,
+                ArtifactIntegrity::from_env()?,
+                ArchiveCompression::from_env()?,
+            ))
+        } else if env::var("NX_SELF_HOSTED_REMOTE_CACHE_S3_BUCKET").is_ok() {
+            trace!("Using S3 remote cache");
+            RemoteCacheBackend::S3(S3RemoteCache::from_env()?)
+        } else {
+            trace!("Using HTTP remote cache");
+            RemoteCacheBackend::Http(HttpRemoteCache::new()?)
+        };
+
+        Ok(Self {
+            backend,
+            retrievals: DashMap::new(),
+        })
+    }
+
+    #[napi]
+    pub async fn retrieve(
+        &self,
+        hash: String,
+        cache_directory: String,
+    ) -> napi::Result<Option<CachedResult>> {
+        let result = self.retrieve_once(&hash, &cache_directory).await;
+        self.retrievals.remove(&hash);
+        result
+    }
+
+    /// Downloads the artifact for `hash` unless a download is already in flight or done
+    async fn retrieve_once(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+    ) -> napi::Result<Option<CachedResult>> {
+        let retrieval = self.retrievals.entry(hash.to_string()).or_default().clone();
+        retrieval
+            .get_or_try_init(|| RemoteCache::retrieve(&self.backend, hash, cache_directory))
+            .await
+            .cloned()
+    }
+
+    #[napi]
+    pub async fn store(
+        &self,
+        hash: String,
+        cache_directory: String,
+        terminal_output: String,
+        code: u32,
+    ) -> napi::Result<bool> {
+        RemoteCache::store(
+            &self.backend,
+            &hash,
+            &cache_directory,
+            &terminal_output,
+            code,
+        )
+        .await
+    }
+
+    #[napi]
+    pub async fn exists(&self, hash: String) -> napi::Result<bool> {
+        RemoteCache::exists(&self.backend, &hash).await
+    }
+
+    #[napi]
+    pub async fn query(&self, hashes: Vec<String>) -> napi::Result<Vec<String>> {
+        RemoteCache::query(&self.backend, &hashes).await
+    }
+
+    /// Downloads the artifacts of every task in `task_graph` which already has a hash
+    /// and exists in the remote cache, so that later calls to `retrieve` resolve
+    /// without waiting on the network. Returns the hashes which were prefetched.
+    #[napi]
+    pub async fn prefetch(
+        &self,
+        task_graph: TaskGraph,
+        cache_directory: String,
+        concurrency: Option<u32>,
+    ) -> napi::Result<Vec<String>> {
+        let hashes = task_graph
+            .tasks
+            .into_values()
+            .filter_map(|task| task.hash)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let found = RemoteCache::query(&self.backend, &hashes).await?;
+        debug!(
+            "Prefetching {} of {} task hashes",
+            found.len(),
+            hashes.len()
+        );
+
+        let concurrency = concurrency.unwrap_or(DEFAULT_PREFETCH_CONCURRENCY).max(1) as usize;
+        let prefetched = stream::iter(found)
+            .map(|hash| {
+                let cache_directory = &cache_directory;
+                async move {
+                    match self.retrieve_once(&hash, cache_directory).await {
+                        Ok(Some(_)) => Some(hash),
+                        Ok(None) => None,
+                        Err(e) => {
+                            // Prefetching is best effort, retrieve will report the error again
+                            trace!("Unable to prefetch {}: {:?}", &hash, e);
+                            self.retrievals.remove(&hash);
+                            None
+                        }
+                    }
+                }
+            })
+            .buffer_unordered(concurrency)
+            .filter_map(|hash| async move { hash })
+            .collect::<Vec<_>>()
+            .await;
+
+        Ok(prefetched)
+    }
+}
+
+/// Creates the archive for `hash` in memory
+pub fn create_archive(
+    hash: &str,
+    cache_directory: &str,
+    terminal_output: &str,
+    code: u32,
+    integrity: &ArtifactIntegrity,
+    compression: ArchiveCompression,
+) -> anyhow::Result<Vec<u8>> {
+    let buffer = write_archive(
+        Vec::new(),
+        hash,
+        cache_directory,
+        terminal_output,
+        code,
+        integrity,
+        compression,
+    )?;
+    trace!("Read tarball into memory");
+    Ok(buffer)
+}
+
+/// Writes the archive for `hash` to `writer` as it is created, returning the writer
+pub fn write_archive<W: Write>(
+    writer: W,
+    hash: &str,
+    cache_directory: &str,
+    terminal_output: &str,
+    code: u32,
+    integrity: &ArtifactIntegrity,
+    compression: ArchiveCompression,
+) -> anyhow::Result<W> {
+    // We can change the creation of the tar in a future version without
+    // worrying about breaking existing user cache's, because when the
+    // user updates their task's hashes will be changed... so users
+    // retrieving old hashes will not be affected, and new entries
+    // will have distinct hashes.
+
+    // create a tarball from the cache dir
+    let enc = compression.encoder(writer)?;
+    let mut archive = Builder::new(enc);
+    archive.follow_symlinks(false);
+    trace!("Created tar file for writing");
+
+    let cache_path = Path::new(cache_directory);
+    let outputs_path = cache_path.join(hash);
+
+    trace!("Adding cache artifacts to tarball");
+    archive.append_dir_all("", &outputs_path)?;
+    trace!("Added cache directory to tarball");
+
+    let mut digests = digest_directory(&outputs_path)?;
+
+    trace!("Adding terminal output to tarball");
+    let mut terminal_output_header = tar::Header::new_old();
+    let terminal_output_bytes = terminal_output.as_bytes();
+    terminal_output_header.set_size(terminal_output_bytes.len() as u64);
+    terminal_output_header.set_cksum(); // Ensure the checksum is set correctly
+    archive.append_data(
+        &mut terminal_output_header,
+        "terminalOutput",
+        terminal_output_bytes,
+    )?;
+    digests.insert("terminalOutput".to_string(), sha256(terminal_output_bytes));
+    trace!("Added terminal output to tarball");
+
+    trace!("Adding code to tarball");
+    let mut code_header = tar::Header::new_old();
+    code_header.set_size(4);
+    code_header.set_cksum(); // Ensure the checksum is set correctly
+    archive.append_data(&mut code_header, "code", &code.to_be_bytes()[..])?;
+    digests.insert("code".to_string(), sha256(&code.to_be_bytes()));
+    trace!("Added code to tarball");
+
+    trace!("Adding integrity manifest to tarball");
+    let manifest = serde_json::to_vec(&integrity.seal(hash, digests))?;
+    let mut integrity_header = tar::Header::new_old();
+    integrity_header.set_size(manifest.len() as u64);
+    integrity_header.set_cksum();
+    archive.append_data(&mut integrity_header, INTEGRITY_ENTRY, &manifest[..])?;
+    trace!("Added integrity manifest to tarball");
+
+    trace!("Finishing tarball");
+    archive
+        .finish() // Finish the archive to get the inner bytes
+        .map_err(|e| anyhow::anyhow!(format!("Failed to finish tarball: {}", e)))?;
+    trace!("Finished tarball");
+
+    let writer = archive.into_inner()?.finish()?;
+    Ok(writer)
+}
+
+/// Extracts an artifact into `cache_directory` and verifies it against its
+/// integrity manifest. The artifact is extracted into a staging directory first and
+/// only moved into place once it passed verification, so a tampered artifact never
+/// reaches the cache directory.
+pub fn extract_archive(
+    content: impl Read,
+    cache_directory: &str,
+    hash: &str,
+    integrity: &ArtifactIntegrity,
+) -> napi::Result<CachedResult> {
+    let output_dir = Path::new(cache_directory).join(hash);
+    let staging_dir =
+        Path::new(cache_directory).join(format!("{}.staging-{}", hash, std::process::id()));
+    let _ = fs::remove_dir_all(&staging_dir);
+
+    let verified = unpack_archive(content, &staging_dir).and_then(|unpacked| {
+        integrity
+            .verify(hash, unpacked.manifest.as_ref(), &unpacked.digests)
+            .map_err(anyhow::Error::from)?;
+        let code = unpacked.code.ok_or_else(|| {
+            HttpRemoteCacheErrors::IntegrityError(format!("artifact for {} has no exit code", hash))
+        })?;
+        move_into_place(&staging_dir, &output_dir)?;
+        Ok((unpacked, code))
+    });
+
+    let (unpacked, code) = match verified {
+        Ok(verified) => verified,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging_dir);
+            return Err(match e.downcast::<HttpRemoteCacheErrors>() {
+                Ok(e) => e.into(),
+                Err(e) => e.into(),
+            });
+        }
+    };
+
+    trace!("Extracted tarball to {}", output_dir.display());
+
+    Ok(CachedResult {
+        terminal_output: unpacked.terminal_output,
+        // Codes which do not fit are still reported as failures
+        code: i16::try_from(code).unwrap_or(i16::MAX),
+        outputs_path: output_dir.to_string_lossy().into_owned(),
+        size: Some(unpacked.size),
+    })
+}
+
+/// Replaces `output_dir` with the verified `staging_dir`
+fn move_into_place(staging_dir: &Path, output_dir: &Path) -> anyhow::Result<()> {
+    // The outputs may have no files, in which case nothing was staged
+    fs::create_dir_all(staging_dir)?;
+    if output_dir.exists() {
+        fs::remove_dir_all(output_dir)?;
+    }
+    match fs::rename(staging_dir, output_dir) {
+        // Another process retrieved the same artifact in the meantime
+        Err(_) if output_dir.exists() => {
+            fs::remove_dir_all(staging_dir)?;
+            Ok(())
+        }
+        result => Ok(result?),
+    }
+}
+
+#[derive(Default)]
+struct UnpackedArchive {
+    code: Option<u32>,
+    terminal_output: Option<String>,
+    size: i64,
+    digests: BTreeMap<String, String>,
+    manifest: Option<IntegrityManifest>,
+}
+
+/// Unpacks the outputs of an archive into `staging_dir`, recording the digest of every entry.
+/// Nothing is verified yet, so `staging_dir` must not be used before the digests are.
+fn unpack_archive(content: impl Read, staging_dir: &Path) -> anyhow::Result<UnpackedArchive> {
+    let tar = decoder(content)?;
+    let mut archive = Archive::new(tar);
+    let entries = archive
+        .entries() // Get the entries in the archive
+        .map_err(|_| anyhow::anyhow!("Failed to read entries from tarball"))?;
+
+    let mut unpacked = UnpackedArchive::default();
+    fs::create_dir_all(staging_dir)?;
+
+    // Extract the archive to the staging directory
+    for entry in entries {
+        let mut entry = entry.map_err(|_| anyhow::anyhow!("Failed to read entry from tarball"))?;
+
+        let entry_path = entry.path()?.into_owned();
+        // Never write outside of the staging directory
+        if !entry_path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(HttpRemoteCacheErrors::IntegrityError(format!(
+                "unexpected entry {}",
+                entry_path.display()
+            ))
+            .into());
+        }
+        let entry_path = entry_path.to_string_lossy().replace('\\', "/");
+
+        if entry_path == "code" {
+            let code_file_bytes = entry.bytes().collect::<Result<Vec<u8>, _>>()?;
+            unpacked
+                .digests
+                .insert(entry_path, sha256(&code_file_bytes));
+            // The code is written as a big-endian u32 by `write_archive`
+            let Ok(code_bytes) = <[u8; 4]>::try_from(&code_file_bytes[..]) else {
+                return Err(HttpRemoteCacheErrors::IntegrityError(format!(
+                    "invalid exit code of {} bytes",
+                    code_file_bytes.len()
+                ))
+                .into());
+            };
+            let code = u32::from_be_bytes(code_bytes);
+            unpacked.code = Some(code);
+            trace!("Retrieved exit code from cache: {}", code);
+        } else if entry_path == "terminalOutput" {
+            let terminal_output_bytes = entry.bytes().collect::<Result<Vec<u8>, _>>()?;
+            let terminal_output_size = terminal_output_bytes.len();
+
+            unpacked
+                .digests
+                .insert(entry_path, sha256(&terminal_output_bytes));
+            unpacked.terminal_output = Some(String::from_utf8(terminal_output_bytes)?);
+            unpacked.size += terminal_output_size as i64;
+
+            trace!(
+                "Retrieved terminal output from cache: {} bytes",
+                terminal_output_size
+            );
+        } else if entry_path == INTEGRITY_ENTRY {
+            unpacked.manifest = Some(serde_json::from_reader(&mut entry)?);
+        } else {
+            trace!("Extracting entry {}", &entry_path);
+            let entry_type = entry.header().entry_type();
+            if !(entry_type.is_file() || entry_type.is_dir() || entry_type.is_symlink()) {
+                return Err(HttpRemoteCacheErrors::IntegrityError(format!(
+                    "unexpected entry {} of type {:?}",
+                    entry_path, entry_type
+                ))
+                .into());
+            }
+            let link_name = entry.link_name()?.map(|link| link.into_owned());
+            // Refuses entries which would be written through a symlink out of the staging directory
+            let extracted = entry.unpack_in(staging_dir).map_err(|e| {
+                HttpRemoteCacheErrors::IntegrityError(format!(
+                    "Failed to unpack entry {}: {}",
+                    entry_path, e
+                ))
+            })?;
+            if !extracted {
+                return Err(HttpRemoteCacheErrors::IntegrityError(format!(
+                    "unexpected entry {}",
+                    entry_path
+                ))
+                .into());
+            }
+            if entry_type.is_file() {
+                let (digest, size) = sha256_reader(fs::File::open(staging_dir.join(&entry_path))?)?;
+                unpacked.size += size as i64;
+                unpacked.digests.insert(entry_path, digest);
+            } else if let Some(target) = link_name.filter(|_| entry_type.is_symlink()) {
+                unpacked.digests.insert(entry_path, symlink_digest(&target));
+            }
+        }
+    }
+
+    Ok(unpacked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::native::cache::compression::ArchiveEncoder;
+    use assert_fs::TempDir;
+    use assert_fs::prelude::*;
+
+    fn archive(build: impl FnOnce(&mut Builder<ArchiveEncoder<Vec<u8>>>)) -> Vec<u8> {
+        let mut builder = Builder::new(ArchiveCompression::default().encoder(Vec::new()).unwrap());
+        build(&mut builder);
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn append(builder: &mut Builder<ArchiveEncoder<Vec<u8>>>, path: &str, data: &[u8]) {
+        let mut header = tar::Header::new_old();
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, path, data).unwrap();
+    }
+
+    #[test]
+    fn should_not_extract_tampered_artifacts() {
+        let temp = TempDir::new().unwrap();
+        temp.child("local/123/dist/main.js")
+            .write_str("content")
+            .unwrap();
+        let local = temp.join("local").display().to_string();
+        let content = create_archive(
+            "123",
+            &local,
+            "",
+            0,
+            &ArtifactIntegrity::default(),
+            ArchiveCompression::default(),
+        )
+        .unwrap();
+        let other = temp.join("other").display().to_string();
+
+        let result =
+            extract_archive(&content[..], &other, "123", &ArtifactIntegrity::default()).unwrap();
+        assert_eq!(result.code, 0);
+        temp.child("other/123/dist/main.js").assert("content");
+
+        // The same manifest with other contents
+        let manifest = serde_json::to_vec(&ArtifactIntegrity::default().seal(
+            "456",
+            BTreeMap::from([
+                ("dist/main.js".to_string(), sha256(b"content")),
+                ("code".to_string(), sha256(&0u32.to_be_bytes())),
+            ]),
+        ))
+        .unwrap();
+        let tampered = archive(|builder| {
+            append(builder, "dist/main.js", b"tampered");
+            append(builder, "code", &0u32.to_be_bytes());
+            append(builder, INTEGRITY_ENTRY, &manifest);
+        });
+
+        assert!(
+            extract_archive(&tampered[..], &other, "456", &ArtifactIntegrity::default()).is_err()
+        );
+        assert_eq!(
+            fs::read_dir(temp.join("other"))
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect::<Vec<_>>(),
+            vec!["123"]
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_exit_codes() {
+        let temp = TempDir::new().unwrap();
+        let cache_directory = temp.display().to_string();
+
+        let short_code = archive(|builder| append(builder, "code", &[1]));
+        assert!(
+            extract_archive(
+                &short_code[..],
+                &cache_directory,
+                "123",
+                &ArtifactIntegrity::default()
+            )
+            .is_err()
+        );
+
+        let no_code = archive(|builder| append(builder, "terminalOutput", b"output"));
+        assert!(
+            extract_archive(
+                &no_code[..],
+                &cache_directory,
+                "123",
+                &ArtifactIntegrity::default()
+            )
+            .is_err()
+        );
+        assert!(!temp.child("123").path().exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_not_write_through_symlinks_out_of_the_cache() {
+        let temp = TempDir::new().unwrap();
+        let outside = temp.child("outside");
+        outside.create_dir_all().unwrap();
+        let cache_directory = temp.join("cache").display().to_string();
+
+        let escaping = archive(|builder| {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder
+                .append_link(&mut header, "dist", outside.path())
+                .unwrap();
+            append(builder, "dist/main.js", b"content");
+            append(builder, "code", &0u32.to_be_bytes());
+        });
+
+        assert!(
+            extract_archive(
+                &escaping[..],
+                &cache_directory,
+                "123",
+                &ArtifactIntegrity::default()
+            )
+            .is_err()
+        );
+        assert!(!outside.child("main.js").path().exists());
+        assert!(!temp.child("cache/123").path().exists());
+    }
+}
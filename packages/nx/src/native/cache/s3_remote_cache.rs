@@ -0,0 +1,300 @@
+use std::env;
+
+use chrono::Utc;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url};
+use ring::hmac;
+use tracing::trace;
+
+use super::{
+    cache::CachedResult,
+    compression::ArchiveCompression,
+    errors::{HttpRemoteCacheErrors, convert_response_to_error, report_request_error},
+    integrity::{ArtifactIntegrity, sha256, to_hex},
+    remote_cache::{RemoteCache, create_archive, extract_archive},
+};
+
+const EMPTY_PAYLOAD_HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// Stores cache artifacts as `<prefix><hash>.tar.gz` objects in an S3-compatible bucket.
+/// Requests are signed with AWS Signature Version 4 and use path-style addressing,
+/// which is supported by AWS as well as MinIO, R2 and other compatible stores.
+pub struct S3RemoteCache {
+    client: Client,
+    endpoint: Url,
+    bucket: String,
+    prefix: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    integrity: ArtifactIntegrity,
+    compression: ArchiveCompression,
+}
+
+impl S3RemoteCache {
+    pub fn from_env() -> Result<Self, HttpRemoteCacheErrors> {
+        let bucket = env::var("NX_SELF_HOSTED_REMOTE_CACHE_S3_BUCKET").map_err(|_| {
+            HttpRemoteCacheErrors::Misconfigured(
+                "NX_SELF_HOSTED_REMOTE_CACHE_S3_BUCKET must be set".to_string(),
+            )
+        })?;
+        let region = env::var("NX_SELF_HOSTED_REMOTE_CACHE_S3_REGION")
+            .or_else(|_| env::var("AWS_REGION"))
+            .unwrap_or_else(|_| "us-east-1".to_string());
+        let endpoint = env::var("NX_SELF_HOSTED_REMOTE_CACHE_S3_ENDPOINT")
+            .unwrap_or_else(|_| format!("https://s3.{}.amazonaws.com", region));
+        let endpoint = Url::parse(&endpoint).map_err(|e| {
+            HttpRemoteCacheErrors::Misconfigured(format!("Invalid S3 endpoint {}: {}", endpoint, e))
+        })?;
+        let (Ok(access_key_id), Ok(secret_access_key)) = (
+            env::var("AWS_ACCESS_KEY_ID"),
+            env::var("AWS_SECRET_ACCESS_KEY"),
+        ) else {
+            return Err(HttpRemoteCacheErrors::Misconfigured(
+                "AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set".to_string(),
+            ));
+        };
+
+        Ok(Self {
+            client: Client::new(),
+            endpoint,
+            bucket,
+            prefix: env::var("NX_SELF_HOSTED_REMOTE_CACHE_S3_PREFIX").unwrap_or_default(),
+            region,
+            access_key_id,
+            secret_access_key,
+            session_token: env::var("AWS_SESSION_TOKEN").ok(),
+            integrity: ArtifactIntegrity::from_env()?,
+            compression: ArchiveCompression::from_env()?,
+        })
+    }
+
+    fn object_path(&self, hash: &str) -> String {
+        format!(
+            "/{}/{}",
+            uri_encode(&self.bucket, false),
+            uri_encode(&format!("{}{}.tar.gz", self.prefix, hash), false)
+        )
+    }
+
+    /// Builds a request for the artifact of `hash`, signed with AWS Signature Version 4
+    fn signed_request(&self, method: Method, hash: &str, payload: Option<&[u8]>) -> RequestBuilder {
+        let path = self.object_path(hash);
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = payload
+            .map(sha256)
+            .unwrap_or_else(|| EMPTY_PAYLOAD_HASH.to_string());
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "{}\n{}\n\n{}\n{}\n{}",
+            method, path, canonical_headers, signed_headers, payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256(canonical_request.as_bytes())
+        );
+
+        let signing_key = [self.region.as_str(), "s3", "aws4_request"].iter().fold(
+            hmac_sha256(
+                format!("AWS4{}", self.secret_access_key).as_bytes(),
+                date.as_bytes(),
+            ),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+        let signature = to_hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+        let mut request = self.client.request(method, url).header(
+            reqwest::header::AUTHORIZATION,
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.access_key_id, scope, signed_headers, signature
+            ),
+        );
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        request
+    }
+
+    async fn send(&self, request: RequestBuilder) -> napi::Result<Response> {
+        request.send().await.map_err(|e| {
+            napi::Error::from(HttpRemoteCacheErrors::RequestError(report_request_error(
+                &e,
+            )))
+        })
+    }
+}
+
+impl RemoteCache for S3RemoteCache {
+    async fn retrieve(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+    ) -> napi::Result<Option<CachedResult>> {
+        let response = self
+            .send(self.signed_request(Method::GET, hash, None))
+            .await?;
+        trace!("S3 response status: {}", response.status());
+
+        match response.status() {
+            StatusCode::OK => {
+                let content = response
+                    .bytes()
+                    .await
+                    .map_err(|e| HttpRemoteCacheErrors::RequestError(report_request_error(&e)))?;
+                trace!("Downloaded {} bytes from S3", content.len());
+                Ok(Some(extract_archive(
+                    &content[..],
+                    cache_directory,
+                    hash,
+                    &self.integrity,
+                )?))
+            }
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Err(convert_s3_response_to_error(response).await.into()),
+        }
+    }
+
+    async fn store(
+        &self,
+        hash: &str,
+        cache_directory: &str,
+        terminal_output: &str,
+        code: u32,
+    ) -> napi::Result<bool> {
+        if !self.integrity.can_store() {
+            trace!("Not storing {} without a signing key", hash);
+            return Ok(false);
+        }
+
+        let buffer = create_archive(
+            hash,
+            cache_directory,
+            terminal_output,
+            code,
+            &self.integrity,
+            self.compression,
+        )?;
+        let request = self
+            .signed_request(Method::PUT, hash, Some(&buffer))
+            // Never overwrite an existing artifact
+            .header(reqwest::header::IF_NONE_MATCH, "*")
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                self.compression.content_type(),
+            )
+            .body(buffer);
+        let response = self.send(request).await?;
+
+        match response.status() {
+            StatusCode::OK => Ok(true),
+            // Object already exists, silently do not store new data
+            StatusCode::PRECONDITION_FAILED | StatusCode::CONFLICT => Ok(false),
+            _ => Err(convert_s3_response_to_error(response).await.into()),
+        }
+    }
+
+    async fn exists(&self, hash: &str) -> napi::Result<bool> {
+        let response = self
+            .send(self.signed_request(Method::HEAD, hash, None))
+            .await?;
+
+        match response.status() {
+            StatusCode::OK => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            _ => Err(convert_s3_response_to_error(response).await.into()),
+        }
+    }
+}
+
+/// S3 rejects bad credentials with a 403 and an XML error body instead of a plain text 401
+async fn convert_s3_response_to_error(response: Response) -> HttpRemoteCacheErrors {
+    if response.status() == StatusCode::FORBIDDEN {
+        HttpRemoteCacheErrors::Unauthorized(
+            response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Forbidden".to_string()),
+        )
+    } else {
+        convert_response_to_error(response).await
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::sign(&key, data).as_ref().to_vec()
+}
+
+/// Percent-encodes everything except unreserved characters, as required for SigV4
+fn uri_encode(input: &str, encode_slash: bool) -> String {
+    input
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b'/' if !encode_slash => "/".to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_uri_encode_object_keys() {
+        assert_eq!(
+            uri_encode("nx/cache 1.tar.gz", false),
+            "nx/cache%201.tar.gz"
+        );
+        assert_eq!(uri_encode("a/b", true), "a%2Fb");
+    }
+
+    #[test]
+    fn should_derive_signing_keys() {
+        // Example from https://docs.aws.amazon.com/general/latest/gr/signature-v4-examples.html
+        let signing_key = ["us-east-1", "iam", "aws4_request"].iter().fold(
+            hmac_sha256(b"AWS4wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", b"20120215"),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+
+        assert_eq!(
+            to_hex(&signing_key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+}
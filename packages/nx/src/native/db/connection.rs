@@ -1,6 +1,9 @@
 use anyhow::Result;
 
-use rusqlite::{Connection, DatabaseName, Error, OptionalExtension, Params, Row, Statement, ToSql};
+use rusqlite::{
+    Connection, DatabaseName, Error, OptionalExtension, Params, Row, Statement, ToSql,
+    TransactionBehavior,
+};
 use std::thread;
 use std::time::Duration;
 use tracing::trace;
@@ -89,10 +92,28 @@ impl NxDbConnection {
     pub fn transaction<T>(
         &mut self,
         transaction_operation: impl Fn(&Connection) -> rusqlite::Result<T>,
+    ) -> Result<T> {
+        self.transaction_with_behavior(TransactionBehavior::Deferred, transaction_operation)
+    }
+
+    /// Runs `transaction_operation` in a transaction which takes the write lock up front, so
+    /// whatever it reads cannot be changed by another connection before it writes
+    pub fn immediate_transaction<T>(
+        &mut self,
+        transaction_operation: impl Fn(&Connection) -> rusqlite::Result<T>,
+    ) -> Result<T> {
+        self.transaction_with_behavior(TransactionBehavior::Immediate, transaction_operation)
+    }
+
+    fn transaction_with_behavior<T>(
+        &mut self,
+        behavior: TransactionBehavior,
+        transaction_operation: impl Fn(&Connection) -> rusqlite::Result<T>,
     ) -> Result<T> {
         if let Some(conn) = self.conn.as_mut() {
-            let transaction = retry_db_operation_when_busy!(conn.transaction())
-                .map_err(|e| anyhow::anyhow!("DB transaction error: {:?}", e))?;
+            let transaction =
+                retry_db_operation_when_busy!(conn.transaction_with_behavior(behavior))
+                    .map_err(|e| anyhow::anyhow!("DB transaction error: {:?}", e))?;
 
             let result = transaction_operation(&transaction)
                 .map_err(|e| anyhow::anyhow!("DB transaction operation error: {:?}", e))?;
@@ -120,6 +141,14 @@ impl NxDbConnection {
         }
     }
 
+    /// The path of the database file, if the database is not in memory
+    pub fn path(&self) -> Option<&str> {
+        self.conn
+            .as_ref()
+            .and_then(|conn| conn.path())
+            .filter(|path| !path.is_empty())
+    }
+
     pub fn close(self) -> Result<()> {
         trace!("Closing database connection");
         if let Some(conn) = self.conn {
@@ -1,4 +1,5 @@
 use crate::native::db::connection::NxDbConnection;
+use crate::native::db::migrations::{create_schema_migrations_table, has_schema_migrations};
 use rusqlite::vtab::array;
 use rusqlite::{Connection, OpenFlags};
 use std::fs::{File, remove_file};
@@ -49,16 +50,30 @@ pub(super) fn initialize_db(nx_version: String, db_path: &Path) -> anyhow::Resul
                 },
             );
             let c = match db_version {
-                Ok(Some(version)) if version == nx_version => {
+                Ok(Some(version)) if version == nx_version && has_schema_migrations(&c)? => {
                     trace!("Database is compatible with Nx {}", nx_version);
                     c
                 }
+                // Each module migrates its own tables when it is set up
+                Ok(Some(version)) if has_schema_migrations(&c)? => {
+                    trace!(
+                        "Keeping database created by Nx {} for Nx {}",
+                        version, nx_version
+                    );
+                    c.execute(
+                        "UPDATE metadata SET value = ?1 WHERE key = 'NX_VERSION'",
+                        [&nx_version],
+                    )?;
+                    c
+                }
                 // If there is no metadata, it means that this database is new
                 Err(s) if s.to_string().contains("metadata") => {
                     configure_database(&c)?;
                     create_metadata_table(&mut c, &nx_version)?;
                     c
                 }
+                // Databases created before migrations existed have an unknown schema, even when
+                // they were created by the same version of Nx
                 reason => {
                     trace!("Incompatible database because: {:?}", reason);
                     trace!("Disconnecting from existing incompatible database");
@@ -104,6 +119,7 @@ fn create_metadata_table(c: &mut NxDbConnection, nx_version: &str) -> anyhow::Re
         )?;
         Ok(())
     })?;
+    create_schema_migrations_table(c)?;
 
     Ok(())
 }
@@ -140,6 +156,7 @@ fn configure_database(connection: &NxDbConnection) -> anyhow::Result<()> {
 
 #[cfg(test)]
 mod tests {
+    use crate::native::db::migrations::{Migration, migrate};
     use crate::native::logger::enable_logger;
 
     use super::*;
@@ -185,15 +202,26 @@ mod tests {
     }
 
     #[test]
-    fn initialize_db_recreates_incompatible_db() -> anyhow::Result<()> {
+    fn initialize_db_keeps_data_across_nx_versions() -> anyhow::Result<()> {
         enable_logger();
         let temp_dir = tempfile::tempdir()?;
         let db_path = temp_dir.path().join("test.db");
-        //
+
         // Create initial db
-        let _ = initialize_db("1.0.0".to_string(), &db_path)?;
+        let mut conn = initialize_db("1.0.0".to_string(), &db_path)?;
+        migrate(
+            &mut conn,
+            "items",
+            &[Migration {
+                version: 1,
+                description: "Create items",
+                sql: "CREATE TABLE items (name TEXT NOT NULL);",
+            }],
+        )?;
+        conn.execute("INSERT INTO items (name) VALUES ('a')", [])?;
+        conn.close()?;
 
-        // Try to initialize with different version
+        // Initialize with a different version
         let conn = initialize_db("2.0.0".to_string(), &db_path)?;
 
         let version: Option<String> = conn.query_row(
@@ -201,8 +229,50 @@ mod tests {
             [],
             |row| row.get(0),
         )?;
-
         assert_eq!(version.unwrap(), "2.0.0");
+
+        let items: Option<u32> =
+            conn.query_row("SELECT COUNT(*) FROM items", [], |row| row.get(0))?;
+        assert_eq!(items, Some(1));
+        Ok(())
+    }
+
+    fn assert_recreates_db_without_migrations(nx_version: &str) -> anyhow::Result<()> {
+        enable_logger();
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join("test.db");
+
+        // Create a db like Nx did before migrations existed
+        let conn = initialize_db("1.0.0".to_string(), &db_path)?;
+        conn.execute_batch(
+            "DROP TABLE schema_migrations;
+            CREATE TABLE items (name TEXT NOT NULL);",
+        )?;
+        conn.close()?;
+
+        let conn = initialize_db(nx_version.to_string(), &db_path)?;
+
+        let version: Option<String> = conn.query_row(
+            "SELECT value FROM metadata WHERE key='NX_VERSION'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(version.unwrap(), nx_version);
+        assert!(has_schema_migrations(&conn)?);
+        assert!(
+            conn.query_row("SELECT COUNT(*) FROM items", [], |row| row.get::<_, u32>(0))
+                .is_err()
+        );
         Ok(())
     }
+
+    #[test]
+    fn initialize_db_recreates_db_without_migrations() -> anyhow::Result<()> {
+        assert_recreates_db_without_migrations("2.0.0")
+    }
+
+    #[test]
+    fn initialize_db_recreates_db_of_same_version_without_migrations() -> anyhow::Result<()> {
+        assert_recreates_db_without_migrations("1.0.0")
+    }
 }
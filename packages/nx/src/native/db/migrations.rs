@@ -0,0 +1,212 @@
+use crate::native::db::connection::NxDbConnection;
+use rusqlite::params;
+use std::fs::remove_file;
+use tracing::{debug, trace};
+
+/// A forward-only change to the tables owned by one module.
+///
+/// Released migrations must never be edited or removed. Schema changes are made by adding a
+/// migration with the next version, which keeps the data of existing databases.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+const CREATE_SCHEMA_MIGRATIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+        module TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        description TEXT NOT NULL,
+        applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (module, version)
+    )";
+
+const SCHEMA_VERSION_QUERY: &str =
+    "SELECT COALESCE(MAX(version), 0) FROM schema_migrations WHERE module = ?1";
+
+pub(super) fn create_schema_migrations_table(db: &NxDbConnection) -> anyhow::Result<()> {
+    db.execute(CREATE_SCHEMA_MIGRATIONS_TABLE, [])?;
+    Ok(())
+}
+
+/// Whether the tables of the database are managed by migrations. Databases created before
+/// migrations existed have an unknown schema.
+pub(super) fn has_schema_migrations(db: &NxDbConnection) -> anyhow::Result<bool> {
+    Ok(db
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_migrations')",
+            [],
+            |row| row.get(0),
+        )?
+        .unwrap_or(false))
+}
+
+/// Brings the tables of `module` up to date by applying the `migrations` which have not been
+/// applied yet, in order of their version.
+///
+/// The database is backed up before existing tables are migrated. All pending migrations are
+/// applied in a single transaction, so a failing migration leaves the database untouched.
+pub fn migrate(
+    db: &mut NxDbConnection,
+    module: &str,
+    migrations: &[Migration],
+) -> anyhow::Result<()> {
+    debug_assert!(
+        migrations.windows(2).all(|m| m[0].version < m[1].version),
+        "Migrations of {module} must be sorted by version"
+    );
+
+    create_schema_migrations_table(db)?;
+    let current_version = schema_version(db, module)?;
+    let Some(latest) = migrations.last() else {
+        return Ok(());
+    };
+    if latest.version <= current_version {
+        trace!("{} schema is at version {}", module, current_version);
+        return Ok(());
+    }
+
+    if current_version > 0 {
+        backup_database(db)?;
+    }
+
+    db.immediate_transaction(|conn| {
+        // Another process may have migrated the tables while this one waited for the lock
+        let current_version: u32 =
+            conn.query_row(SCHEMA_VERSION_QUERY, [module], |row| row.get(0))?;
+        for migration in migrations.iter().filter(|m| m.version > current_version) {
+            debug!(
+                "Migrating {} schema to version {}: {}",
+                module, migration.version, migration.description
+            );
+            conn.execute_batch(migration.sql)?;
+            conn.execute(
+                "INSERT INTO schema_migrations (module, version, description) VALUES (?1, ?2, ?3)",
+                params![module, migration.version, migration.description],
+            )?;
+        }
+        Ok(())
+    })
+    .map_err(|e| anyhow::anyhow!("Unable to migrate the {} schema: {:?}", module, e))
+}
+
+fn schema_version(db: &NxDbConnection, module: &str) -> anyhow::Result<u32> {
+    Ok(db
+        .query_row(SCHEMA_VERSION_QUERY, [module], |row| row.get(0))?
+        .unwrap_or(0))
+}
+
+/// Copies the database next to itself so the data can be recovered if a migration goes wrong.
+/// Only the backup of the most recent migration is kept.
+fn backup_database(db: &NxDbConnection) -> anyhow::Result<()> {
+    let Some(db_path) = db.path() else {
+        return Ok(());
+    };
+    let backup_path = format!("{db_path}.backup");
+    trace!("Backing up the database to {}", backup_path);
+    match remove_file(&backup_path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    db.execute("VACUUM INTO ?1", [&backup_path])
+        .map_err(|e| anyhow::anyhow!("Unable to back up the database: {:?}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+    use std::path::Path;
+
+    const MIGRATIONS: [Migration; 2] = [
+        Migration {
+            version: 1,
+            description: "Create items",
+            sql: "CREATE TABLE items (name TEXT PRIMARY KEY NOT NULL);",
+        },
+        Migration {
+            version: 2,
+            description: "Add count to items",
+            sql: "ALTER TABLE items ADD COLUMN count INTEGER NOT NULL DEFAULT 0;",
+        },
+    ];
+
+    fn open(db_path: &Path) -> NxDbConnection {
+        NxDbConnection::new(Connection::open(db_path).unwrap())
+    }
+
+    fn item_count(db: &NxDbConnection) -> Option<u32> {
+        db.query_row("SELECT count FROM items WHERE name = 'a'", [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn should_apply_pending_migrations_in_order() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join("test.db");
+        let mut db = open(&db_path);
+
+        migrate(&mut db, "items", &MIGRATIONS[..1])?;
+        db.execute("INSERT INTO items (name) VALUES ('a')", [])?;
+        assert_eq!(schema_version(&db, "items")?, 1);
+        assert!(!Path::new(&format!("{}.backup", db_path.display())).exists());
+
+        migrate(&mut db, "items", &MIGRATIONS)?;
+        assert_eq!(schema_version(&db, "items")?, 2);
+        assert_eq!(item_count(&db), Some(0));
+        assert_eq!(schema_version(&db, "other")?, 0);
+
+        // The backup holds the data from before the migration
+        let backup = open(Path::new(&format!("{}.backup", db_path.display())));
+        assert_eq!(schema_version(&backup, "items")?, 1);
+        assert!(
+            backup
+                .query_row("SELECT count FROM items", [], |row| row.get::<_, u32>(0))
+                .is_err()
+        );
+
+        // Applied migrations are not applied again
+        migrate(&mut db, "items", &MIGRATIONS)?;
+        assert_eq!(schema_version(&db, "items")?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn should_not_apply_any_migration_when_one_fails() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut db = open(&temp_dir.path().join("test.db"));
+
+        let failing = [
+            Migration {
+                version: 1,
+                description: "Create items",
+                sql: MIGRATIONS[0].sql,
+            },
+            Migration {
+                version: 2,
+                description: "Broken",
+                sql: "ALTER TABLE missing ADD COLUMN count INTEGER;",
+            },
+        ];
+        assert!(migrate(&mut db, "items", &failing).is_err());
+        assert_eq!(schema_version(&db, "items")?, 0);
+        assert!(!has_table(&db, "items"));
+
+        migrate(&mut db, "items", &MIGRATIONS)?;
+        assert_eq!(schema_version(&db, "items")?, 2);
+        Ok(())
+    }
+
+    fn has_table(db: &NxDbConnection, name: &str) -> bool {
+        db.query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [name],
+            |row| row.get::<_, u32>(0),
+        )
+        .unwrap()
+        .is_some()
+    }
+}
@@ -1,9 +1,10 @@
 pub mod connection;
 mod initialize;
+pub mod migrations;
 
 use crate::native::logger::enable_logger;
 use crate::native::machine_id::get_machine_id;
-use crate::native::{db::connection::NxDbConnection, hasher::hash};
+use crate::native::{db::connection::NxDbConnection, hasher::HashAlgorithm};
 use napi::bindgen_prelude::External;
 use std::fs::create_dir_all;
 use std::path::PathBuf;
@@ -22,7 +23,7 @@ pub fn connect_to_nx_db(
 
     if db_file_name.is_empty() {
         trace!("Invalid db file name, using fallback name");
-        db_file_name = hash(b"machine");
+        db_file_name = HashAlgorithm::Xxh3.hash(b"machine");
     }
 
     let db_path = cache_dir_buf.join(format!("{}.db", db_file_name));
@@ -1,10 +1,120 @@
 use std::path::Path;
+use std::sync::OnceLock;
 
-use tracing::trace;
+use tracing::{trace, warn};
 use xxhash_rust::xxh3;
 
+/// The algorithm used for file and task hashes, configured with `NX_HASH_ALGORITHM`.
+///
+/// `Xxh3` produces 64 bit fingerprints which are fine for a local cache.
+/// Caches shared by many machines should use `Xxh128` or the cryptographic `Blake3`.
+#[napi(string_enum)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[default]
+    Xxh3,
+    Xxh128,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// The algorithm configured for this process
+    pub fn current() -> Self {
+        static CURRENT: OnceLock<HashAlgorithm> = OnceLock::new();
+        *CURRENT.get_or_init(|| {
+            let value = std::env::var("NX_HASH_ALGORITHM").unwrap_or_default();
+            Self::parse(&value).unwrap_or_else(|| {
+                warn!(
+                    "Unsupported hash algorithm {}, expected xxh3, xxh128 or blake3",
+                    value
+                );
+                Self::default()
+            })
+        })
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "xxh3" => Some(Self::Xxh3),
+            "xxh128" | "xxh3-128" => Some(Self::Xxh128),
+            "blake3" => Some(Self::Blake3),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Xxh3 => "Xxh3",
+            Self::Xxh128 => "Xxh128",
+            Self::Blake3 => "Blake3",
+        }
+    }
+
+    /// The inverse of [`HashAlgorithm::name`], hashes recorded before algorithms
+    /// were configurable are `Xxh3`
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "Xxh128" => Self::Xxh128,
+            "Blake3" => Self::Blake3,
+            _ => Self::Xxh3,
+        }
+    }
+
+    pub fn hash(&self, content: &[u8]) -> String {
+        let mut hasher = Hasher::with_algorithm(*self);
+        hasher.update(content);
+        hasher.digest()
+    }
+}
+
+/// Incrementally hashes content with a [`HashAlgorithm`].
+/// `Xxh3` digests are decimal, the others are hex encoded.
+pub enum Hasher {
+    Xxh3(xxh3::Xxh3),
+    Xxh128(xxh3::Xxh3),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    /// A hasher using the algorithm configured for this process
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self::with_algorithm(HashAlgorithm::current())
+    }
+
+    pub fn with_algorithm(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Xxh3 => Self::Xxh3(xxh3::Xxh3::new()),
+            HashAlgorithm::Xxh128 => Self::Xxh128(xxh3::Xxh3::new()),
+            HashAlgorithm::Blake3 => Self::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    pub fn update(&mut self, content: &[u8]) {
+        match self {
+            Self::Xxh3(hasher) | Self::Xxh128(hasher) => hasher.update(content),
+            Self::Blake3(hasher) => {
+                hasher.update(content);
+            }
+        }
+    }
+
+    pub fn digest(&self) -> String {
+        match self {
+            Self::Xxh3(hasher) => hasher.digest().to_string(),
+            Self::Xxh128(hasher) => format!("{:032x}", hasher.digest128()),
+            Self::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
 pub fn hash(content: &[u8]) -> String {
-    xxh3::xxh3_64(content).to_string()
+    HashAlgorithm::current().hash(content)
+}
+
+#[napi]
+pub fn get_hash_algorithm() -> HashAlgorithm {
+    HashAlgorithm::current()
 }
 
 #[napi]
@@ -45,7 +155,7 @@ pub fn hash_file_path<P: AsRef<Path>>(path: P) -> Option<String> {
 
 #[cfg(test)]
 mod tests {
-    use crate::native::hasher::{hash_array, hash_file};
+    use crate::native::hasher::{HashAlgorithm, Hasher, hash_array, hash_file};
     use assert_fs::TempDir;
     use assert_fs::prelude::*;
 
@@ -88,4 +198,30 @@ mod tests {
         let content = hash_array(vec![Some("foo".to_string()), None, Some("bar".to_string())]);
         assert_eq!(content, "10292076446133652019");
     }
+
+    #[test]
+    fn it_hashes_with_each_algorithm() {
+        assert_eq!(HashAlgorithm::Xxh3.hash(b"content"), "6193209363630369380");
+        assert_eq!(HashAlgorithm::Xxh128.hash(b"content").len(), 32);
+        assert_eq!(
+            HashAlgorithm::Blake3.hash(b"content"),
+            blake3::hash(b"content").to_hex().to_string()
+        );
+
+        let mut hasher = Hasher::with_algorithm(HashAlgorithm::Blake3);
+        hasher.update(b"con");
+        hasher.update(b"tent");
+        assert_eq!(hasher.digest(), HashAlgorithm::Blake3.hash(b"content"));
+    }
+
+    #[test]
+    fn it_parses_hash_algorithms() {
+        assert_eq!(HashAlgorithm::parse(""), Some(HashAlgorithm::Xxh3));
+        assert_eq!(
+            HashAlgorithm::parse("XXH3-128"),
+            Some(HashAlgorithm::Xxh128)
+        );
+        assert_eq!(HashAlgorithm::parse("blake3"), Some(HashAlgorithm::Blake3));
+        assert_eq!(HashAlgorithm::parse("md5"), None);
+    }
 }
@@ -26,6 +26,8 @@ export declare class AppLifeCycle {
 }
 
 export declare class ChildProcess {
+  /** The id of the process running the command */
+  get pid(): number
   getParserAndWriter(): ExternalObject<[ParserArc, WriterArc]>
   kill(signal?: NodeJS.Signals): void
   onExit(callback: (message: string) => void): void
@@ -44,7 +46,8 @@ export declare class FileLock {
 
 export declare class HashPlanInspector {
   constructor(allWorkspaceFiles: ExternalObject<Array<FileData>>, projectGraph: ExternalObject<ProjectGraph>, projectFileMap: ExternalObject<Record<string, Array<FileData>>>)
-  inspect(hashPlans: ExternalObject<Record<string, Array<HashInstruction>>>): Record<string, string[]>
+  /** `jsEnv` lists the variables matched by env patterns such as `env:NX_PUBLIC_*` */
+  inspect(hashPlans: ExternalObject<Record<string, Array<HashInstruction>>>, jsEnv?: Record<string, string> | undefined | null): Record<string, string[]>
 }
 
 export declare class HashPlanner {
@@ -57,6 +60,8 @@ export declare class HttpRemoteCache {
   constructor()
   retrieve(hash: string, cacheDirectory: string): Promise<CachedResult | null>
   store(hash: string, cacheDirectory: string, terminalOutput: string, code: number): Promise<boolean>
+  exists(hash: string): Promise<boolean>
+  query(hashes: Array<string>): Promise<Array<string>>
 }
 
 export declare class ImportResult {
@@ -68,13 +73,36 @@ export declare class ImportResult {
 
 export declare class NxCache {
   cacheDirectory: string
-  constructor(workspaceRoot: string, cachePath: string, dbConnection: ExternalObject<NxDbConnection>, linkTaskDetails?: boolean | undefined | null, maxCacheSize?: number | undefined | null)
+  constructor(workspaceRoot: string, cachePath: string, dbConnection: ExternalObject<NxDbConnection>, linkTaskDetails?: boolean | undefined | null, maxCacheSize?: number | undefined | null, evictionOptions?: CacheEvictionOptions | undefined | null)
   get(hash: string): CachedResult | null
   put(hash: string, terminalOutput: string, outputs: Array<string>, code: number): void
   applyRemoteCacheResults(hash: string, result: CachedResult, outputs?: Array<string> | undefined | null): void
   getTaskOutputsPath(hash: string): string
   getCacheSize(): number
+  /** Lists the entries in the cache matching `filter` */
+  listEntries(filter?: CacheEntryFilter | undefined | null): Array<CacheEntryInfo>
+  /** Returns the metadata and the stored files of the entry for `hash` */
+  getEntryDetails(hash: string): CacheEntryDetails | null
+  /** Removes the entries matching `filter` and returns them */
+  removeEntries(filter: CacheEntryFilter): Array<CacheEntryInfo>
+  /**
+   * Writes the entries matching `filter` into a bundle at `bundle_path`,
+   * which can be imported into the cache of another workspace
+   */
+  exportBundle(bundlePath: string, filter?: CacheEntryFilter | undefined | null): Array<CacheEntryInfo>
+  /**
+   * Adds the entries of the bundle at `bundle_path` to the cache.
+   * Entries which are already cached or fail validation are skipped.
+   */
+  importBundle(bundlePath: string): BundleImportResult
+  /**
+   * Evicts entries which have not been used for a week, followed by the configured
+   * policies and the size limit. In a dry run nothing is removed.
+   * Returns the entries which were, or would be, evicted.
+   */
+  evict(dryRun?: boolean | undefined | null): Array<EvictionCandidate>
   copyFilesFromCache(cachedResult: CachedResult, outputs: Array<string>): number
+  restoreFilesFromCache(cachedResult: CachedResult, outputs: Array<string>): RestoreResult
   removeOldCacheRecords(): void
   checkCacheFsInSync(): boolean
 }
@@ -88,14 +116,59 @@ export declare class NxConsolePreferences {
 export declare class NxTaskHistory {
   constructor(db: ExternalObject<NxDbConnection>)
   recordTaskRuns(taskRuns: Array<TaskRun>): void
+  /**
+   * Rolls the runs beyond `retention` up into daily aggregates per target and status,
+   * then removes them along with the instruction details of hashes which have no runs left
+   */
+  compactTaskHistory(retention: TaskHistoryRetention): TaskHistoryCompaction
+  /**
+   * Writes every run, or with `daily` the aggregates of compacted runs, to `path`.
+   * Returns how many rows were written.
+   */
+  exportTaskHistory(path: string, format: TaskHistoryExportFormat, daily?: boolean | undefined | null): number
   getFlakyTasks(hashes: Array<string>): Array<string>
+  /** Scores how flaky every target has been based on its executed runs */
+  getTaskFlakiness(targets: Array<TaskTarget>, options?: FlakinessOptions | undefined | null): Array<TaskFlakiness>
+  /**
+   * Predicts the critical path of `task_graph` and how long it takes to run with
+   * `parallelism` workers, which defaults to 3. Tasks whose hash already succeeded are
+   * expected to be restored from the cache and are estimated with the durations of cache hits.
+   */
+  getTaskGraphEstimate(taskGraph: TaskGraph, parallelism?: number | undefined | null): TaskGraphEstimate
   getEstimatedTaskTimings(targets: Array<TaskTarget>): Record<string, number>
 }
 
+/**
+ * Samples the memory and CPU time of a process and its descendants until it is stopped.
+ * Processes which run for less than one sample interval may not be seen at all.
+ */
+export declare class ProcessTreeMonitor {
+  constructor(pid: number)
+  /**
+   * Stops sampling and returns the usage of the process tree, unless the process was
+   * never seen
+   */
+  stop(): ProcessTreeUsage | null
+}
+
 export declare class RunningTasksService {
   constructor(db: ExternalObject<NxDbConnection>)
   getRunningTasks(ids: Array<string>): Array<string>
-  addRunningTask(taskId: string): void
+  /**
+   * The lease of `task_id` if a live process is running it. Leases of processes which
+   * are gone, or whose pid now belongs to another process, are removed.
+   */
+  getRunningTask(taskId: string): RunningTaskLease | null
+  /**
+   * Takes the lease of `task_id` for the current process. Returns false without taking it
+   * when another live process is already running the task.
+   */
+  addRunningTask(taskId: string): boolean
+  /**
+   * Renews the leases held by the current process, so processes on other machines do not
+   * consider them stale
+   */
+  heartbeat(): void
   removeRunningTask(taskId: string): void
 }
 
@@ -109,14 +182,58 @@ export declare class RustPseudoTerminal {
   fork(id: string, forkScript: string, pseudoIpcPath: string, commandDir: string | undefined | null, jsEnv: Record<string, string> | undefined | null, execArgv: Array<string> | undefined | null, quiet: boolean, commandLabel?: string | undefined | null): ChildProcess
 }
 
+/**
+ * A self-hosted remote cache whose backend is picked from the environment:
+ * - `NX_SELF_HOSTED_REMOTE_CACHE_DIRECTORY` for a shared directory
+ * - `NX_SELF_HOSTED_REMOTE_CACHE_S3_BUCKET` for an S3-compatible bucket
+ * - `NX_SELF_HOSTED_REMOTE_CACHE_SERVER` for the HTTP protocol
+ */
+export declare class SelfHostedRemoteCache {
+  constructor()
+  retrieve(hash: string, cacheDirectory: string): Promise<CachedResult | null>
+  store(hash: string, cacheDirectory: string, terminalOutput: string, code: number): Promise<boolean>
+  exists(hash: string): Promise<boolean>
+  query(hashes: Array<string>): Promise<Array<string>>
+  /**
+   * Downloads the artifacts of every task in `task_graph` which already has a hash
+   * and exists in the remote cache, so that later calls to `retrieve` resolve
+   * without waiting on the network. Returns the hashes which were prefetched.
+   */
+  prefetch(taskGraph: TaskGraph, cacheDirectory: string, concurrency?: number | undefined | null): Promise<Array<string>>
+}
+
 export declare class TaskDetails {
   constructor(db: ExternalObject<NxDbConnection>)
   recordTaskDetails(tasks: Array<HashedTask>): void
+  /** Records the files hashed by file set instructions, keyed by the hash of the instruction */
+  recordFileSets(fileSets: ExternalObject<Record<string, Record<string, string>>>): void
+  /** The hashes of file set instructions persisted by previous runs */
+  loadInstructionHashes(): ExternalObject<Record<string, string>>
+  /**
+   * Persists the hashes of file set instructions for later runs. Hashes which have not been
+   * used for a week are removed, since the files they were computed from are long gone.
+   */
+  recordInstructionHashes(hashes: ExternalObject<Record<string, string>>): void
+  /**
+   * Explains why `hash` differs from `base_hash` by listing the instructions, and the files
+   * within them, which changed. Without `base_hash`, `hash` is compared to the last
+   * successful run of `task`. Returns nothing if the details of either hash are unknown.
+   */
+  diffTaskHashes(task: TaskTarget, hash: string, baseHash?: string | undefined | null): HashDiff | null
 }
 
 export declare class TaskHasher {
   constructor(workspaceRoot: string, projectGraph: ExternalObject<ProjectGraph>, projectFileMap: ExternalObject<ProjectFiles>, allWorkspaceFiles: ExternalObject<Array<FileData>>, tsConfig: Buffer, tsConfigPaths: Record<string, Array<string>>, options?: HasherOptions | undefined | null)
+  /** Reuses the hashes of file set instructions persisted by previous runs */
+  loadInstructionHashes(hashes: ExternalObject<Record<string, string>>): void
   hashPlans(hashPlans: ExternalObject<Record<string, Array<HashInstruction>>>, jsEnv: Record<string, string>): NapiDashMap
+  /** Hands over the file sets hashed since the last call, so they can be persisted */
+  takeFileSets(): ExternalObject<Record<string, Record<string, string>>>
+  /**
+   * Hands over the hashes of file set instructions used since the last call, so they can
+   * be persisted for the next run
+   */
+  takeInstructionHashes(): ExternalObject<Record<string, string>>
 }
 
 export declare class Watcher {
@@ -153,6 +270,13 @@ export declare class WorkspaceContext {
   getFilesInDirectory(directory: string): Array<string>
 }
 
+export interface BundleImportResult {
+  /** Hashes of the entries added to the cache */
+  imported: Array<string>
+  /** Entries which were already cached or failed validation */
+  skipped: Array<SkippedBundleEntry>
+}
+
 export interface CachedResult {
   code: number
   terminalOutput?: string
@@ -160,6 +284,78 @@ export interface CachedResult {
   size?: number
 }
 
+export interface CacheEntryDetails {
+  entry: CacheEntryInfo
+  outputsPath: string
+  terminalOutputPath: string
+  files: Array<CacheFileInfo>
+}
+
+/**
+ * Narrows down the entries returned by `NxCache.listEntries`.
+ * Timestamps are milliseconds since the unix epoch.
+ */
+export interface CacheEntryFilter {
+  hashes?: Array<string>
+  project?: string
+  target?: string
+  /** Only entries created at or after this time */
+  since?: number
+  /** Only entries created before this time */
+  until?: number
+  /** Minimum size in bytes */
+  minSize?: number
+  /** Maximum size in bytes */
+  maxSize?: number
+  code?: number
+  sortBy?: CacheEntrySort
+  limit?: number
+}
+
+export interface CacheEntryInfo {
+  hash: string
+  code: number
+  size: number
+  /** Milliseconds since the unix epoch */
+  createdAt: number
+  /** Milliseconds since the unix epoch */
+  accessedAt: number
+  project?: string
+  target?: string
+  configuration?: string
+  /** The algorithm which produced the hash */
+  hashAlgorithm: HashAlgorithm
+}
+
+export declare const enum CacheEntrySort {
+  /** Most recently used first */
+  AccessedAt = 'AccessedAt',
+  /** Most recently created first */
+  CreatedAt = 'CreatedAt',
+  /** Largest first */
+  Size = 'Size'
+}
+
+export interface CacheEvictionOptions {
+  /** Maximum size in bytes of the cached outputs of each project */
+  projectQuotas?: Record<string, number>
+  /** Number of most recently used entries kept for each target of a project */
+  keepPerTarget?: number
+  /**
+   * When the cache is over its size limit, evict entries which were quick
+   * to compute before entries which took long, based on the task history
+   */
+  costAware?: boolean
+}
+
+export interface CacheFileInfo {
+  /** Path relative to the outputs of the entry */
+  path: string
+  size: number
+  /** The content-addressed blob holding the file, if it is deduplicated */
+  blob?: string
+}
+
 export declare export declare function canInstallNxConsole(): boolean
 
 export declare export declare function canInstallNxConsoleForEditor(editor: SupportedEditor): boolean
@@ -179,6 +375,16 @@ export interface EnvironmentInput {
   env: string
 }
 
+/** A cache entry which is evicted, or would be evicted in a dry run */
+export interface EvictionCandidate {
+  hash: string
+  size: number
+  project?: string
+  target?: string
+  /** The name of the policy which selected the entry */
+  reason: string
+}
+
 export declare const enum EventType {
   delete = 'delete',
   update = 'update',
@@ -194,7 +400,10 @@ export interface ExternalDependenciesInput {
 export interface ExternalNode {
   packageName?: string
   version: string
+  /** The integrity checksum recorded in the lockfile, or a hash of the resolved package */
   hash?: string
+  /** Patch files applied to the package, relative to the workspace root */
+  patches?: Array<string>
 }
 
 export interface FileData {
@@ -202,6 +411,11 @@ export interface FileData {
   hash: string
 }
 
+export interface FileHashChange {
+  file: string
+  change: HashChange
+}
+
 export interface FileMap {
   projectFileMap: ProjectFiles
   nonProjectFiles: Array<FileData>
@@ -213,6 +427,15 @@ export interface FileSetInput {
 
 export declare export declare function findImports(projectFileMap: Record<string, Array<string>>): Array<ImportResult>
 
+export interface FlakinessOptions {
+  /** Only runs which ended after this time, in milliseconds since the epoch, are considered */
+  since?: number
+  /** How many of the most recent statuses are listed, defaults to 10 */
+  recentRuns?: number
+  /** The most retries which are ever recommended, defaults to 3 */
+  maxRetries?: number
+}
+
 export declare export declare function getBinaryTarget(): string
 
 export declare export declare function getDefaultMaxCacheSize(cachePath: string): number
@@ -223,24 +446,65 @@ export declare export declare function getDefaultMaxCacheSize(cachePath: string)
  */
 export declare export declare function getFilesForOutputs(directory: string, entries: Array<string>): Array<string>
 
+export declare export declare function getHashAlgorithm(): HashAlgorithm
+
 export declare export declare function getTransformableOutputs(outputs: Array<string>): Array<string>
 
+/**
+ * The algorithm used for file and task hashes, configured with `NX_HASH_ALGORITHM`.
+ *
+ * `Xxh3` produces 64 bit fingerprints which are fine for a local cache.
+ * Caches shared by many machines should use `Xxh128` or the cryptographic `Blake3`.
+ */
+export declare const enum HashAlgorithm {
+  Xxh3 = 'Xxh3',
+  Xxh128 = 'Xxh128',
+  Blake3 = 'Blake3'
+}
+
 export declare export declare function hashArray(input: Array<string | undefined | null>): string
 
+export declare const enum HashChange {
+  Added = 'Added',
+  Removed = 'Removed',
+  Changed = 'Changed'
+}
+
 export interface HashDetails {
   value: string
   details: Record<string, string>
 }
 
+export interface HashDiff {
+  baseHash: string
+  hash: string
+  changes: Array<InstructionHashChange>
+}
+
 export interface HashedTask {
   hash: string
   project: string
   target: string
   configuration?: string
+  /** The hash of every instruction which makes up the task hash */
+  details?: Record<string, string>
 }
 
 export interface HasherOptions {
   selectivelyHashTsConfig: boolean
+  /** Milliseconds a runtime input may run before hashing fails, defaults to 60 seconds */
+  runtimeTimeout?: number
+  /** Only hash what runtime inputs print to stdout */
+  runtimeHashStdoutOnly?: boolean
+  /** Seconds the result of a runtime input is reused for */
+  runtimeCacheTtl?: number
+  /** Where results of runtime inputs are persisted, requires `runtimeCacheTtl` */
+  runtimeCacheDirectory?: string
+  /**
+   * The cache whose output manifests are used to hash the outputs of dependent tasks,
+   * so those outputs do not have to be restored first
+   */
+  cacheDirectory?: string
 }
 
 export declare export declare function hashFile(file: string): string | null
@@ -255,6 +519,19 @@ export declare export declare function installNxConsole(): boolean
 
 export declare export declare function installNxConsoleForEditor(editor: SupportedEditor): boolean
 
+export interface InstructionHashChange {
+  /** The hash instruction, e.g. `app:ProjectConfiguration` or `env:CI` */
+  instruction: string
+  change: HashChange
+  baseValue?: string
+  value?: string
+  /**
+   * The files which changed, if the instruction hashes a set of files
+   * which was recorded for both hashes
+   */
+  files?: Array<FileHashChange>
+}
+
 export const IS_WASM: boolean
 
 /** Detects if the current process is being run by an AI agent */
@@ -267,6 +544,8 @@ export declare export declare function logDebug(message: string): void
 /** Stripped version of the NxJson interface for use in rust */
 export interface NxJson {
   namedInputs?: Record<string, Array<JsInputs>>
+  /** Hash only the external dependencies a project uses instead of all of them */
+  scopeExternalDependencies?: boolean
 }
 
 export interface NxWorkspaceFiles {
@@ -283,11 +562,20 @@ export interface NxWorkspaceFilesExternals {
 
 export declare export declare function parseTaskStatus(stringStatus: string): TaskStatus
 
+export interface ProcessTreeUsage {
+  /** The peak resident set size of the process tree, in bytes */
+  peakRss: number
+  /** The CPU time of the process tree, in milliseconds */
+  cpuTime: number
+}
+
 export interface Project {
   root: string
   namedInputs?: Record<string, Array<JsInputs>>
   tags?: Array<string>
   targets: Record<string, Target>
+  /** The name in the package.json of the project, if it has one */
+  packageName?: string
 }
 
 export interface ProjectGraph {
@@ -298,6 +586,25 @@ export interface ProjectGraph {
 
 export declare export declare function remove(src: string): void
 
+export interface RestoreResult {
+  size: number
+  /** The slowest strategy any file of this restore had to fall back to, if any file was restored */
+  strategy?: RestoreStrategy
+  reflinkedFiles: number
+  hardlinkedFiles: number
+  copiedFiles: number
+}
+
+/** How a file was restored from the cache */
+export declare const enum RestoreStrategy {
+  /** A copy-on-write clone sharing the cached file's data blocks */
+  Reflink = 'Reflink',
+  /** A hardlink to the cached file, only used for read-only outputs */
+  Hardlink = 'Hardlink',
+  /** A full byte copy */
+  Copy = 'Copy'
+}
+
 export declare export declare function restoreTerminal(): void
 
 export declare const enum RunMode {
@@ -305,10 +612,29 @@ export declare const enum RunMode {
   RunMany = 1
 }
 
+/** The claim of a process on running a task, which other processes wait on */
+export interface RunningTaskLease {
+  taskId: string
+  /** The pid of the process which runs the task */
+  pid: number
+  machineId?: string
+  /** When the task was started, in milliseconds since the epoch */
+  startedAt?: number
+  /** When the owner last renewed the lease, in milliseconds since the epoch */
+  heartbeatAt?: number
+  /** Whether the lease is held by the current process */
+  owned: boolean
+}
+
 export interface RuntimeInput {
   runtime: string
 }
 
+export interface SkippedBundleEntry {
+  hash: string
+  reason: string
+}
+
 export declare const enum SupportedEditor {
   VSCode = 0,
   VSCodeInsiders = 1,
@@ -335,6 +661,40 @@ export interface Task {
   startTime?: number
   endTime?: number
   continuous?: boolean
+  hash?: string
+}
+
+export interface TaskDurationEstimate {
+  taskId: string
+  /** Median duration, in milliseconds, of the runs which were executed */
+  p50?: number
+  /** 90th percentile duration, in milliseconds, of the runs which were executed */
+  p90?: number
+  /** Median duration, in milliseconds, of the runs which were restored from the cache */
+  cacheHitP50?: number
+  /** Whether the task is expected to be restored from the cache, because its hash succeeded before */
+  expectCacheHit: boolean
+  /**
+   * The duration used for the estimate. Tasks without any history are assumed to take
+   * as long as the median task of the graph.
+   */
+  duration: number
+}
+
+export interface TaskFlakiness {
+  target: TaskTarget
+  /** The number of runs which were executed rather than read from the cache */
+  runs: number
+  /** Failed runs of hashes which also succeeded, so the same inputs both passed and failed */
+  flakyFailures: number
+  /** `flakyFailures` divided by `runs` */
+  flakeRate: number
+  /** How many of the most recent runs failed in a row */
+  consecutiveFailures: number
+  /** The statuses of the most recent runs, newest first */
+  recentStatuses: Array<string>
+  /** The retries needed for a flaky failure to pass with 99% certainty */
+  recommendedRetries: number
 }
 
 export interface TaskGraph {
@@ -344,6 +704,38 @@ export interface TaskGraph {
   continuousDependencies: Record<string, Array<string>>
 }
 
+export interface TaskGraphEstimate {
+  tasks: Record<string, TaskDurationEstimate>
+  /** The chain of dependent tasks which takes the longest, from first to last */
+  criticalPath: Array<string>
+  /** How long the critical path takes, in milliseconds. No parallelism can be faster. */
+  criticalPathDuration: number
+  /** How long the whole graph takes, in milliseconds, at the given parallelism */
+  eta: number
+}
+
+export interface TaskHistoryCompaction {
+  /** Runs which were rolled up into daily aggregates and removed */
+  compactedRuns: number
+  /** Hashes whose instruction details were removed, since none of their runs remain */
+  removedHashDetails: number
+}
+
+export declare const enum TaskHistoryExportFormat {
+  JsonLines = 'JsonLines',
+  Csv = 'Csv'
+}
+
+export interface TaskHistoryRetention {
+  /** Runs which ended more than this many days ago are rolled up into daily aggregates */
+  maxAgeDays?: number
+  /**
+   * Only this many of the most recent runs of each target are kept, older runs are
+   * rolled up into daily aggregates
+   */
+  maxRunsPerTarget?: number
+}
+
 export interface TaskResult {
   task: Task
   status: string
@@ -357,6 +749,17 @@ export interface TaskRun {
   code: number
   start: number
   end: number
+  /** Where the outputs came from: `local`, `remote` or `miss` when the task was executed */
+  cacheSource?: string
+  /** The peak resident set size of the process tree, in bytes */
+  peakRss?: number
+  /** The CPU time of the process tree, in milliseconds */
+  cpuTime?: number
+  /** Defaults to the id of the current machine */
+  machineId?: string
+  gitCommit?: string
+  /** Identifies the command which ran the task, shared by every task it ran */
+  commandId?: string
 }
 
 export declare const enum TaskStatus {
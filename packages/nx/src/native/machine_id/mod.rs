@@ -4,11 +4,11 @@ pub fn get_machine_id() -> String {
 
     #[cfg(target_arch = "wasm32")]
     {
-        use crate::native::hasher::hash;
+        use crate::native::hasher::HashAlgorithm;
         use crate::native::tasks::hashers::create_command_builder;
         use std::fs::read_to_string;
 
-        hash(
+        HashAlgorithm::Xxh3.hash(
             read_to_string("/var/lib/dbus/machine-id")
                 .or_else(|_| read_to_string("/etc/machine-id"))
                 .or_else(|_| {
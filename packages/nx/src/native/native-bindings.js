@@ -371,12 +371,15 @@ module.exports.ImportResult = nativeBinding.ImportResult
 module.exports.NxCache = nativeBinding.NxCache
 module.exports.NxConsolePreferences = nativeBinding.NxConsolePreferences
 module.exports.NxTaskHistory = nativeBinding.NxTaskHistory
+module.exports.ProcessTreeMonitor = nativeBinding.ProcessTreeMonitor
 module.exports.RunningTasksService = nativeBinding.RunningTasksService
 module.exports.RustPseudoTerminal = nativeBinding.RustPseudoTerminal
+module.exports.SelfHostedRemoteCache = nativeBinding.SelfHostedRemoteCache
 module.exports.TaskDetails = nativeBinding.TaskDetails
 module.exports.TaskHasher = nativeBinding.TaskHasher
 module.exports.Watcher = nativeBinding.Watcher
 module.exports.WorkspaceContext = nativeBinding.WorkspaceContext
+module.exports.CacheEntrySort = nativeBinding.CacheEntrySort
 module.exports.canInstallNxConsole = nativeBinding.canInstallNxConsole
 module.exports.canInstallNxConsoleForEditor = nativeBinding.canInstallNxConsoleForEditor
 module.exports.closeDbConnection = nativeBinding.closeDbConnection
@@ -388,8 +391,11 @@ module.exports.findImports = nativeBinding.findImports
 module.exports.getBinaryTarget = nativeBinding.getBinaryTarget
 module.exports.getDefaultMaxCacheSize = nativeBinding.getDefaultMaxCacheSize
 module.exports.getFilesForOutputs = nativeBinding.getFilesForOutputs
+module.exports.getHashAlgorithm = nativeBinding.getHashAlgorithm
 module.exports.getTransformableOutputs = nativeBinding.getTransformableOutputs
+module.exports.HashAlgorithm = nativeBinding.HashAlgorithm
 module.exports.hashArray = nativeBinding.hashArray
+module.exports.HashChange = nativeBinding.HashChange
 module.exports.hashFile = nativeBinding.hashFile
 module.exports.installNxConsole = nativeBinding.installNxConsole
 module.exports.installNxConsoleForEditor = nativeBinding.installNxConsoleForEditor
@@ -399,9 +405,11 @@ module.exports.isEditorInstalled = nativeBinding.isEditorInstalled
 module.exports.logDebug = nativeBinding.logDebug
 module.exports.parseTaskStatus = nativeBinding.parseTaskStatus
 module.exports.remove = nativeBinding.remove
+module.exports.RestoreStrategy = nativeBinding.RestoreStrategy
 module.exports.restoreTerminal = nativeBinding.restoreTerminal
 module.exports.RunMode = nativeBinding.RunMode
 module.exports.SupportedEditor = nativeBinding.SupportedEditor
+module.exports.TaskHistoryExportFormat = nativeBinding.TaskHistoryExportFormat
 module.exports.TaskStatus = nativeBinding.TaskStatus
 module.exports.testOnlyTransferFileMap = nativeBinding.testOnlyTransferFileMap
 module.exports.transferProjectGraph = nativeBinding.transferProjectGraph
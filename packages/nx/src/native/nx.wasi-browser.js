@@ -126,6 +126,7 @@ export const IS_WASM = __napiModule.exports.IS_WASM
 export const isAiAgent = __napiModule.exports.isAiAgent
 export const logDebug = __napiModule.exports.logDebug
 export const remove = __napiModule.exports.remove
+export const RestoreStrategy = __napiModule.exports.RestoreStrategy
 export const testOnlyTransferFileMap = __napiModule.exports.testOnlyTransferFileMap
 export const transferProjectGraph = __napiModule.exports.transferProjectGraph
 export const validateOutputs = __napiModule.exports.validateOutputs
@@ -157,6 +157,7 @@ module.exports.IS_WASM = __napiModule.exports.IS_WASM
 module.exports.isAiAgent = __napiModule.exports.isAiAgent
 module.exports.logDebug = __napiModule.exports.logDebug
 module.exports.remove = __napiModule.exports.remove
+module.exports.RestoreStrategy = __napiModule.exports.RestoreStrategy
 module.exports.testOnlyTransferFileMap = __napiModule.exports.testOnlyTransferFileMap
 module.exports.transferProjectGraph = __napiModule.exports.transferProjectGraph
 module.exports.validateOutputs = __napiModule.exports.validateOutputs
@@ -5,7 +5,10 @@ use std::collections::HashMap;
 pub struct ExternalNode {
     pub package_name: Option<String>,
     pub version: String,
+    /// The integrity checksum recorded in the lockfile, or a hash of the resolved package
     pub hash: Option<String>,
+    /// Patch files applied to the package, relative to the workspace root
+    pub patches: Option<Vec<String>>,
 }
 
 #[napi(object)]
@@ -26,6 +29,8 @@ pub struct Project {
     pub named_inputs: Option<HashMap<String, Vec<JsInputs>>>,
     pub tags: Option<Vec<String>>,
     pub targets: HashMap<String, Target>,
+    /// The name in the package.json of the project, if it has one
+    pub package_name: Option<String>,
 }
 
 #[napi(object)]
@@ -49,6 +49,7 @@ mod test {
                     targets: Default::default(),
                     root: "apps/demo-app".into(),
                     named_inputs: None,
+                    package_name: None,
                 },
             ),
             (
@@ -58,6 +59,7 @@ mod test {
                     targets: Default::default(),
                     root: "libs/ui".into(),
                     named_inputs: None,
+                    package_name: None,
                 },
             ),
             (
@@ -67,6 +69,7 @@ mod test {
                     targets: Default::default(),
                     root: "libs/core".into(),
                     named_inputs: None,
+                    package_name: None,
                 },
             ),
             (
@@ -76,6 +79,7 @@ mod test {
                     targets: Default::default(),
                     root: ".".into(),
                     named_inputs: None,
+                    package_name: None,
                 },
             ),
         ]));
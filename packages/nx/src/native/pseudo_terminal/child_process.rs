@@ -21,6 +21,7 @@ pub enum ChildProcessMessage {
 
 #[napi]
 pub struct ChildProcess {
+    pid: u32,
     parser: Arc<RwLock<Parser>>,
     process_killer: ProcessKiller,
     message_receiver: Receiver<String>,
@@ -31,6 +32,7 @@ pub struct ChildProcess {
 #[napi]
 impl ChildProcess {
     pub fn new(
+        pid: u32,
         parser: Arc<RwLock<Parser>>,
         writer_arc: Arc<Mutex<Box<dyn Write + Send>>>,
         process_killer: ProcessKiller,
@@ -38,6 +40,7 @@ impl ChildProcess {
         exit_receiver: Receiver<String>,
     ) -> Self {
         Self {
+            pid,
             parser,
             writer_arc,
             process_killer,
@@ -47,6 +50,12 @@ impl ChildProcess {
         }
     }
 
+    /// The id of the process running the command
+    #[napi(getter)]
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
     #[napi]
     pub fn get_parser_and_writer(&mut self) -> External<(ParserArc, WriterArc)> {
         External::new((self.parser.clone(), self.writer_arc.clone()))
@@ -229,11 +229,10 @@ impl PseudoTerminal {
             trace!("Enabling raw mode");
             enable_raw_mode().expect("Failed to enter raw terminal mode");
         }
-        let process_killer = ProcessKiller::new(
-            child
-                .process_id()
-                .expect("unable to determine child process id") as i32,
-        );
+        let pid = child
+            .process_id()
+            .expect("unable to determine child process id");
+        let process_killer = ProcessKiller::new(pid as i32);
 
         trace!("Getting running clone");
         let running_clone = self.running.clone();
@@ -275,6 +274,7 @@ impl PseudoTerminal {
 
         trace!("Returning ChildProcess");
         Ok(ChildProcess::new(
+            pid,
             self.parser.clone(),
             self.writer.clone(),
             process_killer,
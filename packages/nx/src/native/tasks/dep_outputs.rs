@@ -57,6 +57,7 @@ fn process_tasks_outputs(
                 Some(HashInstruction::TaskOutput(
                     dependent_tasks_output_files.to_string(),
                     task.outputs.clone(),
+                    task.hash.clone(),
                 ))
             } else {
                 None
@@ -133,6 +134,7 @@ mod tests {
             start_time: None,
             end_time: None,
             continuous: None,
+            hash: None,
         }
     }
 
@@ -227,7 +229,8 @@ mod tests {
 
     #[test]
     fn test_process_tasks_outputs() {
-        let task1 = create_test_task("task1", vec!["dist/out1".to_string()]);
+        let mut task1 = create_test_task("task1", vec!["dist/out1".to_string()]);
+        task1.hash = Some("hash1".to_string());
         let task2 = create_test_task("task2", vec![]);
         let task3 = create_test_task("task3", vec!["dist/out3".to_string()]);
 
@@ -236,8 +239,12 @@ mod tests {
 
         // Should only include tasks with outputs
         let expected = vec![
-            HashInstruction::TaskOutput("**/*.js".to_string(), vec!["dist/out1".to_string()]),
-            HashInstruction::TaskOutput("**/*.js".to_string(), vec!["dist/out3".to_string()]),
+            HashInstruction::TaskOutput(
+                "**/*.js".to_string(),
+                vec!["dist/out1".to_string()],
+                Some("hash1".to_string()),
+            ),
+            HashInstruction::TaskOutput("**/*.js".to_string(), vec!["dist/out3".to_string()], None),
         ];
         assert_eq!(result, expected);
     }
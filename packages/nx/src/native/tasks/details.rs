@@ -1,6 +1,11 @@
 use crate::native::db::connection::NxDbConnection;
+use crate::native::db::migrations::{Migration, migrate};
+use crate::native::tasks::hash_diff::{HashDiff, diff_details};
+use crate::native::tasks::task_hasher::{FileSets, InstructionHashes};
+use crate::native::tasks::types::TaskTarget;
 use napi::bindgen_prelude::*;
 use rusqlite::params;
+use std::collections::{BTreeMap, HashMap};
 use tracing::trace;
 
 #[napi(object)]
@@ -10,8 +15,13 @@ pub struct HashedTask {
     pub project: String,
     pub target: String,
     pub configuration: Option<String>,
+    /// The hash of every instruction which makes up the task hash
+    pub details: Option<HashMap<String, String>>,
 }
 
+/// How long the details of a hash are kept after they were last recorded
+const DETAILS_MAX_AGE_SECONDS: i64 = 7 * 24 * 60 * 60;
+
 #[napi]
 pub struct TaskDetails {
     db: External<NxDbConnection>,
@@ -21,24 +31,92 @@ pub struct TaskDetails {
 impl TaskDetails {
     #[napi(constructor)]
     pub fn new(db: External<NxDbConnection>) -> anyhow::Result<Self> {
-        let r = Self { db };
+        let mut r = Self { db };
 
         r.setup()?;
+        // Pruning is best effort and done once per process, another process may hold the lock
+        if let Err(e) = r.prune() {
+            trace!("Unable to prune task details: {:?}", e);
+        }
 
         Ok(r)
     }
 
-    fn setup(&self) -> anyhow::Result<()> {
-        self.db.execute(
-            "CREATE TABLE IF NOT EXISTS task_details (
-                hash    TEXT PRIMARY KEY NOT NULL,
-                project  TEXT NOT NULL,
-                target  TEXT NOT NULL,
-                configuration  TEXT
-            );",
-            params![],
-        )?;
+    fn setup(&mut self) -> anyhow::Result<()> {
+        migrate(
+            &mut self.db,
+            "task_details",
+            &[
+                Migration {
+                    version: 1,
+                    description: "Create task details tables",
+                    sql: "
+                    CREATE TABLE IF NOT EXISTS task_details (
+                        hash    TEXT PRIMARY KEY NOT NULL,
+                        project  TEXT NOT NULL,
+                        target  TEXT NOT NULL,
+                        configuration  TEXT
+                    );
+                    CREATE TABLE IF NOT EXISTS task_hash_details (
+                        hash    TEXT NOT NULL,
+                        instruction  TEXT NOT NULL,
+                        value  TEXT NOT NULL,
+                        PRIMARY KEY (hash, instruction)
+                    );
+                    CREATE TABLE IF NOT EXISTS hash_file_sets (
+                        value    TEXT PRIMARY KEY NOT NULL,
+                        files  TEXT NOT NULL
+                    );
+                    CREATE TABLE IF NOT EXISTS instruction_hashes (
+                        fingerprint    TEXT PRIMARY KEY NOT NULL,
+                        hash  TEXT NOT NULL,
+                        used_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+                    );
+                    ",
+                },
+                Migration {
+                    version: 2,
+                    description: "Record when hash details were last recorded",
+                    sql: "
+                    ALTER TABLE task_hash_details ADD COLUMN recorded_at INTEGER;
+                    CREATE INDEX IF NOT EXISTS task_hash_details_recorded_at_idx ON task_hash_details (recorded_at);
+                    CREATE INDEX IF NOT EXISTS task_hash_details_value_idx ON task_hash_details (value);
+                    ",
+                },
+            ],
+        )
+    }
 
+    /// Removes the details of hashes which have not been recorded for a week, along with the
+    /// file sets no remaining hash or instruction hash refers to. Hashes which are still in use
+    /// are recorded by every run, so only the details of stale hashes are lost. Instruction
+    /// hashes which have not been used for a week are removed too, since the files they were
+    /// computed from are long gone.
+    fn prune(&mut self) -> anyhow::Result<()> {
+        let (removed_details, removed_file_sets, removed_instruction_hashes) =
+            self.db.transaction(|conn| {
+                let removed_details = conn.execute(
+                    "DELETE FROM task_hash_details
+                        WHERE recorded_at IS NULL OR recorded_at < CAST(strftime('%s', 'now') AS INTEGER) - ?1",
+                    params![DETAILS_MAX_AGE_SECONDS],
+                )?;
+                let removed_instruction_hashes = conn.execute(
+                    "DELETE FROM instruction_hashes WHERE used_at < datetime('now', '-7 days')",
+                    [],
+                )?;
+                // Reused instruction hashes do not record their file sets again
+                let removed_file_sets = conn.execute(
+                    "DELETE FROM hash_file_sets
+                        WHERE NOT EXISTS (SELECT 1 FROM task_hash_details WHERE task_hash_details.value = hash_file_sets.value)
+                        AND NOT EXISTS (SELECT 1 FROM instruction_hashes WHERE instruction_hashes.hash = hash_file_sets.value)",
+                    [],
+                )?;
+                Ok((removed_details, removed_file_sets, removed_instruction_hashes))
+            })?;
+        trace!(
+            "Pruned {} hash details, {} file sets and {} instruction hashes",
+            removed_details, removed_file_sets, removed_instruction_hashes
+        );
         Ok(())
     }
 
@@ -52,9 +130,226 @@ impl TaskDetails {
                     params![task.hash, task.project, task.target, task.configuration],
                 )?;
             }
+            let mut stmt = conn.prepare("INSERT OR REPLACE INTO task_hash_details (hash, instruction, value, recorded_at) VALUES (?1, ?2, ?3, CAST(strftime('%s', 'now') AS INTEGER))")?;
+            for task in tasks.iter() {
+                for (instruction, value) in task.details.iter().flatten() {
+                    stmt.execute(params![task.hash, instruction, value])?;
+                }
+            }
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    /// Records the files hashed by file set instructions, keyed by the hash of the instruction
+    #[napi(ts_args_type = "fileSets: ExternalObject<Record<string, Record<string, string>>>")]
+    pub fn record_file_sets(&mut self, file_sets: External<FileSets>) -> anyhow::Result<()> {
+        if file_sets.is_empty() {
+            return Ok(());
+        }
+        trace!("Recording {} file sets", file_sets.len());
+        let file_sets = file_sets
+            .iter()
+            .map(|(value, files)| Ok((value, serde_json::to_string(files)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.db.transaction(|conn| {
+            let mut stmt = conn
+                .prepare("INSERT OR IGNORE INTO hash_file_sets (value, files) VALUES (?1, ?2)")?;
+            for (value, files) in file_sets.iter() {
+                stmt.execute(params![value, files])?;
+            }
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    /// The hashes of file set instructions persisted by previous runs
+    #[napi(ts_return_type = "ExternalObject<Record<string, string>>")]
+    pub fn load_instruction_hashes(&self) -> anyhow::Result<External<InstructionHashes>> {
+        let hashes: InstructionHashes = self
+            .db
+            .prepare("SELECT fingerprint, hash FROM instruction_hashes")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(anyhow::Result::ok)
+            .collect();
+        trace!("Loaded {} instruction hashes", hashes.len());
+        Ok(External::new(hashes))
+    }
+
+    /// Persists the hashes of file set instructions for later runs
+    #[napi(ts_args_type = "hashes: ExternalObject<Record<string, string>>")]
+    pub fn record_instruction_hashes(
+        &mut self,
+        hashes: External<InstructionHashes>,
+    ) -> anyhow::Result<()> {
+        if hashes.is_empty() {
+            return Ok(());
+        }
+        trace!("Recording {} instruction hashes", hashes.len());
+        self.db.transaction(|conn| {
+            let mut stmt = conn.prepare(
+                "INSERT OR REPLACE INTO instruction_hashes (fingerprint, hash, used_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)",
+            )?;
+            for (fingerprint, hash) in hashes.iter() {
+                stmt.execute(params![fingerprint, hash])?;
+            }
             Ok(())
         })?;
 
         Ok(())
     }
+
+    /// Explains why `hash` differs from `base_hash` by listing the instructions, and the files
+    /// within them, which changed. Without `base_hash`, `hash` is compared to the last
+    /// successful run of `task`. Returns nothing if the details of either hash are unknown.
+    #[napi]
+    pub fn diff_task_hashes(
+        &self,
+        task: TaskTarget,
+        hash: String,
+        base_hash: Option<String>,
+    ) -> anyhow::Result<Option<HashDiff>> {
+        let base_hash = match base_hash {
+            Some(base_hash) => base_hash,
+            None => match self.last_successful_hash(&task, &hash)? {
+                Some(base_hash) => base_hash,
+                None => return Ok(None),
+            },
+        };
+
+        let base = self.get_hash_details(&base_hash)?;
+        let current = self.get_hash_details(&hash)?;
+        if base.is_empty() || current.is_empty() {
+            return Ok(None);
+        }
+
+        let changes = diff_details(&base, &current, |value| self.get_file_set(value));
+        Ok(Some(HashDiff {
+            base_hash,
+            hash,
+            changes,
+        }))
+    }
+
+    fn get_hash_details(&self, hash: &str) -> anyhow::Result<HashMap<String, String>> {
+        Ok(self
+            .db
+            .prepare("SELECT instruction, value FROM task_hash_details WHERE hash = ?1")?
+            .query_map(params![hash], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(anyhow::Result::ok)
+            .collect())
+    }
+
+    fn get_file_set(&self, value: &str) -> Option<BTreeMap<String, String>> {
+        let files = self
+            .db
+            .query_row(
+                "SELECT files FROM hash_file_sets WHERE value = ?1",
+                params![value],
+                |row| row.get::<_, String>(0),
+            )
+            .ok()
+            .flatten()?;
+        serde_json::from_str(&files).ok()
+    }
+
+    /// The hash of the most recent successful run of `task` other than `hash`
+    fn last_successful_hash(
+        &self,
+        task: &TaskTarget,
+        hash: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let has_history = self
+            .db
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'task_history')",
+                [],
+                |row| row.get::<_, bool>(0),
+            )?
+            .unwrap_or(false);
+        if !has_history {
+            return Ok(None);
+        }
+
+        self.db.query_row(
+            "SELECT task_history.hash FROM task_history
+                JOIN task_details ON task_details.hash = task_history.hash
+                WHERE task_details.project = ?1 AND task_details.target = ?2
+                    AND task_details.configuration IS ?3
+                    AND task_history.hash != ?4
+                    AND task_history.status IN ('success', 'local-cache', 'local-cache-kept-existing', 'remote-cache')
+                ORDER BY task_history.end DESC
+                LIMIT 1",
+            params![task.project, task.target, task.configuration, hash],
+            |row| row.get(0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    fn count(details: &TaskDetails, table: &str) -> u32 {
+        details
+            .db
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn should_prune_stale_hash_details_file_sets_and_instruction_hashes() -> anyhow::Result<()> {
+        let mut details = TaskDetails::new(External::new(NxDbConnection::new(
+            Connection::open_in_memory()?,
+        )))?;
+        details.record_task_details(vec![HashedTask {
+            hash: "new".to_string(),
+            project: "proj".to_string(),
+            target: "build".to_string(),
+            configuration: None,
+            details: Some(HashMap::from([(
+                "proj:{projectRoot}/**/*".to_string(),
+                "new-files".to_string(),
+            )])),
+        }])?;
+        details.db.execute_batch(
+            "INSERT INTO task_hash_details (hash, instruction, value, recorded_at)
+                VALUES ('old', 'proj:{projectRoot}/**/*', 'old-files', 0);
+            INSERT INTO hash_file_sets (value, files) VALUES
+                ('new-files', '{}'),
+                ('reused-files', '{}'),
+                ('old-files', '{}');
+            INSERT INTO instruction_hashes (fingerprint, hash, used_at) VALUES
+                ('new-fingerprint', 'new-files', CURRENT_TIMESTAMP),
+                ('reused-fingerprint', 'reused-files', CURRENT_TIMESTAMP),
+                ('old-fingerprint', 'old-files', datetime('now', '-8 days'));",
+        )?;
+
+        details.prune()?;
+
+        assert_eq!(count(&details, "task_hash_details"), 1);
+        assert_eq!(details.get_file_set("new-files"), Some(BTreeMap::default()));
+        assert_eq!(
+            details.get_file_set("reused-files"),
+            Some(BTreeMap::default())
+        );
+        assert_eq!(details.get_file_set("old-files"), None);
+        let mut instruction_hashes = details
+            .load_instruction_hashes()?
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        instruction_hashes.sort();
+        assert_eq!(
+            instruction_hashes,
+            vec!["new-fingerprint", "reused-fingerprint"]
+        );
+        Ok(())
+    }
 }
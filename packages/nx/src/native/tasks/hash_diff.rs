@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashChange {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileHashChange {
+    pub file: String,
+    pub change: HashChange,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionHashChange {
+    /// The hash instruction, e.g. `app:ProjectConfiguration` or `env:CI`
+    pub instruction: String,
+    pub change: HashChange,
+    pub base_value: Option<String>,
+    pub value: Option<String>,
+    /// The files which changed, if the instruction hashes a set of files
+    /// which was recorded for both hashes
+    pub files: Option<Vec<FileHashChange>>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashDiff {
+    pub base_hash: String,
+    pub hash: String,
+    pub changes: Vec<InstructionHashChange>,
+}
+
+/// Compares the hashes of every instruction of two task hashes.
+/// `file_set` returns the files recorded for the value of a file set instruction.
+pub fn diff_details(
+    base: &HashMap<String, String>,
+    current: &HashMap<String, String>,
+    file_set: impl Fn(&str) -> Option<BTreeMap<String, String>>,
+) -> Vec<InstructionHashChange> {
+    let instructions = base.keys().chain(current.keys()).collect::<BTreeSet<_>>();
+    instructions
+        .into_iter()
+        .filter_map(|instruction| {
+            let base_value = base.get(instruction);
+            let value = current.get(instruction);
+            let change = match (base_value, value) {
+                (None, Some(_)) => HashChange::Added,
+                (Some(_), None) => HashChange::Removed,
+                (Some(base_value), Some(value)) if base_value != value => HashChange::Changed,
+                _ => return None,
+            };
+            let files = match (base_value, value) {
+                (Some(base_value), Some(value)) => file_set(base_value)
+                    .zip(file_set(value))
+                    .map(|(base_files, files)| diff_file_sets(&base_files, &files)),
+                _ => None,
+            };
+            Some(InstructionHashChange {
+                instruction: instruction.clone(),
+                change,
+                base_value: base_value.cloned(),
+                value: value.cloned(),
+                files,
+            })
+        })
+        .collect()
+}
+
+/// Compares two sets of files, keyed by path with their content hash
+pub fn diff_file_sets(
+    base: &BTreeMap<String, String>,
+    current: &BTreeMap<String, String>,
+) -> Vec<FileHashChange> {
+    let files = base.keys().chain(current.keys()).collect::<BTreeSet<_>>();
+    files
+        .into_iter()
+        .filter_map(|file| {
+            let change = match (base.get(file), current.get(file)) {
+                (None, Some(_)) => HashChange::Added,
+                (Some(_), None) => HashChange::Removed,
+                (Some(base_hash), Some(hash)) if base_hash != hash => HashChange::Changed,
+                _ => return None,
+            };
+            Some(FileHashChange {
+                file: file.clone(),
+                change,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn should_report_changed_instructions() {
+        let base = map(&[("env:CI", "1"), ("app:ProjectConfiguration", "2")]);
+        let current = map(&[("env:CI", "3"), ("AllExternalDependencies", "4")]);
+
+        let changes = diff_details(&base, &current, |_| None);
+
+        assert_eq!(
+            changes
+                .iter()
+                .map(|c| (c.instruction.as_str(), c.change))
+                .collect::<Vec<_>>(),
+            vec![
+                ("AllExternalDependencies", HashChange::Added),
+                ("app:ProjectConfiguration", HashChange::Removed),
+                ("env:CI", HashChange::Changed),
+            ]
+        );
+        assert_eq!(changes[2].base_value.as_deref(), Some("1"));
+        assert_eq!(changes[2].files, None);
+    }
+
+    #[test]
+    fn should_drill_down_into_file_sets() {
+        let base = map(&[("app:{projectRoot}/**/*", "a")]);
+        let current = map(&[("app:{projectRoot}/**/*", "b")]);
+        let file_sets = HashMap::from([
+            (
+                "a",
+                BTreeMap::from([
+                    ("app/main.ts".to_string(), "1".to_string()),
+                    ("app/old.ts".to_string(), "2".to_string()),
+                    ("app/same.ts".to_string(), "3".to_string()),
+                ]),
+            ),
+            (
+                "b",
+                BTreeMap::from([
+                    ("app/main.ts".to_string(), "4".to_string()),
+                    ("app/new.ts".to_string(), "5".to_string()),
+                    ("app/same.ts".to_string(), "3".to_string()),
+                ]),
+            ),
+        ]);
+
+        let changes = diff_details(&base, &current, |value| file_sets.get(value).cloned());
+
+        assert_eq!(
+            changes[0].files,
+            Some(vec![
+                FileHashChange {
+                    file: "app/main.ts".to_string(),
+                    change: HashChange::Changed
+                },
+                FileHashChange {
+                    file: "app/new.ts".to_string(),
+                    change: HashChange::Added
+                },
+                FileHashChange {
+                    file: "app/old.ts".to_string(),
+                    change: HashChange::Removed
+                },
+            ])
+        );
+    }
+}
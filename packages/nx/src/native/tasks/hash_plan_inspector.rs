@@ -1,5 +1,7 @@
 use crate::native::project_graph::types::ProjectGraph;
-use crate::native::tasks::hashers::{collect_project_files, get_workspace_files};
+use crate::native::tasks::hashers::{
+    collect_project_files, get_workspace_files, matching_env_names,
+};
 use crate::native::tasks::types::HashInstruction;
 use crate::native::types::FileData;
 use anyhow::anyhow;
@@ -29,10 +31,12 @@ impl HashPlanInspector {
         }
     }
 
+    /// `js_env` lists the variables matched by env patterns such as `env:NX_PUBLIC_*`
     #[napi(ts_return_type = "Record<string, string[]>")]
     pub fn inspect(
         &self,
         hash_plans: External<HashMap<String, Vec<HashInstruction>>>,
+        js_env: Option<HashMap<String, String>>,
     ) -> anyhow::Result<HashMap<String, Vec<String>>> {
         let a: Vec<(&String, Vec<String>)> = hash_plans
             .iter()
@@ -68,6 +72,16 @@ impl HashPlanInspector {
                     .collect();
                     Ok::<_, anyhow::Error>((task_id, files))
                 }
+                HashInstruction::EnvironmentPattern(pattern) => {
+                    let names = match &js_env {
+                        Some(env) => matching_env_names(pattern, env)?
+                            .into_iter()
+                            .map(|name| format!("env:{}", name))
+                            .collect(),
+                        None => vec![instruction.to_string()],
+                    };
+                    Ok::<_, anyhow::Error>((task_id, names))
+                }
                 _ => Ok::<_, anyhow::Error>((task_id, vec![instruction.to_string()])),
             })
             .collect::<anyhow::Result<_>>()?;
@@ -1,6 +1,7 @@
 use crate::native::logger::enable_logger;
 use crate::native::tasks::{
     dep_outputs::get_dep_output,
+    hashers::is_env_pattern,
     types::{HashInstruction, TaskGraph},
 };
 use crate::native::types::{Input, NxJson};
@@ -14,7 +15,8 @@ use std::collections::HashMap;
 use tracing::trace;
 
 use crate::native::tasks::inputs::{
-    expand_single_project_inputs, get_inputs, get_inputs_for_dependency, get_named_inputs,
+    expand_named_input, expand_single_project_inputs, get_inputs, get_inputs_for_dependency,
+    get_named_inputs,
 };
 use crate::native::tasks::utils;
 use crate::native::utils::find_matching_projects;
@@ -150,6 +152,28 @@ impl HashPlanner {
             return Ok(None);
         };
 
+        let executor_package = target
+            .executor
+            .as_deref()
+            .and_then(|executor| executor.split(':').next());
+        let external_executor = executor_package
+            .and_then(|package| find_external_dependency_node_name(package, &self.project_graph));
+        // executors from local plugins are resolved to the project providing them
+        let local_plugin = executor_package
+            .filter(|_| external_executor.is_none())
+            .and_then(|package| find_local_plugin_project(package, &self.project_graph))
+            .filter(|plugin_project| plugin_project.as_str() != project_name);
+        let local_plugin_inputs = match local_plugin {
+            Some(plugin_project) => {
+                trace!(
+                    "Add Instructions for local plugin {plugin_project}: {}",
+                    target.executor.as_ref().unwrap()
+                );
+                self.local_plugin_inputs(plugin_project, external_deps_map)?
+            }
+            None => vec![],
+        };
+
         // we can only vouch for @nx packages's executor dependencies
         // if it's "run commands" or third-party we skip traversing since we have no info what this command depends on
         if target
@@ -157,19 +181,9 @@ impl HashPlanner {
             .as_ref()
             .is_some_and(|e| e.starts_with("@nrwl/") || e.starts_with("@nx/"))
         {
-            let executor_package = target
-                .executor
-                .as_ref()
-                .unwrap()
-                .split(':')
-                .next()
-                .expect("Executors should always have a ':'");
-            let Some(existing_package) =
-                find_external_dependency_node_name(executor_package, &self.project_graph)
-            else {
-                // this usually happens because the executor was a local plugin.
-                // todo)) @Cammisuli: we need to gather the project's inputs and its dep inputs similar to how we do it in `self_and_deps_inputs`
-                return Ok(None);
+            let Some(existing_package) = external_executor else {
+                // this usually happens because the executor was a local plugin
+                return Ok(local_plugin.map(|_| local_plugin_inputs));
             };
             let mut external_deps: Vec<&'a String> = vec![];
             trace!(
@@ -231,19 +245,123 @@ impl HashPlanner {
                     _ => continue,
                 }
             }
-            if !external_deps.is_empty() {
-                Ok(Some(
+            let instructions = if !external_deps.is_empty() {
+                Some(
                     external_deps
                         .iter()
                         .map(|s| HashInstruction::External(s.to_string()))
                         .collect(),
-                ))
+                )
             } else if !has_external_deps {
-                Ok(Some(vec![HashInstruction::AllExternalDependencies]))
+                Some(vec![self.unknown_external_dependencies(
+                    project_name,
+                    external_deps_map,
+                )])
             } else {
-                Ok(None)
+                None
+            };
+            if local_plugin.is_none() {
+                return Ok(instructions);
+            }
+            Ok(Some(
+                instructions
+                    .into_iter()
+                    .flatten()
+                    .chain(local_plugin_inputs)
+                    .collect(),
+            ))
+        }
+    }
+
+    /// Instructions for the project of a local plugin which provides an executor.
+    /// Covers the files of the plugin and of the workspace projects it depends on,
+    /// as well as the external packages they use.
+    fn local_plugin_inputs<'a>(
+        &'a self,
+        plugin_project: &'a str,
+        external_deps_map: &hashbrown::HashMap<&String, Vec<&'a String>>,
+    ) -> anyhow::Result<Vec<HashInstruction>> {
+        let mut instructions = vec![];
+        let mut visited = hashbrown::HashSet::from([plugin_project]);
+        let mut queue = vec![plugin_project];
+
+        while let Some(project_name) = queue.pop() {
+            let named_inputs =
+                get_named_inputs(&self.nx_json, &self.project_graph.nodes[project_name]);
+            // tests of the plugin do not affect what the executor does
+            let input = if named_inputs.contains_key("production") {
+                "production"
+            } else {
+                "default"
+            };
+            let inputs = expand_named_input(input, &named_inputs)?;
+            instructions.extend(self.gather_self_inputs(project_name, &inputs));
+
+            for dep in self
+                .project_graph
+                .dependencies
+                .get(project_name)
+                .into_iter()
+                .flatten()
+            {
+                if !visited.insert(dep.as_str()) {
+                    continue;
+                }
+                if self.project_graph.nodes.contains_key(dep) {
+                    queue.push(dep);
+                } else if let Some(external_deps) = external_deps_map.get(dep) {
+                    instructions.push(HashInstruction::External(dep.to_string()));
+                    instructions.extend(
+                        external_deps
+                            .iter()
+                            .map(|s| HashInstruction::External(s.to_string())),
+                    );
+                }
             }
         }
+
+        Ok(instructions)
+    }
+
+    /// The instruction for targets which could use any external dependency.
+    /// Covers every external dependency, unless `scopeExternalDependencies` limits it
+    /// to the ones the project and its workspace dependencies use.
+    fn unknown_external_dependencies(
+        &self,
+        project_name: &str,
+        external_deps_map: &hashbrown::HashMap<&String, Vec<&String>>,
+    ) -> HashInstruction {
+        if !self.nx_json.scope_external_dependencies.unwrap_or(false) {
+            return HashInstruction::AllExternalDependencies;
+        }
+
+        let mut externals = std::collections::BTreeSet::new();
+        let mut visited = hashbrown::HashSet::from([project_name]);
+        let mut queue = vec![project_name];
+        while let Some(project_name) = queue.pop() {
+            for dep in self
+                .project_graph
+                .dependencies
+                .get(project_name)
+                .into_iter()
+                .flatten()
+            {
+                if !visited.insert(dep.as_str()) {
+                    continue;
+                }
+                if self.project_graph.nodes.contains_key(dep) {
+                    queue.push(dep);
+                } else if let Some(external_deps) = external_deps_map.get(dep) {
+                    externals.insert(dep.to_string());
+                    externals.extend(external_deps.iter().map(|s| s.to_string()));
+                }
+            }
+        }
+
+        HashInstruction::ProjectExternalDependencies(
+            project_name.to_string(),
+            externals.into_iter().collect(),
+        )
     }
 
     fn self_and_deps_inputs(
@@ -390,6 +508,9 @@ impl HashPlanner {
         };
         let runtime_and_env_inputs = self_inputs.iter().filter_map(|i| match i {
             Input::Runtime(runtime) => Some(HashInstruction::Runtime(runtime.to_string())),
+            Input::Environment(env) if is_env_pattern(env) => {
+                Some(HashInstruction::EnvironmentPattern(env.to_string()))
+            }
             Input::Environment(env) => Some(HashInstruction::Environment(env.to_string())),
             _ => None,
         });
@@ -459,6 +580,20 @@ impl HashPlanner {
     }
 }
 
+/// Finds the workspace project which provides `package_name`, either
+/// through the name in its package.json or its project name
+fn find_local_plugin_project<'a>(
+    package_name: &str,
+    project_graph: &'a ProjectGraph,
+) -> Option<&'a String> {
+    project_graph
+        .nodes
+        .iter()
+        .find(|(_, project)| project.package_name.as_deref() == Some(package_name))
+        .or_else(|| project_graph.nodes.get_key_value(package_name))
+        .map(|(name, _)| name)
+}
+
 fn find_external_dependency_node_name<'a>(
     package_name: &str,
     project_graph: &'a ProjectGraph,
@@ -1,5 +1,6 @@
 mod hash_env;
 mod hash_external;
+mod hash_json;
 mod hash_project_config;
 mod hash_project_files;
 mod hash_runtime;
@@ -9,6 +10,7 @@ mod hash_workspace_files;
 
 pub use hash_env::*;
 pub use hash_external::*;
+pub use hash_json::*;
 pub use hash_project_config::*;
 pub use hash_project_files::*;
 pub use hash_runtime::*;
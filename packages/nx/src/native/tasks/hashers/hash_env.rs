@@ -1,4 +1,5 @@
 use crate::native::hasher::hash;
+use globset::Glob;
 use std::collections::HashMap;
 
 pub fn hash_env(env_name: &str, env: &HashMap<String, String>) -> String {
@@ -6,6 +7,35 @@ pub fn hash_env(env_name: &str, env: &HashMap<String, String>) -> String {
     hash(env_value.as_bytes())
 }
 
+/// Whether an env input such as `NX_PUBLIC_*` matches several variables
+pub fn is_env_pattern(env_name: &str) -> bool {
+    env_name.contains(['*', '?', '['])
+}
+
+/// The sorted names of the variables matching an env pattern
+pub fn matching_env_names<'a>(
+    pattern: &str,
+    env: &'a HashMap<String, String>,
+) -> anyhow::Result<Vec<&'a String>> {
+    let matcher = Glob::new(pattern)?.compile_matcher();
+    let mut names = env
+        .keys()
+        .filter(|name| matcher.is_match(name))
+        .collect::<Vec<_>>();
+    names.sort();
+    Ok(names)
+}
+
+/// Hashes the names and values of every variable matching an env pattern
+pub fn hash_env_pattern(pattern: &str, env: &HashMap<String, String>) -> anyhow::Result<String> {
+    let content = matching_env_names(pattern, env)?
+        .into_iter()
+        .map(|name| format!("{}={}", name, env[name]))
+        .collect::<Vec<_>>()
+        .join("\0");
+    Ok(hash(content.as_bytes()))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -27,4 +57,31 @@ mod test {
 
         assert_eq!(hash, "3244421341483603138");
     }
+
+    #[test]
+    fn should_hash_every_variable_matching_a_pattern() {
+        let env = HashMap::from([
+            ("NX_PUBLIC_B".to_string(), "b".to_string()),
+            ("NX_PUBLIC_A".to_string(), "a".to_string()),
+            ("NX_PRIVATE".to_string(), "secret".to_string()),
+        ]);
+
+        assert!(is_env_pattern("NX_PUBLIC_*"));
+        assert!(!is_env_pattern("NX_PUBLIC_A"));
+        assert_eq!(
+            matching_env_names("NX_PUBLIC_*", &env).unwrap(),
+            vec!["NX_PUBLIC_A", "NX_PUBLIC_B"]
+        );
+        assert_eq!(
+            hash_env_pattern("NX_PUBLIC_*", &env).unwrap(),
+            hash(b"NX_PUBLIC_A=a\0NX_PUBLIC_B=b")
+        );
+
+        let mut changed = env.clone();
+        changed.insert("NX_PRIVATE".to_string(), "other".to_string());
+        assert_eq!(
+            hash_env_pattern("NX_PUBLIC_*", &changed).unwrap(),
+            hash_env_pattern("NX_PUBLIC_*", &env).unwrap()
+        );
+    }
 }
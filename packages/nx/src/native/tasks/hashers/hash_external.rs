@@ -1,12 +1,14 @@
-use crate::native::hasher::{hash, hash_array};
+use crate::native::hasher::{hash, hash_array, hash_file_path};
 use crate::native::project_graph::types::ExternalNode;
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 
 use anyhow::*;
 use dashmap::DashMap;
 
 pub fn hash_external(
+    workspace_root: &str,
     external_name: &str,
     externals: &HashMap<String, ExternalNode>,
     cache: Arc<DashMap<String, String>>,
@@ -19,25 +21,42 @@ pub fn hash_external(
         return Ok(cached_hash.clone());
     }
 
-    let hash = if let Some(external_hash) = &external.hash {
+    let base_hash = if let Some(external_hash) = &external.hash {
         hash(external_hash.as_bytes())
     } else {
         hash(external.version.as_bytes())
     };
 
+    // The hash already carries the lockfile integrity, but not the patches applied on top
+    let patches = external.patches.as_deref().unwrap_or_default();
+    let hash = if patches.is_empty() {
+        base_hash
+    } else {
+        let mut hashes = vec![Some(base_hash)];
+        for patch in patches {
+            let patch_hash = hash_file_path(Path::new(workspace_root).join(patch))
+                .ok_or_else(|| anyhow!("Could not read patch {} of {}", patch, external_name))?;
+            hashes.push(Some(format!("{}:{}", patch, patch_hash)));
+        }
+        hash_array(hashes)
+    };
+
     cache.insert(external_name.to_string(), hash.clone());
 
     Ok(hash)
 }
 
 pub fn hash_all_externals<S: AsRef<str>>(
+    workspace_root: &str,
     sorted_externals: &[S],
     externals: &HashMap<String, ExternalNode>,
     cache: Arc<DashMap<String, String>>,
 ) -> Result<String> {
     let hashes = sorted_externals
         .iter()
-        .map(|name| hash_external(name.as_ref(), externals, Arc::clone(&cache)).map(Some))
+        .map(|name| {
+            hash_external(workspace_root, name.as_ref(), externals, Arc::clone(&cache)).map(Some)
+        })
         .collect::<Result<Vec<Option<String>>>>()?;
     Ok(hash_array(hashes))
 }
@@ -46,6 +65,7 @@ pub fn hash_all_externals<S: AsRef<str>>(
 mod test {
     use super::*;
     use crate::native::project_graph::types::ExternalNode;
+    use assert_fs::prelude::*;
     use dashmap::DashMap;
     use std::sync::Arc;
 
@@ -57,6 +77,7 @@ mod test {
                     package_name: Some("my_external".into()),
                     version: "0.0.1".into(),
                     hash: None,
+                    patches: None,
                 },
             ),
             (
@@ -65,6 +86,7 @@ mod test {
                     package_name: Some("my_external_with_hash".into()),
                     version: "0.0.1".into(),
                     hash: Some("hashvalue".into()),
+                    patches: None,
                 },
             ),
         ])
@@ -74,11 +96,15 @@ mod test {
         let external_nodes = get_external_nodes_map();
         let cache: Arc<DashMap<String, String>> = Arc::new(DashMap::new());
         let no_external_node_hash =
-            hash_external("my_external", &external_nodes, Arc::clone(&cache));
+            hash_external("", "my_external", &external_nodes, Arc::clone(&cache));
         assert_eq!(no_external_node_hash.unwrap(), "3342527690135000204");
 
-        let external_node_hash =
-            hash_external("my_external_with_hash", &external_nodes, Arc::clone(&cache));
+        let external_node_hash = hash_external(
+            "",
+            "my_external_with_hash",
+            &external_nodes,
+            Arc::clone(&cache),
+        );
         assert_eq!(external_node_hash.unwrap(), "4204073044699973956");
     }
 
@@ -87,10 +113,50 @@ mod test {
         let external_nodes = get_external_nodes_map();
         let cache: Arc<DashMap<String, String>> = Arc::new(DashMap::new());
         let all_externals = hash_all_externals(
+            "",
             &["my_external", "my_external_with_hash"],
             &external_nodes,
             Arc::clone(&cache),
         );
         assert_eq!(all_externals.unwrap(), "9354284926255893100");
     }
+
+    #[test]
+    fn should_hash_patches() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let patch = temp.child("patches/my_external.patch");
+        patch.write_str("--- a/index.js").unwrap();
+        let workspace_root = temp.path().to_str().unwrap();
+
+        let external = |patches: Option<Vec<String>>| {
+            HashMap::from([(
+                "my_external".to_string(),
+                ExternalNode {
+                    package_name: Some("my_external".into()),
+                    version: "0.0.1".into(),
+                    hash: Some("sha512-abc".into()),
+                    patches,
+                },
+            )])
+        };
+        let hash_with = |externals: HashMap<String, ExternalNode>| {
+            hash_external(
+                workspace_root,
+                "my_external",
+                &externals,
+                Arc::new(DashMap::new()),
+            )
+            .unwrap()
+        };
+
+        let unpatched = hash_with(external(None));
+        let patches = Some(vec!["patches/my_external.patch".to_string()]);
+        let patched = hash_with(external(patches.clone()));
+
+        assert_eq!(hash_with(external(Some(vec![]))), unpatched);
+        assert_ne!(patched, unpatched);
+
+        patch.write_str("--- b/index.js").unwrap();
+        assert_ne!(hash_with(external(patches)), patched);
+    }
 }
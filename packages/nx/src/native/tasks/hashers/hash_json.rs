@@ -0,0 +1,214 @@
+use std::path::Path;
+
+use serde_json::Value;
+use tracing::{trace, warn};
+
+use crate::native::hasher::hash;
+
+/// Splits a file set such as `{workspaceRoot}/package.json#/dependencies` into its glob
+/// and JSON pointer. A trailing `#` selects the whole document.
+///
+/// File sets with a selector are hashed by their canonical JSON content instead of their raw
+/// bytes, so formatting, comments and key order do not affect the hash.
+pub fn split_json_selector(file_set: &str) -> (&str, Option<&str>) {
+    match file_set.rsplit_once('#') {
+        Some((glob, pointer)) if pointer.is_empty() || pointer.starts_with('/') => {
+            (glob, Some(pointer))
+        }
+        _ => (file_set, None),
+    }
+}
+
+/// Hashes the canonical form of the value at `pointer` in the JSON (or JSONC) file at `path`.
+/// Files which cannot be parsed fall back to hashing their raw content, and files which cannot
+/// be read as text fall back to `file_hash`, the hash of their raw content.
+pub fn hash_json_file(path: &Path, pointer: &str, file_hash: &str) -> String {
+    trace!("Hashing {:?}#{} as JSON", path, pointer);
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!(
+                "Unable to read {:?} as JSON, using its file hash: {}",
+                path, e
+            );
+            return file_hash.to_string();
+        }
+    };
+    hash_json_content(&content, pointer).unwrap_or_else(|| {
+        warn!(
+            "Unable to parse {:?} as JSON, hashing its raw content",
+            path
+        );
+        hash(content.as_bytes())
+    })
+}
+
+fn hash_json_content(content: &str, pointer: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(&strip_jsonc(content)).ok()?;
+    let mut canonical = String::new();
+    // A missing value hashes differently from any JSON value, including `null`
+    if let Some(selected) = value.pointer(pointer) {
+        write_canonical(selected, &mut canonical);
+    }
+    Some(hash(canonical.as_bytes()))
+}
+
+/// Writes `value` without whitespace and with the keys of every object sorted
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// Removes comments and trailing commas so JSONC files such as `tsconfig.json` can be parsed
+fn strip_jsonc(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => {
+                    if let Some(escaped) = chars.next() {
+                        out.push(escaped);
+                    }
+                }
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = None;
+                for c in chars.by_ref() {
+                    if previous == Some('*') && c == '/' {
+                        break;
+                    }
+                    previous = Some(c);
+                }
+                out.push(' ');
+            }
+            ']' | '}' => {
+                // Drop a comma which is only followed by whitespace
+                let trimmed_len = out.trim_end().len();
+                if out[..trimmed_len].ends_with(',') {
+                    out.remove(trimmed_len - 1);
+                }
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_split_json_selectors() {
+        assert_eq!(
+            split_json_selector("{workspaceRoot}/package.json#/dependencies"),
+            ("{workspaceRoot}/package.json", Some("/dependencies"))
+        );
+        assert_eq!(
+            split_json_selector("{projectRoot}/tsconfig.json#"),
+            ("{projectRoot}/tsconfig.json", Some(""))
+        );
+        assert_eq!(
+            split_json_selector("{projectRoot}/src/#generated/**/*"),
+            ("{projectRoot}/src/#generated/**/*", None)
+        );
+        assert_eq!(
+            split_json_selector("{projectRoot}/**/*"),
+            ("{projectRoot}/**/*", None)
+        );
+    }
+
+    #[test]
+    fn should_ignore_formatting_and_key_order() {
+        let a = r#"{"name": "app", "dependencies": {"b": "1.0.0", "a": "2.0.0"}}"#;
+        let b = r#"{
+            // the dependencies
+            "dependencies": {
+                "a": "2.0.0",
+                "b": "1.0.0", /* trailing comma */
+            },
+            "name": "app",
+        }"#;
+
+        assert_eq!(hash_json_content(a, ""), hash_json_content(b, ""));
+        assert!(hash_json_content(a, "").is_some());
+    }
+
+    #[test]
+    fn should_only_hash_the_selected_value() {
+        let a = r#"{"version": "1.0.0", "dependencies": {"a": "1.0.0"}}"#;
+        let b = r#"{"version": "2.0.0", "dependencies": {"a": "1.0.0"}}"#;
+        let c = r#"{"version": "2.0.0", "dependencies": {"a": "1.1.0"}}"#;
+
+        assert_eq!(
+            hash_json_content(a, "/dependencies"),
+            hash_json_content(b, "/dependencies")
+        );
+        assert_ne!(
+            hash_json_content(b, "/dependencies"),
+            hash_json_content(c, "/dependencies")
+        );
+        assert_ne!(
+            hash_json_content(a, "/devDependencies"),
+            hash_json_content(r#"{"devDependencies": null}"#, "/devDependencies")
+        );
+    }
+
+    #[test]
+    fn should_keep_comment_markers_inside_strings() {
+        let content = r#"{"url": "https://nx.dev/*", "glob": "**/*.ts",}"#;
+        assert_eq!(
+            serde_json::from_str::<Value>(&strip_jsonc(content)).unwrap(),
+            serde_json::json!({ "url": "https://nx.dev/*", "glob": "**/*.ts" })
+        );
+    }
+}
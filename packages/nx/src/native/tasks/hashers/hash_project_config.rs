@@ -79,6 +79,7 @@ mod tests {
                 Project {
                     root: "".into(),
                     named_inputs: None,
+                    package_name: None,
                     tags: None,
                     targets: Default::default(),
                 },
@@ -88,6 +89,7 @@ mod tests {
                 Project {
                     root: "libs/js".into(),
                     named_inputs: None,
+                    package_name: None,
                     tags: Some(vec!["type:lib".into(), "scope:js".into()]),
                     targets: HashMap::from([
                         (
@@ -116,6 +118,7 @@ mod tests {
                 Project {
                     root: "libs/js".into(),
                     named_inputs: None,
+                    package_name: None,
                     tags: Some(vec!["type:lib".into(), "scope:js".into()]),
                     targets: HashMap::from([
                         (
@@ -1,27 +1,60 @@
 use std::collections::HashMap;
+use std::path::Path;
 
 use anyhow::*;
 use tracing::{trace, trace_span};
 
 use crate::native::glob::build_glob_set;
+use crate::native::hasher::Hasher;
+use crate::native::tasks::hashers::{hash_json_file, split_json_selector};
 use crate::native::types::FileData;
 
 pub fn hash_project_files(
+    workspace_root: &str,
     project_name: &str,
     project_root: &str,
     file_sets: &[String],
     project_file_map: &HashMap<String, Vec<FileData>>,
 ) -> Result<String> {
     let _span = trace_span!("hash_project_files", project_name).entered();
-    let collected_files =
-        collect_project_files(project_name, project_root, file_sets, project_file_map)?;
-    trace!("collected_files: {:?}", collected_files.len());
-    let mut hasher = xxhash_rust::xxh3::Xxh3::new();
-    for file in collected_files {
-        hasher.update(file.hash.as_bytes());
-        hasher.update(file.file.as_bytes());
+    let (json_file_sets, file_sets): (Vec<_>, Vec<_>) = file_sets
+        .iter()
+        .cloned()
+        .partition(|f| !f.starts_with('!') && split_json_selector(f).1.is_some());
+    let negations = file_sets
+        .iter()
+        .filter(|f| f.starts_with('!'))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut hasher = Hasher::new();
+    // Without an including glob, only the selected JSON values are hashed
+    if file_sets.len() > negations.len() {
+        let collected_files =
+            collect_project_files(project_name, project_root, &file_sets, project_file_map)?;
+        trace!("collected_files: {:?}", collected_files.len());
+        for file in collected_files {
+            hasher.update(file.hash.as_bytes());
+            hasher.update(file.file.as_bytes());
+        }
     }
-    Ok(hasher.digest().to_string())
+    for json_file_set in json_file_sets {
+        let (glob, pointer) = split_json_selector(&json_file_set);
+        let pointer = pointer.unwrap_or_default();
+        let globs = std::iter::once(glob.to_string())
+            .chain(negations.iter().cloned())
+            .collect::<Vec<_>>();
+        for file in collect_project_files(project_name, project_root, &globs, project_file_map)? {
+            let json_hash = hash_json_file(
+                &Path::new(workspace_root).join(&file.file),
+                pointer,
+                &file.hash,
+            );
+            hasher.update(json_hash.as_bytes());
+            hasher.update(pointer.as_bytes());
+            hasher.update(file.file.as_bytes());
+        }
+    }
+    Ok(hasher.digest())
 }
 
 /// base function that should be testable (to make sure that we're getting the proper files back)
@@ -33,6 +66,7 @@ pub fn collect_project_files<'a>(
 ) -> Result<Vec<&'a FileData>> {
     let globs = file_sets
         .iter()
+        .map(|f| split_json_selector(f).0)
         .map(|f| {
             if project_root == "." {
                 f.replace("{projectRoot}/", "")
@@ -64,7 +98,9 @@ mod tests {
     use crate::native::hasher::hash;
 
     use super::*;
+    use assert_fs::prelude::*;
     use std::collections::HashMap;
+    use std::path::Path;
 
     #[test]
     fn test_collect_files() {
@@ -156,7 +192,8 @@ mod tests {
                 file_data4.clone(),
             ],
         );
-        let hash_result = hash_project_files(proj_name, proj_root, file_sets, &file_map).unwrap();
+        let hash_result =
+            hash_project_files("", proj_name, proj_root, file_sets, &file_map).unwrap();
         assert_eq!(
             hash_result,
             hash(
@@ -206,7 +243,8 @@ mod tests {
                 file_data4.clone(),
             ],
         );
-        let hash_result = hash_project_files(proj_name, proj_root, file_sets, &file_map).unwrap();
+        let hash_result =
+            hash_project_files("", proj_name, proj_root, file_sets, &file_map).unwrap();
         assert_eq!(
             hash_result,
             hash(
@@ -220,4 +258,40 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn should_only_hash_selected_json_values_of_projects() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("test/root/package.json")
+            .write_str(r#"{"dependencies": {"a": "1.0.0"}}"#)
+            .unwrap();
+        let proj_name = "test_project";
+        let proj_root = "test/root";
+        let file_sets = &["{projectRoot}/package.json#/dependencies".to_string()];
+        let hash_dependencies = |source_hash: &str| {
+            let file_map = HashMap::from([(
+                String::from(proj_name),
+                vec![
+                    FileData {
+                        file: "test/root/package.json".into(),
+                        hash: "package_json".into(),
+                    },
+                    FileData {
+                        file: "test/root/src/index.ts".into(),
+                        hash: source_hash.into(),
+                    },
+                ],
+            )]);
+            hash_project_files(
+                temp.path().to_str().unwrap(),
+                proj_name,
+                proj_root,
+                file_sets,
+                &file_map,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(hash_dependencies("index_1"), hash_dependencies("index_2"));
+    }
 }
@@ -1,9 +1,14 @@
 use crate::native::hasher::hash;
 use dashmap::DashMap;
-use std::collections::HashMap;
-use std::process::Command;
-use std::sync::Arc;
-use tracing::trace;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, trace};
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
@@ -12,16 +17,158 @@ use std::os::windows::process::CommandExt;
 #[cfg(target_os = "windows")]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
+/// Runtime inputs which do not finish within this time fail hashing
+pub const DEFAULT_RUNTIME_TIMEOUT: Duration = Duration::from_secs(60);
+
+const RUNTIME_CACHE_FILE: &str = "runtime-hashes.json";
+
+#[derive(Debug, Clone, Copy)]
+pub struct RuntimeHashOptions {
+    pub timeout: Duration,
+    /// Ignore what the command prints to stderr
+    pub stdout_only: bool,
+}
+
+impl Default for RuntimeHashOptions {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_RUNTIME_TIMEOUT,
+            stdout_only: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RuntimeCacheEntry {
+    hash: String,
+    /// Seconds since the unix epoch
+    created_at: u64,
+}
+
+/// The results of runtime inputs.
+///
+/// Results expire after `ttl`. When a directory is given they are also
+/// persisted, so they survive restarts of the daemon.
+#[derive(Default)]
+pub struct RuntimeCache {
+    entries: DashMap<String, RuntimeCacheEntry>,
+    path: Option<PathBuf>,
+    ttl: Option<Duration>,
+    dirty: AtomicBool,
+}
+
+impl RuntimeCache {
+    pub fn new(directory: Option<PathBuf>, ttl: Option<Duration>) -> Self {
+        let path = directory
+            .filter(|_| ttl.is_some())
+            .map(|directory| directory.join(RUNTIME_CACHE_FILE));
+        let cache = Self {
+            path,
+            ttl,
+            ..Default::default()
+        };
+        if let Some(path) = &cache.path {
+            let persisted = std::fs::read(path)
+                .ok()
+                .and_then(|content| {
+                    serde_json::from_slice::<HashMap<String, RuntimeCacheEntry>>(&content).ok()
+                })
+                .unwrap_or_default();
+            for (key, entry) in persisted {
+                if !cache.is_expired(&entry) {
+                    cache.entries.insert(key, entry);
+                }
+            }
+            trace!(
+                "Loaded {} runtime hashes from {:?}",
+                cache.entries.len(),
+                path
+            );
+        }
+        cache
+    }
+
+    fn is_expired(&self, entry: &RuntimeCacheEntry) -> bool {
+        self.ttl
+            .is_some_and(|ttl| now_seconds().saturating_sub(entry.created_at) >= ttl.as_secs())
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.entries
+            .get(key)
+            .filter(|entry| !self.is_expired(entry))
+            .map(|entry| entry.hash.clone())
+    }
+
+    fn insert(&self, key: String, hash: String) {
+        self.entries.insert(
+            key,
+            RuntimeCacheEntry {
+                hash,
+                created_at: now_seconds(),
+            },
+        );
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Writes new results to disk, if the cache is persisted
+    pub fn persist(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let entries = self
+            .entries
+            .iter()
+            .filter(|entry| !self.is_expired(entry.value()))
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect::<HashMap<_, _>>();
+        let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&tmp_path, serde_json::to_vec(&entries)?)?;
+        std::fs::rename(&tmp_path, path)?;
+        debug!("Persisted {} runtime hashes to {:?}", entries.len(), path);
+        Ok(())
+    }
+}
+
+fn now_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Results are scoped by the directory the command runs in and its environment
+fn runtime_cache_key(
+    workspace_root: &str,
+    command: &str,
+    env: &HashMap<String, String>,
+    options: &RuntimeHashOptions,
+) -> String {
+    let env = env.iter().collect::<BTreeMap<_, _>>();
+    hash(
+        format!(
+            "{}\0{}\0{}\0{:?}",
+            workspace_root, command, options.stdout_only, env
+        )
+        .as_bytes(),
+    )
+}
+
 pub fn hash_runtime(
     workspace_root: &str,
     command: &str,
     env: &HashMap<String, String>,
-    cache: Arc<DashMap<String, String>>,
+    options: &RuntimeHashOptions,
+    cache: &RuntimeCache,
 ) -> anyhow::Result<String> {
-    let cache_key = format!("{}-{:?}", command, env);
+    let cache_key = runtime_cache_key(workspace_root, command, env, options);
 
     if let Some(cache_results) = cache.get(&cache_key) {
-        return Ok(cache_results.clone());
+        return Ok(cache_results);
     }
 
     let mut command_builder = create_command_builder();
@@ -32,21 +179,73 @@ pub fn hash_runtime(
     env.iter().for_each(|(key, value)| {
         command_builder.env(key, value);
     });
+    command_builder
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
     trace!("executing: {:?}", command_builder);
-    let output = command_builder
-        .output()
+    let mut child = command_builder
+        .spawn()
         .map_err(|e| anyhow::anyhow!("Failed to execute: '{}'\n{}", command, e))?;
-    trace!("{} output: {:?}", command, output);
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= options.timeout {
+            // The output is not awaited, as processes started by the command may still hold it open
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!(
+                "Runtime input '{}' did not finish within {}ms",
+                command,
+                options.timeout.as_millis()
+            );
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    trace!("{} exited with {:?}", command, status);
+
+    let std_out = std::str::from_utf8(&stdout)?.trim();
+    let std_err = std::str::from_utf8(&stderr)?.trim();
+    if !status.success() {
+        anyhow::bail!(
+            "Runtime input '{}' failed with {}\n{}",
+            command,
+            status
+                .code()
+                .map(|code| format!("exit code {}", code))
+                .unwrap_or_else(|| "no exit code".to_string()),
+            std_err
+        );
+    }
 
-    let std_out = std::str::from_utf8(&output.stdout)?.trim();
-    let std_err = std::str::from_utf8(&output.stderr)?.trim();
-    let hash_result = hash(&[std_out.as_bytes(), std_err.as_bytes()].concat());
+    let hash_result = if options.stdout_only {
+        hash(std_out.as_bytes())
+    } else {
+        hash(&[std_out.as_bytes(), std_err.as_bytes()].concat())
+    };
 
     cache.insert(cache_key, hash_result.clone());
 
     Ok(hash_result)
 }
 
+fn read_in_background<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut content = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut content);
+        }
+        content
+    })
+}
+
 #[cfg(target_os = "windows")]
 pub fn create_command_builder() -> Command {
     let comspec = std::env::var("COMSPEC");
@@ -70,18 +269,96 @@ pub fn create_command_builder() -> Command {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use dashmap::DashMap;
+    use assert_fs::TempDir;
     use std::collections::HashMap;
-    use std::sync::Arc;
 
     #[test]
     fn test_hash_runtime() {
         let workspace_root = "/tmp";
         let command = "echo 'runtime'";
         let env: HashMap<String, String> = HashMap::new();
-        let cache = Arc::new(DashMap::new());
+        let cache = RuntimeCache::default();
 
-        let result = hash_runtime(workspace_root, command, &env, Arc::clone(&cache)).unwrap();
+        let result = hash_runtime(
+            workspace_root,
+            command,
+            &env,
+            &RuntimeHashOptions::default(),
+            &cache,
+        )
+        .unwrap();
         assert_eq!(result, "10571312846059850300");
     }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn should_fail_runtime_inputs_which_fail_or_hang() {
+        let env = HashMap::new();
+        let cache = RuntimeCache::default();
+
+        let error = hash_runtime(
+            "/tmp",
+            "echo 'broken' >&2; exit 3",
+            &env,
+            &RuntimeHashOptions::default(),
+            &cache,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Runtime input 'echo 'broken' >&2; exit 3' failed with exit code 3\nbroken"
+        );
+
+        let options = RuntimeHashOptions {
+            timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let error = hash_runtime("/tmp", "sleep 5", &env, &options, &cache).unwrap_err();
+        assert!(error.to_string().contains("did not finish within 50ms"));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn should_only_hash_stdout() {
+        let env = HashMap::new();
+        let options = RuntimeHashOptions {
+            stdout_only: true,
+            ..Default::default()
+        };
+
+        let noisy = hash_runtime(
+            "/tmp",
+            "echo 'runtime'; echo 'warning' >&2",
+            &env,
+            &options,
+            &RuntimeCache::default(),
+        )
+        .unwrap();
+        assert_eq!(noisy, hash(b"runtime"));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn should_persist_runtime_hashes() {
+        let temp = TempDir::new().unwrap();
+        let env = HashMap::new();
+        let options = RuntimeHashOptions::default();
+        let ttl = Some(Duration::from_secs(60));
+
+        let cache = RuntimeCache::new(Some(temp.path().to_path_buf()), ttl);
+        let result = hash_runtime("/tmp", "date +%s%N", &env, &options, &cache).unwrap();
+        cache.persist().unwrap();
+
+        let restored = RuntimeCache::new(Some(temp.path().to_path_buf()), ttl);
+        assert_eq!(
+            hash_runtime("/tmp", "date +%s%N", &env, &options, &restored).unwrap(),
+            result
+        );
+
+        // Results are scoped by working directory
+        assert_ne!(
+            runtime_cache_key("/tmp", "date +%s%N", &env, &options),
+            runtime_cache_key("/var", "date +%s%N", &env, &options)
+        );
+    }
 }
@@ -1,16 +1,71 @@
 use crate::native::cache::expand_outputs::get_files_for_outputs;
-use crate::native::glob::build_glob_set;
+use crate::native::glob::{build_glob_set, contains_glob_pattern};
 use crate::native::hasher::{hash_array, hash_file};
 use anyhow::*;
 use rayon::prelude::*;
+use std::collections::BTreeMap;
 use std::path::Path;
 use tracing::trace;
 
-pub fn hash_task_output(workspace_root: &str, glob: &str, outputs: &[String]) -> Result<String> {
+/// The output files of a task as recorded in the cache, mapped to their content hashes.
+/// Paths are relative to the workspace root.
+pub type OutputManifest = BTreeMap<String, String>;
+
+/// Reads the output files recorded in the cache for the task with `hash`. Manifests which
+/// miss files or were hashed with another algorithm are ignored, so the outputs are hashed
+/// from disk instead.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_output_manifest(cache_directory: &str, hash: &str) -> Option<OutputManifest> {
+    use crate::native::cache::content_store::read_cached_manifest;
+    use crate::native::hasher::HashAlgorithm;
+
+    let manifest = read_cached_manifest(Path::new(cache_directory), hash)?;
+    if !manifest.complete
+        || manifest.hash_algorithm.as_deref() != Some(HashAlgorithm::current().name())
+    {
+        trace!("The cache manifest of {} cannot be used for hashing", hash);
+        return None;
+    }
+    Some(
+        manifest
+            .files
+            .into_iter()
+            .map(|(file, entry)| (file, entry.blob))
+            .collect(),
+    )
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read_output_manifest(_cache_directory: &str, _hash: &str) -> Option<OutputManifest> {
+    None
+}
+
+/// Hashes the output files matching `glob`. When the `manifest` of the task which produced
+/// the outputs is known, the recorded hashes are used, so the outputs do not have to exist
+/// on disk. Both ways produce the same hash for the same files.
+pub fn hash_task_output(
+    workspace_root: &str,
+    glob: &str,
+    outputs: &[String],
+    manifest: Option<&OutputManifest>,
+) -> Result<String> {
+    let glob = build_glob_set(&[glob])?;
+
+    if let Some(manifest) = manifest {
+        let hashes = manifest_files_for_outputs(manifest, outputs)?
+            .filter(|(file, _)| glob.is_match(file))
+            .map(|(_, hash)| Some(hash.clone()))
+            .collect::<Vec<_>>();
+        trace!(
+            "hashed {} output files from the cache manifest",
+            hashes.len()
+        );
+        return Ok(hash_array(hashes));
+    }
+
     let now = std::time::Instant::now();
     let output_files = get_files_for_outputs(workspace_root.to_string(), outputs.to_vec())?;
     trace!("get_files_for_outputs: {:?}", now.elapsed());
-    let glob = build_glob_set(&[glob])?;
     let hashes = output_files
         .into_par_iter()
         .filter(|file| glob.is_match(file))
@@ -26,3 +81,129 @@ pub fn hash_task_output(workspace_root: &str, glob: &str, outputs: &[String]) ->
         .collect::<Vec<_>>();
     Ok(hash_array(hashes.into_iter().map(Some).collect()))
 }
+
+/// The entries of `manifest` which belong to `outputs`, in the same order as
+/// `get_files_for_outputs` lists them on disk
+fn manifest_files_for_outputs<'a>(
+    manifest: &'a OutputManifest,
+    outputs: &'a [String],
+) -> Result<impl Iterator<Item = (&'a String, &'a String)>> {
+    let (output_globs, output_paths): (Vec<_>, Vec<_>) = outputs
+        .iter()
+        .partition(|output| contains_glob_pattern(output));
+    // An empty glob set matches every file
+    let output_globs = if output_globs.is_empty() {
+        None
+    } else {
+        Some(build_glob_set(&output_globs)?)
+    };
+    let output_paths = output_paths
+        .into_iter()
+        .map(|output| output.trim_end_matches('/'))
+        .collect::<Vec<_>>();
+
+    Ok(manifest.iter().filter(move |(file, _)| {
+        output_globs
+            .as_ref()
+            .is_some_and(|globs| globs.is_match(file.as_str()))
+            || output_paths.iter().any(|output| {
+                file.as_str() == *output
+                    || file
+                        .strip_prefix(output)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::native::hasher::hash;
+    use assert_fs::TempDir;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn should_hash_outputs_from_the_manifest_like_from_disk() {
+        let temp = TempDir::new().unwrap();
+        temp.child("dist/libs/a/index.js").write_str("a").unwrap();
+        temp.child("dist/libs/a/index.d.ts")
+            .write_str("a types")
+            .unwrap();
+        temp.child("dist/libs/a/lib/util.js")
+            .write_str("util")
+            .unwrap();
+        let workspace_root = temp.display().to_string();
+        let outputs = vec!["dist/libs/a".to_string()];
+
+        let manifest = OutputManifest::from([
+            ("dist/libs/a/index.d.ts".to_string(), hash(b"a types")),
+            ("dist/libs/a/index.js".to_string(), hash(b"a")),
+            ("dist/libs/a/lib/util.js".to_string(), hash(b"util")),
+            ("dist/libs/ab/index.js".to_string(), hash(b"ab")),
+        ]);
+
+        let from_disk = hash_task_output(&workspace_root, "**/*.js", &outputs, None).unwrap();
+        let from_manifest =
+            hash_task_output(&workspace_root, "**/*.js", &outputs, Some(&manifest)).unwrap();
+        assert_eq!(from_disk, from_manifest);
+
+        std::fs::remove_dir_all(temp.join("dist")).unwrap();
+        assert_eq!(
+            hash_task_output(&workspace_root, "**/*.js", &outputs, Some(&manifest)).unwrap(),
+            from_disk
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_hash_outputs_from_a_stored_manifest_like_from_disk() {
+        use crate::native::cache::content_store::{CacheManifest, ContentStore};
+        use std::os::unix::fs::PermissionsExt;
+
+        let workspace = TempDir::new().unwrap();
+        let cache = TempDir::new().unwrap();
+        let store = ContentStore::new(cache.path()).unwrap();
+        for dir in [
+            workspace.to_path_buf(),
+            cache.join("previous"),
+            cache.join("1"),
+        ] {
+            let dir = assert_fs::fixture::ChildPath::new(dir);
+            dir.child("dist/libs/a/index.js").write_str("a").unwrap();
+            dir.child("dist/libs/a/main.js").write_str("main").unwrap();
+        }
+        // A blob with other permissions already exists, so this file is not stored
+        store.deduplicate(&cache.join("previous")).unwrap();
+        std::fs::set_permissions(
+            cache.join("1/dist/libs/a/main.js"),
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        let manifest = store.deduplicate(&cache.join("1")).unwrap();
+        assert!(!manifest.files["dist/libs/a/main.js"].stored);
+        store.write_manifest("1", &manifest).unwrap();
+
+        let workspace_root = workspace.display().to_string();
+        let outputs = vec!["dist/libs/a".to_string()];
+        let output_manifest =
+            read_output_manifest(&cache.display().to_string(), "1").expect("a usable manifest");
+        assert_eq!(
+            hash_task_output(&workspace_root, "**/*.js", &outputs, Some(&output_manifest)).unwrap(),
+            hash_task_output(&workspace_root, "**/*.js", &outputs, None).unwrap()
+        );
+
+        store
+            .write_manifest(
+                "1",
+                &CacheManifest {
+                    hash_algorithm: Some("Unknown".to_string()),
+                    ..manifest
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            read_output_manifest(&cache.display().to_string(), "1"),
+            None
+        );
+    }
+}
@@ -1,9 +1,11 @@
 use rayon::prelude::*;
+use std::path::Path;
 use std::sync::Arc;
 
 use crate::native::glob::build_glob_set;
 use crate::native::glob::glob_files::glob_files;
-use crate::native::hasher::hash;
+use crate::native::hasher::{Hasher, hash};
+use crate::native::tasks::hashers::{hash_json_file, split_json_selector};
 use crate::native::types::FileData;
 use anyhow::*;
 use dashmap::DashMap;
@@ -38,11 +40,43 @@ pub fn get_workspace_files<'a, 'b>(
     workspace_file_sets: &'a [String],
     all_workspace_files: &'b [FileData],
 ) -> napi::Result<impl ParallelIterator<Item = &'b FileData>> {
-    let globs = globs_from_workspace_inputs(workspace_file_sets);
+    let globs = globs_from_workspace_inputs(workspace_file_sets)
+        .into_iter()
+        .map(|glob| split_json_selector(&glob).0.to_string())
+        .collect::<Vec<_>>();
     glob_files(all_workspace_files, globs, None)
 }
 
+/// The path prefixes of every file `workspace_file_sets` can match, sorted and without
+/// duplicates. Files outside of them never change the hash of the file sets.
+pub fn get_workspace_file_set_roots(workspace_file_sets: &[String]) -> Vec<String> {
+    let globs = globs_from_workspace_inputs(workspace_file_sets);
+    let mut roots = globs
+        .iter()
+        .filter(|glob| !glob.starts_with('!'))
+        .map(|glob| glob_root(split_json_selector(glob).0).to_string())
+        .collect::<Vec<_>>();
+    if roots.iter().any(|root| root.is_empty()) {
+        return vec![String::new()];
+    }
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// The directory part of `glob` before its first pattern, or all of it when it is a path
+fn glob_root(glob: &str) -> &str {
+    match glob.find(['*', '?', '[', '{', '!', '(', '|']) {
+        Some(pattern_start) => {
+            let root_end = glob[..pattern_start].rfind('/').map_or(0, |i| i + 1);
+            &glob[..root_end]
+        }
+        None => glob,
+    }
+}
+
 pub fn hash_workspace_files(
+    workspace_root: &str,
     workspace_file_sets: &[String],
     all_workspace_files: &[FileData],
     cache: Arc<DashMap<String, String>>,
@@ -58,19 +92,59 @@ pub fn hash_workspace_files(
         return Ok(cache_results.clone());
     }
 
-    let glob = build_glob_set(&globs)?;
+    let (json_globs, globs): (Vec<_>, Vec<_>) = globs
+        .iter()
+        .partition(|glob| !glob.starts_with('!') && split_json_selector(glob).1.is_some());
+    let negations = globs
+        .iter()
+        .filter(|glob| glob.starts_with('!'))
+        .map(|glob| glob.as_str())
+        .collect::<Vec<_>>();
+    // Without an including glob, only the selected JSON values are hashed
+    let glob = if globs.len() > negations.len() {
+        Some(build_glob_set(&globs)?)
+    } else {
+        None
+    };
 
-    let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+    let mut hasher = Hasher::new();
     debug_span!("Hashing workspace fileset", cache_key).in_scope(|| {
-        for file in all_workspace_files
-            .iter()
-            .filter(|file| glob.is_match(&file.file))
-        {
-            debug!("Adding {:?} ({:?}) to hash", file.hash, file.file);
-            hasher.update(file.file.clone().as_bytes());
-            hasher.update(file.hash.clone().as_bytes());
+        if let Some(glob) = glob {
+            for file in all_workspace_files
+                .iter()
+                .filter(|file| glob.is_match(&file.file))
+            {
+                debug!("Adding {:?} ({:?}) to hash", file.hash, file.file);
+                hasher.update(file.file.clone().as_bytes());
+                hasher.update(file.hash.clone().as_bytes());
+            }
         }
-        let hashed_value = hasher.digest().to_string();
+        for json_glob in json_globs {
+            let (json_glob, pointer) = split_json_selector(json_glob);
+            let pointer = pointer.unwrap_or_default();
+            let json_glob_set = std::iter::once(json_glob)
+                .chain(negations.iter().copied())
+                .collect::<Vec<_>>();
+            let glob = build_glob_set(&json_glob_set)?;
+            for file in all_workspace_files
+                .iter()
+                .filter(|file| glob.is_match(&file.file))
+            {
+                let json_hash = hash_json_file(
+                    &Path::new(workspace_root).join(&file.file),
+                    pointer,
+                    &file.hash,
+                );
+                debug!(
+                    "Adding {:?} ({:?}#{}) to hash",
+                    json_hash, file.file, pointer
+                );
+                hasher.update(file.file.as_bytes());
+                hasher.update(pointer.as_bytes());
+                hasher.update(json_hash.as_bytes());
+            }
+        }
+        let hashed_value = hasher.digest();
         debug!("Hash Value: {:?}", hashed_value);
 
         cache.insert(cache_key.to_string(), hashed_value.clone());
@@ -83,12 +157,14 @@ mod test {
     use crate::native::hasher::hash;
 
     use super::*;
+    use assert_fs::prelude::*;
     use dashmap::DashMap;
     use std::sync::Arc;
 
     #[test]
     fn invalid_workspace_input_is_just_empty_hash() {
         let result = hash_workspace_files(
+            "",
             &["packages/{package}".to_string()],
             &[],
             Arc::new(DashMap::new()),
@@ -116,6 +192,7 @@ mod test {
             hash: "abc".into(),
         };
         let result = hash_workspace_files(
+            "",
             &["{workspaceRoot}/.gitignore".to_string()],
             &[
                 gitignore_file.clone(),
@@ -149,6 +226,7 @@ mod test {
         };
         for i in 0..1000 {
             let result = hash_workspace_files(
+                "",
                 &["{workspaceRoot}/**/*".to_string()],
                 &[
                     gitignore_file.clone(),
@@ -162,4 +240,121 @@ mod test {
             assert_eq!(result, "13759877301064854697");
         }
     }
+
+    #[test]
+    fn should_hash_selected_json_values() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let package_json = temp.child("package.json");
+        let files = [FileData {
+            file: "package.json".into(),
+            hash: "123".into(),
+        }];
+        let hash_dependencies = || {
+            hash_workspace_files(
+                temp.path().to_str().unwrap(),
+                &["{workspaceRoot}/package.json#/dependencies".to_string()],
+                &files,
+                Arc::new(DashMap::new()),
+            )
+            .unwrap()
+        };
+
+        package_json
+            .write_str(r#"{"version": "1.0.0", "dependencies": {"a": "1.0.0", "b": "1.0.0"}}"#)
+            .unwrap();
+        let before = hash_dependencies();
+
+        package_json
+            .write_str(r#"{"dependencies": {"b": "1.0.0", "a": "1.0.0"}, "version": "2.0.0"}"#)
+            .unwrap();
+        assert_eq!(hash_dependencies(), before);
+
+        package_json
+            .write_str(r#"{"dependencies": {"a": "1.1.0", "b": "1.0.0"}}"#)
+            .unwrap();
+        assert_ne!(hash_dependencies(), before);
+    }
+
+    #[test]
+    fn should_exclude_files_from_selected_json_values() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("package.json")
+            .write_str(r#"{"dependencies": {"a": "1.0.0"}}"#)
+            .unwrap();
+        temp.child("tools/package.json")
+            .write_str(r#"{"dependencies": {"b": "1.0.0"}}"#)
+            .unwrap();
+        let files = |tools_hash: &str| {
+            [
+                FileData {
+                    file: "package.json".into(),
+                    hash: "123".into(),
+                },
+                FileData {
+                    file: "tools/package.json".into(),
+                    hash: tools_hash.into(),
+                },
+            ]
+        };
+        let hash_dependencies = |files: &[FileData]| {
+            hash_workspace_files(
+                temp.path().to_str().unwrap(),
+                &[
+                    "{workspaceRoot}/**/package.json#/dependencies".to_string(),
+                    "!{workspaceRoot}/tools/**".to_string(),
+                ],
+                files,
+                Arc::new(DashMap::new()),
+            )
+            .unwrap()
+        };
+
+        let before = hash_dependencies(&files("456"));
+        temp.child("tools/package.json")
+            .write_str(r#"{"dependencies": {"b": "2.0.0"}}"#)
+            .unwrap();
+        assert_eq!(hash_dependencies(&files("789")), before);
+    }
+
+    #[test]
+    fn should_hash_unreadable_json_files_by_their_file_hash() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let hash_dependencies = |file_hash: &str| {
+            hash_workspace_files(
+                temp.path().to_str().unwrap(),
+                &["{workspaceRoot}/package.json#/dependencies".to_string()],
+                &[FileData {
+                    file: "package.json".into(),
+                    hash: file_hash.into(),
+                }],
+                Arc::new(DashMap::new()),
+            )
+            .unwrap()
+        };
+
+        assert_ne!(hash_dependencies("123"), hash_dependencies("456"));
+    }
+
+    #[test]
+    fn should_find_the_roots_of_workspace_file_sets() {
+        let roots = |file_sets: &[&str]| {
+            get_workspace_file_set_roots(
+                &file_sets.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            )
+        };
+
+        assert_eq!(
+            roots(&[
+                "{workspaceRoot}/tools/**/*.ts",
+                "{workspaceRoot}/package.json#/dependencies",
+                "{workspaceRoot}/tools/scripts/*.js",
+                "!{workspaceRoot}/**/*.spec.ts",
+                "{workspaceRoot}/.github/",
+            ]),
+            vec![".github/", "package.json", "tools/", "tools/scripts/"]
+        );
+        assert_eq!(roots(&["{workspaceRoot}/*.json"]), vec![""]);
+        assert!(roots(&["!{workspaceRoot}/dist/**"]).is_empty());
+        assert!(roots(&["packages/{package}"]).is_empty());
+    }
 }
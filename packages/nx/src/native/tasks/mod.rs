@@ -10,6 +10,12 @@ mod utils;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod details;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod hash_diff;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod process_tree_usage;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod running_tasks_service;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod task_graph_estimate;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod task_history;
@@ -0,0 +1,185 @@
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, bounded};
+use std::collections::HashMap;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+use tracing::trace;
+
+/// How often the processes of a tree are sampled
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+#[napi(object)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessTreeUsage {
+    /// The peak resident set size of the process tree, in bytes
+    pub peak_rss: i64,
+    /// The CPU time of the process tree, in milliseconds
+    pub cpu_time: i64,
+}
+
+/// Samples the memory and CPU time of a process and its descendants until it is stopped.
+/// Processes which run for less than one sample interval may not be seen at all.
+#[napi]
+pub struct ProcessTreeMonitor {
+    stop_tx: Sender<()>,
+    sampler: Option<JoinHandle<Option<ProcessTreeUsage>>>,
+    usage: Option<ProcessTreeUsage>,
+}
+
+#[napi]
+impl ProcessTreeMonitor {
+    #[napi(constructor)]
+    pub fn new(pid: u32) -> Self {
+        let (stop_tx, stop_rx) = bounded(1);
+        let sampler = std::thread::spawn(move || sample_process_tree(Pid::from_u32(pid), stop_rx));
+        Self {
+            stop_tx,
+            sampler: Some(sampler),
+            usage: None,
+        }
+    }
+
+    /// Stops sampling and returns the usage of the process tree, unless the process was
+    /// never seen
+    #[napi]
+    pub fn stop(&mut self) -> Option<ProcessTreeUsage> {
+        if let Some(sampler) = self.sampler.take() {
+            self.stop_tx.send(()).ok();
+            self.usage = sampler.join().ok().flatten();
+        }
+        self.usage
+    }
+}
+
+impl Drop for ProcessTreeMonitor {
+    fn drop(&mut self) {
+        self.stop_tx.send(()).ok();
+    }
+}
+
+fn sample_process_tree(root: Pid, stop_rx: Receiver<()>) -> Option<ProcessTreeUsage> {
+    let mut sys = System::new();
+    let refresh_kind = ProcessRefreshKind::nothing().with_memory().with_cpu();
+    let mut tree = ProcessTree::new(root);
+    let mut peak_rss: Option<u64> = None;
+
+    loop {
+        sys.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh_kind);
+        if let Some(rss) = tree.sample(&sys) {
+            peak_rss = Some(peak_rss.map_or(rss, |peak| peak.max(rss)));
+        }
+        match stop_rx.recv_timeout(SAMPLE_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => continue,
+            _ => break,
+        }
+    }
+
+    let usage = peak_rss.map(|peak_rss| ProcessTreeUsage {
+        peak_rss: peak_rss as i64,
+        cpu_time: tree.cpu_time() as i64,
+    });
+    trace!("Usage of the process tree of {}: {:?}", root, usage);
+    usage
+}
+
+/// The processes descending from a root process, identified by their pid and start time so
+/// recycled pids are not mistaken for members
+struct ProcessTree {
+    root: Pid,
+    members: HashMap<Pid, u64>,
+    cpu_times: HashMap<Pid, u64>,
+}
+
+impl ProcessTree {
+    fn new(root: Pid) -> Self {
+        Self {
+            root,
+            members: HashMap::new(),
+            cpu_times: HashMap::new(),
+        }
+    }
+
+    /// Adds the new descendants of the tree and records the CPU time of its members.
+    /// Returns the resident set size of the live members, if there are any.
+    fn sample(&mut self, sys: &System) -> Option<u64> {
+        if self.members.is_empty() {
+            let root = sys.process(self.root)?;
+            self.members.insert(self.root, root.start_time());
+        }
+
+        let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+        for (pid, process) in sys.processes() {
+            if let Some(parent) = process.parent() {
+                children.entry(parent).or_default().push(*pid);
+            }
+        }
+        let mut pending = self
+            .members
+            .keys()
+            .copied()
+            .filter(|pid| self.is_member(sys, *pid))
+            .collect::<Vec<_>>();
+        while let Some(pid) = pending.pop() {
+            for child in children.get(&pid).into_iter().flatten() {
+                if self.members.contains_key(child) {
+                    continue;
+                }
+                if let Some(process) = sys.process(*child) {
+                    self.members.insert(*child, process.start_time());
+                    pending.push(*child);
+                }
+            }
+        }
+
+        let mut rss = None;
+        for pid in self.members.keys() {
+            if !self.is_member(sys, *pid) {
+                continue;
+            }
+            let Some(process) = sys.process(*pid) else {
+                continue;
+            };
+            *rss.get_or_insert(0) += process.memory();
+            self.cpu_times.insert(*pid, process.accumulated_cpu_time());
+        }
+        rss
+    }
+
+    fn is_member(&self, sys: &System, pid: Pid) -> bool {
+        match (self.members.get(&pid), sys.process(pid)) {
+            (Some(start_time), Some(process)) => process.start_time() == *start_time,
+            _ => false,
+        }
+    }
+
+    /// The CPU time of every member, as of the last sample it was alive in
+    fn cpu_time(&self) -> u64 {
+        self.cpu_times.values().sum()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn should_sample_the_usage_of_a_process_tree() {
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 1 & wait"])
+            .spawn()
+            .unwrap();
+        let mut monitor = ProcessTreeMonitor::new(child.id());
+        child.wait().unwrap();
+
+        let usage = monitor.stop().expect("the process tree was sampled");
+        assert!(usage.peak_rss > 0);
+        assert_eq!(monitor.stop(), Some(usage));
+    }
+
+    #[test]
+    fn should_not_report_usage_of_missing_processes() {
+        let mut monitor = ProcessTreeMonitor::new(u32::MAX);
+        assert_eq!(monitor.stop(), None);
+    }
+}
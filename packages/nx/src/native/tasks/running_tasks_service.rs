@@ -1,15 +1,107 @@
 use crate::native::db::connection::NxDbConnection;
+use crate::native::db::migrations::{Migration, migrate};
+use crate::native::machine_id::get_machine_id;
 use crate::native::utils::Normalize;
 use hashbrown::HashSet;
 use napi::bindgen_prelude::External;
+use rusqlite::{OptionalExtension, Row, params};
 use std::env::args_os;
 use std::ffi::OsString;
+use std::time::{SystemTime, UNIX_EPOCH};
 use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
 use tracing::debug;
 
+/// How long the lease of a task running on another machine stays valid without a heartbeat,
+/// in milliseconds. The pid of such a task cannot be checked.
+const LEASE_TIMEOUT: i64 = 30_000;
+
+const SELECT_LEASE: &str =
+    "SELECT pid, command, cwd, machine_id, process_started_at, started_at, heartbeat_at
+    FROM running_tasks WHERE task_id = ?1";
+
+/// The claim of a process on running a task, which other processes wait on
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunningTaskLease {
+    pub task_id: String,
+    /// The pid of the process which runs the task
+    pub pid: u32,
+    pub machine_id: Option<String>,
+    /// When the task was started, in milliseconds since the epoch
+    pub started_at: Option<i64>,
+    /// When the owner last renewed the lease, in milliseconds since the epoch
+    pub heartbeat_at: Option<i64>,
+    /// Whether the lease is held by the current process
+    pub owned: bool,
+}
+
+/// A row of `running_tasks`. Rows written before leases existed only have a pid, command and cwd.
+#[derive(Debug)]
+struct StoredLease {
+    pid: u32,
+    command: String,
+    cwd: String,
+    machine_id: Option<String>,
+    process_started_at: Option<i64>,
+    started_at: Option<i64>,
+    heartbeat_at: Option<i64>,
+}
+
+impl StoredLease {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            pid: row.get(0)?,
+            command: row.get(1)?,
+            cwd: row.get(2)?,
+            machine_id: row.get(3)?,
+            process_started_at: row.get(4)?,
+            started_at: row.get(5)?,
+            heartbeat_at: row.get(6)?,
+        })
+    }
+}
+
+/// The current process, as the owner of leases
+struct LeaseOwner {
+    pid: u32,
+    machine_id: String,
+    /// When the process started, in seconds since the epoch. Tells a process apart from a
+    /// later one which got the same pid.
+    process_started_at: Option<i64>,
+}
+
+impl LeaseOwner {
+    fn current() -> Self {
+        let pid = std::process::id();
+        Self {
+            pid,
+            machine_id: get_machine_id(),
+            process_started_at: process_start_time(pid),
+        }
+    }
+
+    fn owns(&self, lease: &StoredLease) -> bool {
+        lease.pid == self.pid
+            && lease
+                .machine_id
+                .as_ref()
+                .is_none_or(|machine_id| *machine_id == self.machine_id)
+    }
+
+    fn is_alive(&self, lease: &StoredLease, now: i64) -> bool {
+        match &lease.machine_id {
+            Some(machine_id) if *machine_id != self.machine_id => lease
+                .heartbeat_at
+                .is_some_and(|heartbeat_at| now - heartbeat_at < LEASE_TIMEOUT),
+            _ => is_process_alive(lease),
+        }
+    }
+}
+
 #[napi]
 struct RunningTasksService {
     db: External<NxDbConnection>,
+    owner: LeaseOwner,
     added_tasks: HashSet<String>,
 }
 
@@ -17,8 +109,9 @@ struct RunningTasksService {
 impl RunningTasksService {
     #[napi(constructor)]
     pub fn new(db: External<NxDbConnection>) -> anyhow::Result<Self> {
-        let s = Self {
+        let mut s = Self {
             db,
+            owner: LeaseOwner::current(),
             added_tasks: Default::default(),
         };
 
@@ -31,7 +124,7 @@ impl RunningTasksService {
     pub fn get_running_tasks(&mut self, ids: Vec<String>) -> anyhow::Result<Vec<String>> {
         let mut results = Vec::<String>::with_capacity(ids.len());
         for id in ids.into_iter() {
-            if self.is_task_running(&id)? {
+            if self.get_running_task(id.clone())?.is_some() {
                 debug!("Task {} is running", &id);
                 results.push(id);
             } else {
@@ -41,53 +134,41 @@ impl RunningTasksService {
         Ok(results)
     }
 
-    fn is_task_running(&self, task_id: &String) -> anyhow::Result<bool> {
-        if let Some((pid, db_process_command, db_process_cwd)) = self.db.query_row(
-            "SELECT pid, command, cwd FROM running_tasks WHERE task_id = ?",
-            [task_id],
-            |row| {
-                let pid: u32 = row.get(0)?;
-                let command: String = row.get(1)?;
-                let cwd: String = row.get(2)?;
-
-                Ok((pid, command, cwd))
-            },
-        )? {
-            debug!("Checking if {} exists", pid);
-
-            let mut sys = System::new();
-            sys.refresh_processes_specifics(
-                ProcessesToUpdate::Some(&[Pid::from(pid as usize)]),
-                true,
-                ProcessRefreshKind::everything(),
-            );
-
-            match sys.process(sysinfo::Pid::from(pid as usize)) {
-                Some(process_info) => {
-                    let cmd = process_info.cmd().to_vec();
-                    let cmd_str = cmd
-                        .iter()
-                        .map(|s| s.to_string_lossy().to_string())
-                        .collect::<Vec<_>>()
-                        .join(" ");
-
-                    if let Some(cwd_path) = process_info.cwd() {
-                        let cwd_str = cwd_path.to_normalized_string();
-                        Ok(cmd_str == db_process_command && cwd_str == db_process_cwd)
-                    } else {
-                        Ok(cmd_str == db_process_command)
-                    }
-                }
-                None => Ok(false),
-            }
-        } else {
-            Ok(false)
+    /// The lease of `task_id` if a live process is running it. Leases of processes which
+    /// are gone, or whose pid now belongs to another process, are removed.
+    #[napi]
+    pub fn get_running_task(&self, task_id: String) -> anyhow::Result<Option<RunningTaskLease>> {
+        let Some(lease) = self
+            .db
+            .query_row(SELECT_LEASE, [&task_id], StoredLease::from_row)?
+        else {
+            return Ok(None);
+        };
+
+        if self.owner.is_alive(&lease, now()) {
+            return Ok(Some(RunningTaskLease {
+                owned: self.owner.owns(&lease),
+                task_id,
+                pid: lease.pid,
+                machine_id: lease.machine_id,
+                started_at: lease.started_at,
+                heartbeat_at: lease.heartbeat_at,
+            }));
         }
+
+        debug!("Removing stale lease of {} held by {}", &task_id, lease.pid);
+        // Only remove the stale lease, another process may have taken over in the meantime
+        self.db.execute(
+            "DELETE FROM running_tasks WHERE task_id = ?1 AND pid = ?2 AND started_at IS ?3",
+            params![task_id, lease.pid, lease.started_at],
+        )?;
+        Ok(None)
     }
 
+    /// Takes the lease of `task_id` for the current process. Returns false without taking it
+    /// when another live process is already running the task.
     #[napi]
-    pub fn add_running_task(&mut self, task_id: String) -> anyhow::Result<()> {
-        let pid = std::process::id();
+    pub fn add_running_task(&mut self, task_id: String) -> anyhow::Result<bool> {
         let command = args_os().collect::<Vec<OsString>>();
         // Convert command vector to a string representation
         let command_str = command
@@ -99,33 +180,90 @@ impl RunningTasksService {
         let cwd = std::env::current_dir()
             .expect("The current working directory does not exist")
             .to_normalized_string();
+        let owner = &self.owner;
+        let now = now();
+        let acquired = self.db.immediate_transaction(|conn| {
+            let lease = conn
+                .query_row(SELECT_LEASE, [&task_id], StoredLease::from_row)
+                .optional()?;
+            match lease {
+                Some(lease) if !owner.owns(&lease) && owner.is_alive(&lease, now) => {
+                    debug!("{} is already running in process {}", &task_id, lease.pid);
+                    Ok(false)
+                }
+                _ => {
+                    conn.execute(
+                        "INSERT OR REPLACE INTO running_tasks (task_id, pid, command, cwd, machine_id, process_started_at, started_at, heartbeat_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                        params![
+                            task_id,
+                            owner.pid,
+                            command_str,
+                            cwd,
+                            owner.machine_id,
+                            owner.process_started_at,
+                            now
+                        ],
+                    )?;
+                    Ok(true)
+                }
+            }
+        })?;
+        if acquired {
+            debug!("Added {} to running tasks", &task_id);
+            self.added_tasks.insert(task_id);
+        }
+        Ok(acquired)
+    }
+
+    /// Renews the leases held by the current process, so processes on other machines do not
+    /// consider them stale
+    #[napi]
+    pub fn heartbeat(&self) -> anyhow::Result<()> {
         self.db.execute(
-            "INSERT OR REPLACE INTO running_tasks (task_id, pid, command, cwd) VALUES (?, ?, ?, ?)",
-            [&task_id, &pid.to_string(), &command_str, &cwd],
+            "UPDATE running_tasks SET heartbeat_at = ?1 WHERE pid = ?2 AND machine_id = ?3",
+            params![now(), self.owner.pid, self.owner.machine_id],
         )?;
-        debug!("Added {} to running tasks", &task_id);
-        self.added_tasks.insert(task_id);
         Ok(())
     }
 
     #[napi]
     pub fn remove_running_task(&self, task_id: String) -> anyhow::Result<()> {
-        self.db
-            .execute("DELETE FROM running_tasks WHERE task_id = ?", [&task_id])?;
+        self.db.execute(
+            "DELETE FROM running_tasks WHERE task_id = ?1 AND pid = ?2 AND (machine_id IS NULL OR machine_id = ?3)",
+            params![task_id, self.owner.pid, self.owner.machine_id],
+        )?;
         debug!("Removed {} from running tasks", task_id);
         Ok(())
     }
 
-    fn setup(&self) -> anyhow::Result<()> {
-        self.db.execute_batch(
-            "
-            CREATE TABLE IF NOT EXISTS running_tasks (
-                task_id TEXT PRIMARY KEY NOT NULL,
-                pid INTEGER NOT NULL,
-                command TEXT NOT NULL,
-                cwd TEXT NOT NULL
-            );
-            ",
+    fn setup(&mut self) -> anyhow::Result<()> {
+        migrate(
+            &mut self.db,
+            "running_tasks",
+            &[
+                Migration {
+                    version: 1,
+                    description: "Create running_tasks",
+                    sql: "
+                    CREATE TABLE IF NOT EXISTS running_tasks (
+                        task_id TEXT PRIMARY KEY NOT NULL,
+                        pid INTEGER NOT NULL,
+                        command TEXT NOT NULL,
+                        cwd TEXT NOT NULL
+                    );
+                    ",
+                },
+                Migration {
+                    version: 2,
+                    description: "Add leases to running_tasks",
+                    sql: "
+                    ALTER TABLE running_tasks ADD COLUMN machine_id TEXT;
+                    ALTER TABLE running_tasks ADD COLUMN process_started_at INTEGER;
+                    ALTER TABLE running_tasks ADD COLUMN started_at INTEGER;
+                    ALTER TABLE running_tasks ADD COLUMN heartbeat_at INTEGER;
+                    ",
+                },
+            ],
         )?;
         debug!("Setup running tasks service");
         Ok(())
@@ -141,6 +279,57 @@ impl Drop for RunningTasksService {
     }
 }
 
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn refresh_process(pid: u32) -> System {
+    let mut sys = System::new();
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[Pid::from(pid as usize)]),
+        true,
+        ProcessRefreshKind::everything(),
+    );
+    sys
+}
+
+fn process_start_time(pid: u32) -> Option<i64> {
+    refresh_process(pid)
+        .process(Pid::from(pid as usize))
+        .map(|process| process.start_time() as i64)
+}
+
+/// Whether the process which took `lease` on this machine is still running
+fn is_process_alive(lease: &StoredLease) -> bool {
+    debug!("Checking if {} exists", lease.pid);
+    let sys = refresh_process(lease.pid);
+    let Some(process_info) = sys.process(Pid::from(lease.pid as usize)) else {
+        return false;
+    };
+
+    // A recycled pid belongs to a process which started later
+    if let Some(process_started_at) = lease.process_started_at {
+        return process_info.start_time() as i64 == process_started_at;
+    }
+
+    let cmd_str = process_info
+        .cmd()
+        .iter()
+        .map(|s| s.to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if let Some(cwd_path) = process_info.cwd() {
+        let cwd_str = cwd_path.to_normalized_string();
+        cmd_str == lease.command && cwd_str == lease.cwd
+    } else {
+        cmd_str == lease.command
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,4 +358,47 @@ mod tests {
             dbg!("Process {} is not running", pid);
         }
     }
+
+    fn lease(pid: u32, machine_id: &str) -> StoredLease {
+        StoredLease {
+            pid,
+            command: String::new(),
+            cwd: String::new(),
+            machine_id: Some(machine_id.to_string()),
+            process_started_at: process_start_time(pid),
+            started_at: Some(0),
+            heartbeat_at: Some(0),
+        }
+    }
+
+    #[test]
+    fn should_consider_leases_of_running_processes_alive() {
+        let owner = LeaseOwner::current();
+        let lease = lease(owner.pid, &owner.machine_id);
+
+        assert!(owner.owns(&lease));
+        assert!(owner.is_alive(&lease, now()));
+    }
+
+    #[test]
+    fn should_consider_leases_of_recycled_pids_stale() {
+        let owner = LeaseOwner::current();
+        let mut lease = lease(owner.pid, &owner.machine_id);
+        lease.process_started_at = lease.process_started_at.map(|started_at| started_at - 60);
+
+        assert!(!owner.is_alive(&lease, now()));
+    }
+
+    #[test]
+    fn should_expire_leases_from_other_machines_without_heartbeat() {
+        let owner = LeaseOwner::current();
+        let mut lease = lease(owner.pid, "other-machine");
+        let now = now();
+
+        assert!(!owner.owns(&lease));
+        lease.heartbeat_at = Some(now - 1_000);
+        assert!(owner.is_alive(&lease, now));
+        lease.heartbeat_at = Some(now - LEASE_TIMEOUT);
+        assert!(!owner.is_alive(&lease, now));
+    }
 }
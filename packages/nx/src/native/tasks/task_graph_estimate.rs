@@ -0,0 +1,418 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::native::tasks::types::TaskGraph;
+
+#[napi(object)]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskDurationEstimate {
+    pub task_id: String,
+    /// Median duration, in milliseconds, of the runs which were executed
+    pub p50: Option<f64>,
+    /// 90th percentile duration, in milliseconds, of the runs which were executed
+    pub p90: Option<f64>,
+    /// Median duration, in milliseconds, of the runs which were restored from the cache
+    pub cache_hit_p50: Option<f64>,
+    /// Whether the task is expected to be restored from the cache, because its hash succeeded before
+    pub expect_cache_hit: bool,
+    /// The duration used for the estimate. Tasks without any history are assumed to take
+    /// as long as the median task of the graph.
+    pub duration: f64,
+}
+
+#[napi(object)]
+#[derive(Debug, PartialEq)]
+pub struct TaskGraphEstimate {
+    pub tasks: HashMap<String, TaskDurationEstimate>,
+    /// The chain of dependent tasks which takes the longest, from first to last
+    pub critical_path: Vec<String>,
+    /// How long the critical path takes, in milliseconds. No parallelism can be faster.
+    pub critical_path_duration: f64,
+    /// How long the whole graph takes, in milliseconds, at the given parallelism
+    pub eta: f64,
+}
+
+/// The durations of the runs of a target, in milliseconds
+#[derive(Default)]
+pub(super) struct TargetDurations {
+    pub executed: Vec<f64>,
+    pub cache_hits: Vec<f64>,
+}
+
+/// The value below which `p` percent of `durations` fall, using the nearest rank
+pub(super) fn percentile(durations: &mut [f64], p: f64) -> Option<f64> {
+    if durations.is_empty() {
+        return None;
+    }
+    durations.sort_by(f64::total_cmp);
+    let rank = ((p / 100.0) * durations.len() as f64).ceil() as usize;
+    Some(durations[rank.clamp(1, durations.len()) - 1])
+}
+
+pub(super) fn estimate_task_durations(
+    task_graph: &TaskGraph,
+    mut durations: HashMap<String, TargetDurations>,
+    cached_tasks: &HashSet<String>,
+) -> HashMap<String, TaskDurationEstimate> {
+    let mut estimates = task_graph
+        .tasks
+        .keys()
+        .map(|task_id| {
+            let durations = durations.entry(task_id.clone()).or_default();
+            let p50 = percentile(&mut durations.executed, 50.0);
+            let p90 = percentile(&mut durations.executed, 90.0);
+            let cache_hit_p50 = percentile(&mut durations.cache_hits, 50.0);
+            let expect_cache_hit = cached_tasks.contains(task_id);
+            let duration = if expect_cache_hit {
+                cache_hit_p50.or(p50)
+            } else {
+                p50.or(cache_hit_p50)
+            };
+            (
+                task_id.clone(),
+                TaskDurationEstimate {
+                    task_id: task_id.clone(),
+                    p50,
+                    p90,
+                    cache_hit_p50,
+                    expect_cache_hit,
+                    duration: duration.unwrap_or(f64::NAN),
+                },
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut known = estimates
+        .values()
+        .map(|estimate| estimate.duration)
+        .filter(|duration| !duration.is_nan())
+        .collect::<Vec<_>>();
+    let fallback = percentile(&mut known, 50.0).unwrap_or(0.0);
+    for estimate in estimates.values_mut() {
+        if estimate.duration.is_nan() {
+            estimate.duration = fallback;
+        }
+    }
+    estimates
+}
+
+/// Finds the chain of dependencies which takes the longest to complete
+pub(super) fn critical_path(
+    task_graph: &TaskGraph,
+    durations: &HashMap<String, f64>,
+) -> (Vec<String>, f64) {
+    let finish_times = finish_times(task_graph, durations);
+
+    let Some((&last, &duration)) = finish_times
+        .iter()
+        .max_by(|(a_id, a), (b_id, b)| a.total_cmp(b).then_with(|| b_id.cmp(a_id)))
+    else {
+        return (vec![], 0.0);
+    };
+
+    let mut task_id = last;
+
+    let mut path = vec![last.to_string()];
+    while let Some(dependency) = task_graph
+        .dependencies
+        .get(task_id)
+        .into_iter()
+        .flatten()
+        .filter(|dependency| finish_times.contains_key(dependency.as_str()))
+        .max_by(|a, b| finish_times[a.as_str()].total_cmp(&finish_times[b.as_str()]))
+    {
+        path.push(dependency.clone());
+        task_id = dependency.as_str();
+    }
+    path.reverse();
+
+    (path, duration)
+}
+
+/// The earliest time every task can finish with unlimited parallelism
+fn finish_times<'a>(
+    task_graph: &'a TaskGraph,
+    durations: &HashMap<String, f64>,
+) -> HashMap<&'a str, f64> {
+    let mut finish_times: HashMap<&str, f64> = HashMap::new();
+    for task_id in topological_order(task_graph) {
+        let start = task_graph
+            .dependencies
+            .get(task_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|dependency| finish_times.get(dependency.as_str()))
+            .fold(0.0, |a: f64, b| a.max(*b));
+        finish_times.insert(task_id, start + durations.get(task_id).unwrap_or(&0.0));
+    }
+    finish_times
+}
+
+/// Simulates running the graph with `parallelism` workers. Tasks become ready once their
+/// dependencies completed and ready tasks on the longest remaining path start first.
+pub(super) fn simulate_eta(
+    task_graph: &TaskGraph,
+    durations: &HashMap<String, f64>,
+    parallelism: usize,
+) -> f64 {
+    let order = topological_order(task_graph);
+    let priorities = remaining_path_durations(task_graph, durations, &order);
+
+    let mut pending_dependencies = order
+        .iter()
+        .map(|&task_id| {
+            let count = task_graph
+                .dependencies
+                .get(task_id)
+                .into_iter()
+                .flatten()
+                .filter(|dependency| task_graph.tasks.contains_key(dependency.as_str()))
+                .count();
+            (task_id, count)
+        })
+        .collect::<HashMap<_, _>>();
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for &task_id in order.iter() {
+        for dependency in task_graph.dependencies.get(task_id).into_iter().flatten() {
+            dependents
+                .entry(dependency.as_str())
+                .or_default()
+                .push(task_id);
+        }
+    }
+
+    let mut ready = order
+        .iter()
+        .copied()
+        .filter(|task_id| pending_dependencies[task_id] == 0)
+        .collect::<Vec<_>>();
+    let mut running: Vec<(&str, f64)> = vec![];
+    let mut now = 0.0;
+
+    loop {
+        ready.sort_by(|a, b| {
+            priorities[a]
+                .total_cmp(&priorities[b])
+                .then_with(|| b.cmp(a))
+        });
+        while running.len() < parallelism.max(1) {
+            let Some(task_id) = ready.pop() else {
+                break;
+            };
+            running.push((task_id, now + durations.get(task_id).unwrap_or(&0.0)));
+        }
+
+        let Some(next) = running
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+        else {
+            break;
+        };
+        let (task_id, finished_at) = running.swap_remove(next);
+        now = finished_at;
+        for &dependent in dependents.get(task_id).into_iter().flatten() {
+            if let Some(count) = pending_dependencies.get_mut(dependent) {
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(dependent);
+                }
+            }
+        }
+    }
+
+    now
+}
+
+/// How long it takes from the start of every task until the graph completes
+fn remaining_path_durations<'a>(
+    task_graph: &'a TaskGraph,
+    durations: &HashMap<String, f64>,
+    order: &[&'a str],
+) -> HashMap<&'a str, f64> {
+    let mut remaining: HashMap<&str, f64> = HashMap::new();
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for &task_id in order {
+        for dependency in task_graph.dependencies.get(task_id).into_iter().flatten() {
+            dependents
+                .entry(dependency.as_str())
+                .or_default()
+                .push(task_id);
+        }
+    }
+    for &task_id in order.iter().rev() {
+        let after = dependents
+            .get(task_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|dependent| remaining.get(dependent))
+            .fold(0.0, |a: f64, b| a.max(*b));
+        remaining.insert(task_id, after + durations.get(task_id).unwrap_or(&0.0));
+    }
+    remaining
+}
+
+/// Orders the tasks so every task comes after its dependencies. Tasks in a cycle are left out.
+fn topological_order(task_graph: &TaskGraph) -> Vec<&str> {
+    let mut pending_dependencies = task_graph
+        .tasks
+        .keys()
+        .map(|task_id| {
+            let count = task_graph
+                .dependencies
+                .get(task_id)
+                .into_iter()
+                .flatten()
+                .filter(|dependency| task_graph.tasks.contains_key(dependency.as_str()))
+                .count();
+            (task_id.as_str(), count)
+        })
+        .collect::<HashMap<_, _>>();
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (task_id, dependencies) in task_graph.dependencies.iter() {
+        if !task_graph.tasks.contains_key(task_id) {
+            continue;
+        }
+        for dependency in dependencies {
+            dependents
+                .entry(dependency.as_str())
+                .or_default()
+                .push(task_id.as_str());
+        }
+    }
+
+    let mut queue = pending_dependencies
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(task_id, _)| *task_id)
+        .collect::<Vec<_>>();
+    queue.sort_unstable();
+    let mut order = Vec::with_capacity(task_graph.tasks.len());
+    while let Some(task_id) = queue.pop() {
+        order.push(task_id);
+        for &dependent in dependents.get(task_id).into_iter().flatten() {
+            if let Some(count) = pending_dependencies.get_mut(dependent) {
+                *count -= 1;
+                if *count == 0 {
+                    queue.push(dependent);
+                }
+            }
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::native::tasks::types::Task;
+
+    fn task_graph(dependencies: &[(&str, &[&str])]) -> TaskGraph {
+        let tasks = dependencies
+            .iter()
+            .map(|(id, _)| {
+                (
+                    id.to_string(),
+                    Task {
+                        id: id.to_string(),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        TaskGraph {
+            roots: vec![],
+            tasks,
+            dependencies: dependencies
+                .iter()
+                .map(|(id, deps)| {
+                    (
+                        id.to_string(),
+                        deps.iter().map(|dep| dep.to_string()).collect(),
+                    )
+                })
+                .collect(),
+            continuous_dependencies: HashMap::new(),
+        }
+    }
+
+    fn durations(durations: &[(&str, f64)]) -> HashMap<String, f64> {
+        durations
+            .iter()
+            .map(|(id, duration)| (id.to_string(), *duration))
+            .collect()
+    }
+
+    #[test]
+    fn should_use_nearest_rank_percentiles() {
+        let mut durations = vec![5.0, 1.0, 4.0, 2.0, 3.0, 100.0, 6.0, 7.0, 8.0, 9.0];
+        assert_eq!(percentile(&mut durations, 50.0), Some(5.0));
+        assert_eq!(percentile(&mut durations, 90.0), Some(9.0));
+        assert_eq!(percentile(&mut [], 50.0), None);
+    }
+
+    #[test]
+    fn should_find_the_critical_path() {
+        let graph = task_graph(&[
+            ("app:build", &["lib-a:build", "lib-b:build"]),
+            ("lib-a:build", &["lib-c:build"]),
+            ("lib-b:build", &[]),
+            ("lib-c:build", &[]),
+        ]);
+        let durations = durations(&[
+            ("app:build", 10.0),
+            ("lib-a:build", 5.0),
+            ("lib-b:build", 30.0),
+            ("lib-c:build", 20.0),
+        ]);
+
+        assert_eq!(
+            critical_path(&graph, &durations),
+            (
+                vec!["lib-b:build".to_string(), "app:build".to_string()],
+                40.0
+            )
+        );
+        assert_eq!(simulate_eta(&graph, &durations, 3), 40.0);
+        // lib-b and lib-c start first, then lib-a, then app
+        assert_eq!(simulate_eta(&graph, &durations, 1), 65.0);
+    }
+
+    #[test]
+    fn should_start_tasks_on_the_longest_path_first() {
+        let graph = task_graph(&[("a", &[]), ("b", &[]), ("c", &["b"])]);
+        let durations = durations(&[("a", 10.0), ("b", 5.0), ("c", 10.0)]);
+
+        // b and c are the longest path, so a runs alongside them on the second worker
+        assert_eq!(simulate_eta(&graph, &durations, 2), 15.0);
+    }
+
+    #[test]
+    fn should_prefer_cache_hit_durations_for_cached_tasks() {
+        let graph = task_graph(&[("a", &[]), ("b", &[]), ("c", &[])]);
+        let history = HashMap::from([
+            (
+                "a".to_string(),
+                TargetDurations {
+                    executed: vec![100.0, 120.0],
+                    cache_hits: vec![2.0],
+                },
+            ),
+            (
+                "b".to_string(),
+                TargetDurations {
+                    executed: vec![50.0],
+                    cache_hits: vec![],
+                },
+            ),
+        ]);
+
+        let estimates = estimate_task_durations(&graph, history, &HashSet::from(["a".to_string()]));
+
+        assert_eq!(estimates["a"].duration, 2.0);
+        assert_eq!(estimates["a"].p90, Some(120.0));
+        assert_eq!(estimates["b"].duration, 50.0);
+        // Tasks without history take as long as the median task
+        assert_eq!(estimates["c"].duration, 2.0);
+    }
+}
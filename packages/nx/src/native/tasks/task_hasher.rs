@@ -1,26 +1,32 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::native::{
-    hasher::hash,
+    hasher::{HashAlgorithm, Hasher, hash},
     project_graph::{types::ProjectGraph, utils::create_project_root_mappings},
     tasks::types::HashInstruction,
     types::NapiDashMap,
 };
 use crate::native::{
     project_graph::utils::ProjectRootMappings,
-    tasks::hashers::{hash_env, hash_runtime, hash_workspace_files},
+    tasks::hashers::{
+        DEFAULT_RUNTIME_TIMEOUT, RuntimeCache, RuntimeHashOptions, hash_env, hash_env_pattern,
+        hash_runtime, hash_workspace_files,
+    },
 };
 use crate::native::{
     tasks::hashers::{
+        collect_project_files, get_workspace_file_set_roots, get_workspace_files,
         hash_all_externals, hash_external, hash_project_config, hash_project_files,
-        hash_task_output, hash_tsconfig_selectively,
+        hash_task_output, hash_tsconfig_selectively, read_output_manifest,
     },
     types::FileData,
     workspace::types::ProjectFiles,
 };
 use anyhow::anyhow;
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use napi::bindgen_prelude::*;
 use rayon::prelude::*;
 use tracing::{debug, trace, trace_span};
@@ -32,9 +38,27 @@ pub struct HashDetails {
     pub details: HashMap<String, String>,
 }
 
+/// The files hashed by file set instructions, keyed by the hash of the instruction.
+/// Every file is mapped to its content hash.
+pub type FileSets = HashMap<String, BTreeMap<String, String>>;
+
+/// The hashes of file set instructions, keyed by a fingerprint of everything they depend on
+pub type InstructionHashes = HashMap<String, String>;
+
 #[napi(object)]
 pub struct HasherOptions {
     pub selectively_hash_ts_config: bool,
+    /// Milliseconds a runtime input may run before hashing fails, defaults to 60 seconds
+    pub runtime_timeout: Option<u32>,
+    /// Only hash what runtime inputs print to stdout
+    pub runtime_hash_stdout_only: Option<bool>,
+    /// Seconds the result of a runtime input is reused for
+    pub runtime_cache_ttl: Option<u32>,
+    /// Where results of runtime inputs are persisted, requires `runtimeCacheTtl`
+    pub runtime_cache_directory: Option<String>,
+    /// The cache whose output manifests are used to hash the outputs of dependent tasks,
+    /// so those outputs do not have to be restored first
+    pub cache_directory: Option<String>,
 }
 
 #[napi]
@@ -48,7 +72,14 @@ pub struct TaskHasher {
     options: Option<HasherOptions>,
     workspace_files_cache: Arc<DashMap<String, String>>,
     external_cache: Arc<DashMap<String, String>>,
-    runtime_cache: Arc<DashMap<String, String>>,
+    runtime_options: RuntimeHashOptions,
+    runtime_cache: RuntimeCache,
+    file_sets: DashMap<String, BTreeMap<String, String>>,
+    recorded_file_sets: DashSet<String>,
+    persisted_instruction_hashes: InstructionHashes,
+    instruction_hashes: DashMap<String, String>,
+    workspace_files_fingerprints: DashMap<Vec<String>, String>,
+    project_files_fingerprints: DashMap<String, String>,
 }
 #[napi]
 impl TaskHasher {
@@ -62,6 +93,27 @@ impl TaskHasher {
         ts_config_paths: HashMap<String, Vec<String>>,
         options: Option<HasherOptions>,
     ) -> Self {
+        let runtime_options = RuntimeHashOptions {
+            timeout: options
+                .as_ref()
+                .and_then(|o| o.runtime_timeout)
+                .map(|timeout| Duration::from_millis(timeout as u64))
+                .unwrap_or(DEFAULT_RUNTIME_TIMEOUT),
+            stdout_only: options
+                .as_ref()
+                .and_then(|o| o.runtime_hash_stdout_only)
+                .unwrap_or(false),
+        };
+        let runtime_cache = RuntimeCache::new(
+            options
+                .as_ref()
+                .and_then(|o| o.runtime_cache_directory.as_ref())
+                .map(PathBuf::from),
+            options
+                .as_ref()
+                .and_then(|o| o.runtime_cache_ttl)
+                .map(|ttl| Duration::from_secs(ttl as u64)),
+        );
         Self {
             workspace_root,
             project_graph,
@@ -72,10 +124,28 @@ impl TaskHasher {
             options,
             workspace_files_cache: Arc::new(DashMap::new()),
             external_cache: Arc::new(DashMap::new()),
-            runtime_cache: Arc::new(DashMap::new()),
+            runtime_options,
+            runtime_cache,
+            file_sets: DashMap::new(),
+            recorded_file_sets: DashSet::new(),
+            persisted_instruction_hashes: HashMap::new(),
+            instruction_hashes: DashMap::new(),
+            workspace_files_fingerprints: DashMap::new(),
+            project_files_fingerprints: DashMap::new(),
         }
     }
 
+    /// Reuses the hashes of file set instructions persisted by previous runs
+    #[napi(ts_args_type = "hashes: ExternalObject<Record<string, string>>")]
+    pub fn load_instruction_hashes(&mut self, hashes: External<InstructionHashes>) {
+        trace!("Loaded {} persisted instruction hashes", hashes.len());
+        self.persisted_instruction_hashes.extend(
+            hashes
+                .iter()
+                .map(|(fingerprint, hash)| (fingerprint.clone(), hash.clone())),
+        );
+    }
+
     #[napi]
     pub fn hash_plans(
         &self,
@@ -146,13 +216,13 @@ impl TaskHasher {
             let (hash_id, hash_details) = h.pair_mut();
             let mut keys = hash_details.details.keys().collect::<Vec<_>>();
             keys.par_sort();
-            let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+            let mut hasher = Hasher::new();
             trace_span!("Assembling hash", hash_id).in_scope(|| {
                 for key in keys {
                     trace!("Adding {} ({}) to hash", hash_details.details[key], key);
                     hasher.update(hash_details.details[key].as_bytes());
                 }
-                let hash = hasher.digest().to_string();
+                let hash = hasher.digest();
                 trace!("Hash Value: {}", hash);
                 hash_details.value = hash;
             });
@@ -171,6 +241,10 @@ impl TaskHasher {
             assemble_duration
         );
 
+        if let Err(e) = self.runtime_cache.persist() {
+            debug!("Unable to persist runtime hashes: {:?}", e);
+        }
+
         Ok(hashes)
     }
 
@@ -190,11 +264,34 @@ impl TaskHasher {
         let span = trace_span!("hashing", task_id).entered();
         let hash = match instruction {
             HashInstruction::WorkspaceFileSet(workspace_file_set) => {
-                let hashed_workspace_files = hash_workspace_files(
-                    workspace_file_set,
-                    &self.all_workspace_files,
-                    Arc::clone(&self.workspace_files_cache),
-                );
+                let roots = get_workspace_file_set_roots(workspace_file_set);
+                let fingerprint = self
+                    .workspace_files_fingerprints
+                    .entry(roots.clone())
+                    .or_insert_with(|| {
+                        fingerprint_files(self.all_workspace_files.iter().filter(|file| {
+                            roots
+                                .iter()
+                                .any(|root| file.file.starts_with(root.as_str()))
+                        }))
+                    })
+                    .clone();
+                let hashed_workspace_files =
+                    self.persisted_instruction_hash(instruction, &fingerprint, || {
+                        let hashed_workspace_files = hash_workspace_files(
+                            &self.workspace_root,
+                            workspace_file_set,
+                            &self.all_workspace_files,
+                            Arc::clone(&self.workspace_files_cache),
+                        )?;
+                        self.record_file_set(&hashed_workspace_files, || {
+                            Ok(
+                                get_workspace_files(workspace_file_set, &self.all_workspace_files)?
+                                    .collect(),
+                            )
+                        })?;
+                        Ok(hashed_workspace_files)
+                    });
                 trace!(parent: &span, "hash_workspace_files: {:?}", now.elapsed());
                 hashed_workspace_files?
             }
@@ -203,7 +300,8 @@ impl TaskHasher {
                     &self.workspace_root,
                     runtime,
                     js_env,
-                    Arc::clone(&self.runtime_cache),
+                    &self.runtime_options,
+                    &self.runtime_cache,
                 )?;
                 trace!(parent: &span, "hash_runtime: {:?}", now.elapsed());
                 hashed_runtime
@@ -213,20 +311,50 @@ impl TaskHasher {
                 trace!(parent: &span, "hash_env: {:?}", now.elapsed());
                 hashed_env
             }
+            HashInstruction::EnvironmentPattern(pattern) => {
+                let hashed_env = hash_env_pattern(pattern, js_env)?;
+                trace!(parent: &span, "hash_env_pattern: {:?}", now.elapsed());
+                hashed_env
+            }
             HashInstruction::ProjectFileSet(project_name, file_sets) => {
                 let project = self
                     .project_graph
                     .nodes
                     .get(project_name)
                     .ok_or_else(|| anyhow!("project {} not found", project_name))?;
-                let hashed_project_files = hash_project_files(
-                    project_name,
-                    &project.root,
-                    file_sets,
-                    &self.project_file_map,
-                )?;
+                let fingerprint = self
+                    .project_files_fingerprints
+                    .entry(project_name.clone())
+                    .or_insert_with(|| {
+                        fingerprint_files(
+                            self.project_file_map
+                                .get(project_name)
+                                .into_iter()
+                                .flatten(),
+                        )
+                    })
+                    .clone();
+                let hashed_project_files =
+                    self.persisted_instruction_hash(instruction, &fingerprint, || {
+                        let hashed_project_files = hash_project_files(
+                            &self.workspace_root,
+                            project_name,
+                            &project.root,
+                            file_sets,
+                            &self.project_file_map,
+                        )?;
+                        self.record_file_set(&hashed_project_files, || {
+                            collect_project_files(
+                                project_name,
+                                &project.root,
+                                file_sets,
+                                &self.project_file_map,
+                            )
+                        })?;
+                        Ok(hashed_project_files)
+                    });
                 trace!(parent: &span, "hash_project_files: {:?}", now.elapsed());
-                hashed_project_files
+                hashed_project_files?
             }
             HashInstruction::ProjectConfiguration(project_name) => {
                 let hashed_project_config =
@@ -260,13 +388,23 @@ impl TaskHasher {
                 trace!(parent: &span, "hash_tsconfig: {:?}", now.elapsed());
                 ts_hash
             }
-            HashInstruction::TaskOutput(glob, outputs) => {
-                let hashed_task_output = hash_task_output(&self.workspace_root, glob, outputs)?;
+            HashInstruction::TaskOutput(glob, outputs, dep_hash) => {
+                let manifest = self
+                    .options
+                    .as_ref()
+                    .and_then(|o| o.cache_directory.as_deref())
+                    .zip(dep_hash.as_deref())
+                    .and_then(|(cache_directory, dep_hash)| {
+                        read_output_manifest(cache_directory, dep_hash)
+                    });
+                let hashed_task_output =
+                    hash_task_output(&self.workspace_root, glob, outputs, manifest.as_ref())?;
                 trace!(parent: &span, "hash_task_output: {:?}", now.elapsed());
                 hashed_task_output
             }
             HashInstruction::External(external) => {
                 let hashed_external = hash_external(
+                    &self.workspace_root,
                     external,
                     &self.project_graph.external_nodes,
                     Arc::clone(&self.external_cache),
@@ -276,6 +414,7 @@ impl TaskHasher {
             }
             HashInstruction::AllExternalDependencies => {
                 let hashed_all_externals = hash_all_externals(
+                    &self.workspace_root,
                     sorted_externals,
                     &self.project_graph.external_nodes,
                     Arc::clone(&self.external_cache),
@@ -283,9 +422,104 @@ impl TaskHasher {
                 trace!(parent: &span, "hash_all_externals: {:?}", now.elapsed());
                 hashed_all_externals
             }
+            HashInstruction::ProjectExternalDependencies(_, externals) => {
+                let hashed_externals = hash_all_externals(
+                    &self.workspace_root,
+                    externals,
+                    &self.project_graph.external_nodes,
+                    Arc::clone(&self.external_cache),
+                )?;
+                trace!(parent: &span, "hash_project_externals: {:?}", now.elapsed());
+                hashed_externals
+            }
         };
         Ok((instruction.to_string(), hash))
     }
+
+    /// Hands over the file sets hashed since the last call, so they can be persisted
+    #[napi(ts_return_type = "ExternalObject<Record<string, Record<string, string>>>")]
+    pub fn take_file_sets(&self) -> External<FileSets> {
+        let values = self
+            .file_sets
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>();
+        External::new(
+            values
+                .into_iter()
+                .filter_map(|value| self.file_sets.remove(&value))
+                .collect(),
+        )
+    }
+
+    /// Hands over the hashes of file set instructions used since the last call, so they can
+    /// be persisted for the next run
+    #[napi(ts_return_type = "ExternalObject<Record<string, string>>")]
+    pub fn take_instruction_hashes(&self) -> External<InstructionHashes> {
+        let fingerprints = self
+            .instruction_hashes
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>();
+        External::new(
+            fingerprints
+                .into_iter()
+                .filter_map(|fingerprint| self.instruction_hashes.remove(&fingerprint))
+                .collect(),
+        )
+    }
+
+    /// Reuses the persisted hash of `instruction` when none of the files it could match
+    /// changed since it was computed, otherwise computes it
+    fn persisted_instruction_hash(
+        &self,
+        instruction: &HashInstruction,
+        files_fingerprint: &str,
+        compute: impl FnOnce() -> anyhow::Result<String>,
+    ) -> anyhow::Result<String> {
+        let mut hasher = Hasher::new();
+        hasher.update(HashAlgorithm::current().name().as_bytes());
+        hasher.update(instruction.to_string().as_bytes());
+        hasher.update(files_fingerprint.as_bytes());
+        let fingerprint = hasher.digest();
+
+        let hash = match self.persisted_instruction_hashes.get(&fingerprint) {
+            Some(hash) => {
+                trace!("Reusing persisted hash of {}", instruction);
+                hash.clone()
+            }
+            None => compute()?,
+        };
+        self.instruction_hashes.insert(fingerprint, hash.clone());
+        Ok(hash)
+    }
+
+    /// Keeps the files behind a file set hash, unless they were kept before
+    fn record_file_set<'a>(
+        &self,
+        value: &str,
+        files: impl FnOnce() -> anyhow::Result<Vec<&'a FileData>>,
+    ) -> anyhow::Result<()> {
+        if !self.recorded_file_sets.insert(value.to_string()) {
+            return Ok(());
+        }
+        let files = files()?
+            .into_iter()
+            .map(|file| (file.file.clone(), file.hash.clone()))
+            .collect();
+        self.file_sets.insert(value.to_string(), files);
+        Ok(())
+    }
+}
+
+/// Fingerprints the paths and content hashes of `files`
+fn fingerprint_files<'a>(files: impl Iterator<Item = &'a FileData>) -> String {
+    let mut hasher = Hasher::new();
+    for file in files {
+        hasher.update(file.file.as_bytes());
+        hasher.update(file.hash.as_bytes());
+    }
+    hasher.digest()
 }
 
 struct HashInstructionArgs<'a> {
@@ -1,10 +1,20 @@
 use crate::native::db::connection::NxDbConnection;
-use crate::native::tasks::types::TaskTarget;
+use crate::native::db::migrations::{Migration, migrate};
+use crate::native::machine_id::get_machine_id;
+use crate::native::tasks::task_graph_estimate::{
+    TargetDurations, TaskGraphEstimate, critical_path, estimate_task_durations, simulate_eta,
+};
+use crate::native::tasks::types::{TaskGraph, TaskTarget};
 use napi::bindgen_prelude::*;
-use rusqlite::{params, types::Value};
-use std::collections::HashMap;
+use rusqlite::{
+    params,
+    types::{Value, ValueRef},
+};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::rc::Rc;
-use tracing::trace;
+use tracing::{debug, trace};
 
 #[napi(object)]
 pub struct TaskRun {
@@ -13,44 +23,155 @@ pub struct TaskRun {
     pub code: i16,
     pub start: i64,
     pub end: i64,
+    /// Where the outputs came from: `local`, `remote` or `miss` when the task was executed
+    pub cache_source: Option<String>,
+    /// The peak resident set size of the process tree, in bytes
+    pub peak_rss: Option<i64>,
+    /// The CPU time of the process tree, in milliseconds
+    pub cpu_time: Option<i64>,
+    /// Defaults to the id of the current machine
+    pub machine_id: Option<String>,
+    pub git_commit: Option<String>,
+    /// Identifies the command which ran the task, shared by every task it ran
+    pub command_id: Option<String>,
 }
 
+#[napi(object)]
+#[derive(Default)]
+pub struct FlakinessOptions {
+    /// Only runs which ended after this time, in milliseconds since the epoch, are considered
+    pub since: Option<i64>,
+    /// How many of the most recent statuses are listed, defaults to 10
+    pub recent_runs: Option<u32>,
+    /// The most retries which are ever recommended, defaults to 3
+    pub max_retries: Option<u32>,
+}
+
+#[napi(object)]
+#[derive(Debug, PartialEq)]
+pub struct TaskFlakiness {
+    pub target: TaskTarget,
+    /// The number of runs which were executed rather than read from the cache
+    pub runs: u32,
+    /// Failed runs of hashes which also succeeded, so the same inputs both passed and failed
+    pub flaky_failures: u32,
+    /// `flakyFailures` divided by `runs`
+    pub flake_rate: f64,
+    /// How many of the most recent runs failed in a row
+    pub consecutive_failures: u32,
+    /// The statuses of the most recent runs, newest first
+    pub recent_statuses: Vec<String>,
+    /// The retries needed for a flaky failure to pass with 99% certainty
+    pub recommended_retries: u32,
+}
+
+/// How many of the most recent runs of a target its durations are estimated from
+const DURATION_HISTORY_RUNS: u32 = 100;
+
+/// The chance that every attempt of a flaky task fails, which retries are recommended to stay below
+const RETRY_FAILURE_TOLERANCE: f64 = 0.01;
+
+#[napi(object)]
+#[derive(Default)]
+pub struct TaskHistoryRetention {
+    /// Runs which ended more than this many days ago are rolled up into daily aggregates
+    pub max_age_days: Option<u32>,
+    /// Only this many of the most recent runs of each target are kept, older runs are
+    /// rolled up into daily aggregates
+    pub max_runs_per_target: Option<u32>,
+}
+
+#[napi(object)]
+pub struct TaskHistoryCompaction {
+    /// Runs which were rolled up into daily aggregates and removed
+    pub compacted_runs: u32,
+    /// Hashes whose instruction details were removed, since none of their runs remain
+    pub removed_hash_details: u32,
+}
+
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskHistoryExportFormat {
+    JsonLines,
+    Csv,
+}
+
+/// Selects the runs which are beyond the retention settings. Runs without task details cannot
+/// be rolled up into the aggregates of their target, so they are never selected.
+const EXPIRED_RUNS: &str = "
+    expired_runs AS (
+        SELECT id FROM (
+            SELECT task_history.id, task_history.end,
+                ROW_NUMBER() OVER (
+                    PARTITION BY task_details.project, task_details.target, task_details.configuration
+                    ORDER BY task_history.end DESC, task_history.id DESC
+                ) AS position
+            FROM task_history
+                JOIN task_details ON task_history.hash = task_details.hash
+        )
+        WHERE end < ?1 OR position > ?2
+    )";
+
 #[napi]
 pub struct NxTaskHistory {
     db: External<NxDbConnection>,
+    machine_id: String,
 }
 
 #[napi]
 impl NxTaskHistory {
     #[napi(constructor)]
     pub fn new(db: External<NxDbConnection>) -> anyhow::Result<Self> {
-        let s = Self { db };
+        let mut s = Self {
+            db,
+            machine_id: get_machine_id(),
+        };
 
         s.setup()?;
 
         Ok(s)
     }
 
-    fn setup(&self) -> anyhow::Result<()> {
-        self.db
-            .execute_batch(
-                "
-            BEGIN IMMEDIATE;
-            CREATE TABLE IF NOT EXISTS task_history (
-                id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
-                hash TEXT NOT NULL,
-                status TEXT NOT NULL,
-                code INTEGER NOT NULL,
-                start TIMESTAMP NOT NULL,
-                end TIMESTAMP NOT NULL,
-                FOREIGN KEY (hash) REFERENCES task_details (hash)
-            );
-            CREATE INDEX IF NOT EXISTS hash_idx ON task_history (hash);
-            CREATE INDEX IF NOT EXISTS status_idx ON task_history (status);
-            COMMIT;
-            ",
-            )
-            .map_err(anyhow::Error::from)
+    fn setup(&mut self) -> anyhow::Result<()> {
+        migrate(
+            &mut self.db,
+            "task_history",
+            &[Migration {
+                version: 1,
+                description: "Create task history tables",
+                sql: "
+                CREATE TABLE IF NOT EXISTS task_history (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+                    hash TEXT NOT NULL,
+                    status TEXT NOT NULL,
+                    code INTEGER NOT NULL,
+                    start TIMESTAMP NOT NULL,
+                    end TIMESTAMP NOT NULL,
+                    cache_source TEXT,
+                    peak_rss INTEGER,
+                    cpu_time INTEGER,
+                    machine_id TEXT,
+                    git_commit TEXT,
+                    command_id TEXT,
+                    FOREIGN KEY (hash) REFERENCES task_details (hash)
+                );
+                CREATE INDEX IF NOT EXISTS hash_idx ON task_history (hash);
+                CREATE INDEX IF NOT EXISTS status_idx ON task_history (status);
+                CREATE TABLE IF NOT EXISTS task_history_daily (
+                    project TEXT NOT NULL,
+                    target TEXT NOT NULL,
+                    configuration TEXT NOT NULL DEFAULT '',
+                    day TEXT NOT NULL,
+                    status TEXT NOT NULL,
+                    runs INTEGER NOT NULL,
+                    total_duration INTEGER NOT NULL,
+                    min_duration INTEGER NOT NULL,
+                    max_duration INTEGER NOT NULL,
+                    PRIMARY KEY (project, target, configuration, day, status)
+                );
+                ",
+            }],
+        )
     }
 
     #[napi]
@@ -59,8 +180,8 @@ impl NxTaskHistory {
         self.db.transaction(|conn| {
             let mut stmt = conn.prepare(
                 "INSERT OR REPLACE INTO task_history
-        (hash, status, code, start, end)
-        VALUES (?1, ?2, ?3, ?4, ?5)",
+        (hash, status, code, start, end, cache_source, peak_rss, cpu_time, machine_id, git_commit, command_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
             )?;
             for task_run in task_runs.iter() {
                 stmt.execute(params![
@@ -68,7 +189,13 @@ impl NxTaskHistory {
                     task_run.status,
                     task_run.code,
                     task_run.start,
-                    task_run.end
+                    task_run.end,
+                    task_run.cache_source,
+                    task_run.peak_rss,
+                    task_run.cpu_time,
+                    task_run.machine_id.as_deref().unwrap_or(&self.machine_id),
+                    task_run.git_commit,
+                    task_run.command_id
                 ])
                 .inspect_err(|e| trace!("Error trying to insert {:?}: {:?}", &task_run.hash, e))?;
             }
@@ -77,6 +204,146 @@ impl NxTaskHistory {
         Ok(())
     }
 
+    /// Rolls the runs beyond `retention` up into daily aggregates per target and status,
+    /// then removes them along with the instruction details of hashes which have no runs left
+    #[napi]
+    pub fn compact_task_history(
+        &mut self,
+        retention: TaskHistoryRetention,
+    ) -> anyhow::Result<TaskHistoryCompaction> {
+        let cutoff = retention
+            .max_age_days
+            .map(|days| {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|now| now.as_millis() as i64 - days as i64 * 24 * 60 * 60 * 1000)
+                    .unwrap_or(i64::MIN)
+            })
+            .unwrap_or(i64::MIN);
+        let max_runs = retention
+            .max_runs_per_target
+            .map(i64::from)
+            .unwrap_or(i64::MAX);
+
+        let (compacted_runs, removed_hash_details) = self.db.transaction(|conn| {
+            conn.execute(
+                &format!(
+                    "WITH {EXPIRED_RUNS}
+                    INSERT INTO task_history_daily
+                        (project, target, configuration, day, status, runs, total_duration, min_duration, max_duration)
+                    SELECT task_details.project, task_details.target, COALESCE(task_details.configuration, ''),
+                        date(task_history.end / 1000, 'unixepoch'), task_history.status, COUNT(*),
+                        SUM(task_history.end - task_history.start),
+                        MIN(task_history.end - task_history.start),
+                        MAX(task_history.end - task_history.start)
+                    FROM task_history
+                        JOIN task_details ON task_history.hash = task_details.hash
+                    WHERE task_history.id IN (SELECT id FROM expired_runs)
+                    GROUP BY 1, 2, 3, 4, 5
+                    ON CONFLICT (project, target, configuration, day, status) DO UPDATE SET
+                        runs = runs + excluded.runs,
+                        total_duration = total_duration + excluded.total_duration,
+                        min_duration = MIN(min_duration, excluded.min_duration),
+                        max_duration = MAX(max_duration, excluded.max_duration)"
+                ),
+                params![cutoff, max_runs],
+            )?;
+            let removed_hash_details = conn.execute(
+                &format!(
+                    "WITH {EXPIRED_RUNS}
+                    DELETE FROM task_hash_details
+                    WHERE hash IN (
+                        SELECT hash FROM task_history WHERE id IN (SELECT id FROM expired_runs)
+                    ) AND hash NOT IN (
+                        SELECT hash FROM task_history WHERE id NOT IN (SELECT id FROM expired_runs)
+                    )"
+                ),
+                params![cutoff, max_runs],
+            )?;
+            let compacted_runs = conn.execute(
+                &format!(
+                    "WITH {EXPIRED_RUNS}
+                    DELETE FROM task_history WHERE id IN (SELECT id FROM expired_runs)"
+                ),
+                params![cutoff, max_runs],
+            )?;
+            Ok((compacted_runs, removed_hash_details))
+        })?;
+
+        debug!(
+            "Compacted {} task runs and removed the details of {} hashes",
+            compacted_runs, removed_hash_details
+        );
+        Ok(TaskHistoryCompaction {
+            compacted_runs: compacted_runs as u32,
+            removed_hash_details: removed_hash_details as u32,
+        })
+    }
+
+    /// Writes every run, or with `daily` the aggregates of compacted runs, to `path`.
+    /// Returns how many rows were written.
+    #[napi]
+    pub fn export_task_history(
+        &self,
+        path: String,
+        format: TaskHistoryExportFormat,
+        daily: Option<bool>,
+    ) -> anyhow::Result<u32> {
+        let query = if daily.unwrap_or(false) {
+            "SELECT project, target, NULLIF(configuration, '') AS configuration, day, status,
+                runs, total_duration, min_duration, max_duration
+                FROM task_history_daily
+                ORDER BY day, project, target, configuration, status"
+        } else {
+            "SELECT task_history.hash, task_details.project, task_details.target,
+                task_details.configuration, task_history.status, task_history.code,
+                task_history.start, task_history.end, task_history.cache_source,
+                task_history.peak_rss, task_history.cpu_time, task_history.machine_id,
+                task_history.git_commit, task_history.command_id
+                FROM task_history
+                    LEFT JOIN task_details ON task_history.hash = task_details.hash
+                ORDER BY task_history.end, task_history.id"
+        };
+        let mut stmt = self.db.prepare(query)?;
+        let columns = stmt
+            .column_names()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        let mut writer = BufWriter::new(File::create(&path)?);
+        if format == TaskHistoryExportFormat::Csv {
+            writeln!(writer, "{}", columns.join(","))?;
+        }
+
+        let mut rows = stmt.query([])?;
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            let values = (0..columns.len())
+                .map(|i| row.get_ref(i).map(json_value))
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            match format {
+                TaskHistoryExportFormat::JsonLines => {
+                    let object = columns
+                        .iter()
+                        .cloned()
+                        .zip(values)
+                        .collect::<serde_json::Map<_, _>>();
+                    writeln!(writer, "{}", serde_json::Value::Object(object))?;
+                }
+                TaskHistoryExportFormat::Csv => {
+                    let fields = values.iter().map(csv_field).collect::<Vec<_>>();
+                    writeln!(writer, "{}", fields.join(","))?;
+                }
+            }
+            count += 1;
+        }
+        writer.flush()?;
+
+        trace!("Exported {} task history rows to {}", count, path);
+        Ok(count)
+    }
+
     #[napi]
     pub fn get_flaky_tasks(&self, hashes: Vec<String>) -> anyhow::Result<Vec<String>> {
         let values = Rc::new(
@@ -99,6 +366,127 @@ impl NxTaskHistory {
             .collect()
     }
 
+    /// Scores how flaky every target has been based on its executed runs
+    #[napi]
+    pub fn get_task_flakiness(
+        &self,
+        targets: Vec<TaskTarget>,
+        options: Option<FlakinessOptions>,
+    ) -> anyhow::Result<Vec<TaskFlakiness>> {
+        let options = options.unwrap_or_default();
+        let mut stmt = self.db.prepare(
+            "SELECT task_history.hash, task_history.status, task_history.code
+                FROM task_history
+                    JOIN task_details ON task_history.hash = task_details.hash
+                WHERE task_details.project = ?1 AND task_details.target = ?2
+                    AND task_details.configuration IS ?3
+                    AND task_history.status IN ('success', 'failure')
+                    AND task_history.end >= ?4
+                ORDER BY task_history.end DESC, task_history.id DESC",
+        )?;
+
+        targets
+            .into_iter()
+            .map(|target| {
+                let runs = stmt
+                    .query_map(
+                        params![
+                            target.project,
+                            target.target,
+                            target.configuration,
+                            options.since.unwrap_or(0)
+                        ],
+                        |row| {
+                            Ok(ExecutedRun {
+                                hash: row.get(0)?,
+                                status: row.get(1)?,
+                                code: row.get(2)?,
+                            })
+                        },
+                    )?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok::<_, anyhow::Error>(score_flakiness(target, &runs, &options))
+            })
+            .collect()
+    }
+
+    /// Predicts the critical path of `task_graph` and how long it takes to run with
+    /// `parallelism` workers, which defaults to 3. Tasks whose hash already succeeded are
+    /// expected to be restored from the cache and are estimated with the durations of cache hits.
+    #[napi]
+    pub fn get_task_graph_estimate(
+        &self,
+        task_graph: TaskGraph,
+        parallelism: Option<u32>,
+    ) -> anyhow::Result<TaskGraphEstimate> {
+        let mut durations_stmt = self.db.prepare(
+            "SELECT task_history.status, task_history.end - task_history.start
+                FROM task_history
+                    JOIN task_details ON task_history.hash = task_details.hash
+                WHERE task_details.project = ?1 AND task_details.target = ?2
+                    AND task_details.configuration IS ?3
+                    AND task_history.code = 0
+                ORDER BY task_history.end DESC
+                LIMIT ?4",
+        )?;
+        let mut cached_stmt = self
+            .db
+            .prepare("SELECT EXISTS (SELECT 1 FROM task_history WHERE hash = ?1 AND code = 0)")?;
+
+        let mut durations = HashMap::new();
+        let mut cached_tasks = HashSet::new();
+        for (task_id, task) in task_graph.tasks.iter() {
+            let mut target_durations = TargetDurations::default();
+            let runs = durations_stmt.query_map(
+                params![
+                    task.target.project,
+                    task.target.target,
+                    task.target.configuration,
+                    DURATION_HISTORY_RUNS
+                ],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)),
+            )?;
+            for run in runs {
+                let (status, duration) = run?;
+                match status.as_str() {
+                    "success" => target_durations.executed.push(duration),
+                    "local-cache" | "local-cache-kept-existing" | "remote-cache" => {
+                        target_durations.cache_hits.push(duration)
+                    }
+                    _ => {}
+                }
+            }
+            durations.insert(task_id.clone(), target_durations);
+
+            let cached = match &task.hash {
+                Some(hash) => cached_stmt.query_row(params![hash], |row| row.get::<_, bool>(0))?,
+                None => false,
+            };
+            if cached {
+                cached_tasks.insert(task_id.clone());
+            }
+        }
+
+        let tasks = estimate_task_durations(&task_graph, durations, &cached_tasks);
+        let task_durations = tasks
+            .iter()
+            .map(|(task_id, estimate)| (task_id.clone(), estimate.duration))
+            .collect::<HashMap<_, _>>();
+        let (critical_path, critical_path_duration) = critical_path(&task_graph, &task_durations);
+        let eta = simulate_eta(
+            &task_graph,
+            &task_durations,
+            parallelism.unwrap_or(3) as usize,
+        );
+
+        Ok(TaskGraphEstimate {
+            tasks,
+            critical_path,
+            critical_path_duration,
+            eta,
+        })
+    }
+
     #[napi]
     pub fn get_estimated_task_timings(
         &self,
@@ -140,3 +528,149 @@ impl NxTaskHistory {
             .collect()
     }
 }
+
+fn json_value(value: ValueRef) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => f.into(),
+        ValueRef::Text(t) | ValueRef::Blob(t) => String::from_utf8_lossy(t).into(),
+    }
+}
+
+/// Formats `value` as a CSV field, quoting it when it contains a separator, quote or newline
+fn csv_field(value: &serde_json::Value) -> String {
+    let field = match value {
+        serde_json::Value::Null => return String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+struct ExecutedRun {
+    hash: String,
+    status: String,
+    code: i16,
+}
+
+/// Scores `runs`, which are ordered from newest to oldest
+fn score_flakiness(
+    target: TaskTarget,
+    runs: &[ExecutedRun],
+    options: &FlakinessOptions,
+) -> TaskFlakiness {
+    let succeeded_hashes = runs
+        .iter()
+        .filter(|run| run.code == 0)
+        .map(|run| run.hash.as_str())
+        .collect::<HashSet<_>>();
+    let flaky_failures = runs
+        .iter()
+        .filter(|run| run.code != 0 && succeeded_hashes.contains(run.hash.as_str()))
+        .count() as u32;
+    let flake_rate = if runs.is_empty() {
+        0.0
+    } else {
+        flaky_failures as f64 / runs.len() as f64
+    };
+
+    let max_retries = options.max_retries.unwrap_or(3);
+    let recommended_retries = if flake_rate == 0.0 {
+        0
+    } else if flake_rate >= 1.0 {
+        max_retries
+    } else {
+        // Every attempt has to fail for the task to fail: flake_rate ^ attempts < tolerance
+        let attempts = (RETRY_FAILURE_TOLERANCE.ln() / flake_rate.ln()).ceil() as u32;
+        attempts.saturating_sub(1).min(max_retries)
+    };
+
+    TaskFlakiness {
+        target,
+        runs: runs.len() as u32,
+        flaky_failures,
+        flake_rate,
+        consecutive_failures: runs.iter().take_while(|run| run.code != 0).count() as u32,
+        recent_statuses: runs
+            .iter()
+            .take(options.recent_runs.unwrap_or(10) as usize)
+            .map(|run| run.status.clone())
+            .collect(),
+        recommended_retries,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(hash: &str, code: i16) -> ExecutedRun {
+        ExecutedRun {
+            hash: hash.to_string(),
+            status: if code == 0 { "success" } else { "failure" }.to_string(),
+            code,
+        }
+    }
+
+    #[test]
+    fn should_score_flaky_failures() {
+        let runs = [
+            run("c", 1),
+            run("c", 1),
+            run("b", 0),
+            run("b", 1),
+            run("a", 1),
+            run("a", 0),
+            run("a", 0),
+            run("a", 0),
+            run("a", 0),
+            run("a", 0),
+        ];
+        let flakiness = score_flakiness(
+            TaskTarget::default(),
+            &runs,
+            &FlakinessOptions {
+                recent_runs: Some(3),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(flakiness.runs, 10);
+        // Only failures of hashes which also succeeded are flaky
+        assert_eq!(flakiness.flaky_failures, 2);
+        assert_eq!(flakiness.flake_rate, 0.2);
+        assert_eq!(flakiness.consecutive_failures, 2);
+        assert_eq!(
+            flakiness.recent_statuses,
+            vec!["failure", "failure", "success"]
+        );
+        // 0.2 ^ 3 < 0.01
+        assert_eq!(flakiness.recommended_retries, 2);
+    }
+
+    #[test]
+    fn should_quote_csv_fields() {
+        assert_eq!(csv_field(&serde_json::Value::Null), "");
+        assert_eq!(csv_field(&42.into()), "42");
+        assert_eq!(csv_field(&"app".into()), "app");
+        assert_eq!(
+            csv_field(&"say \"hi\", twice".into()),
+            "\"say \"\"hi\"\", twice\""
+        );
+    }
+
+    #[test]
+    fn should_not_recommend_retries_without_flaky_failures() {
+        let runs = [run("b", 1), run("a", 0)];
+        let flakiness = score_flakiness(TaskTarget::default(), &runs, &Default::default());
+
+        assert_eq!(flakiness.flake_rate, 0.0);
+        assert_eq!(flakiness.consecutive_failures, 1);
+        assert_eq!(flakiness.recommended_retries, 0);
+    }
+}
@@ -16,6 +16,7 @@ pub struct Task {
     pub start_time: Option<i64>,
     pub end_time: Option<i64>,
     pub continuous: Option<bool>,
+    pub hash: Option<String>,
 }
 
 #[napi(object)]
@@ -48,12 +49,18 @@ pub enum HashInstruction {
     WorkspaceFileSet(Vec<String>),
     Runtime(String),
     Environment(String),
+    /// Every variable matching a pattern such as `NX_PUBLIC_*`
+    EnvironmentPattern(String),
     ProjectFileSet(String, Vec<String>),
     ProjectConfiguration(String),
     TsConfiguration(String),
-    TaskOutput(String, Vec<String>),
+    /// The output files of a dependent task matching a glob, along with the hash of the
+    /// dependent task when it is known
+    TaskOutput(String, Vec<String>, Option<String>),
     External(String),
     AllExternalDependencies,
+    /// The sorted external dependencies used by a project and its workspace dependencies
+    ProjectExternalDependencies(String, Vec<String>),
 }
 
 impl ToNapiValue for HashInstruction {
@@ -83,14 +90,18 @@ impl fmt::Display for HashInstruction {
             "{}",
             match self {
                 HashInstruction::AllExternalDependencies => "AllExternalDependencies".to_string(),
+                HashInstruction::ProjectExternalDependencies(project_name, _) => {
+                    format!("{project_name}:ExternalDependencies")
+                }
                 HashInstruction::ProjectFileSet(project_name, file_set) => {
                     format!("{project_name}:{}", file_set.join(","))
                 }
                 HashInstruction::WorkspaceFileSet(file_set) =>
                     format!("workspace:[{}]", file_set.join(",")),
                 HashInstruction::Runtime(runtime) => format!("runtime:{}", runtime),
-                HashInstruction::Environment(env) => format!("env:{}", env),
-                HashInstruction::TaskOutput(task_output, dep_outputs) => {
+                HashInstruction::Environment(env) | HashInstruction::EnvironmentPattern(env) =>
+                    format!("env:{}", env),
+                HashInstruction::TaskOutput(task_output, dep_outputs, _) => {
                     let dep_outputs = dep_outputs.join(",");
                     format!("{task_output}:{dep_outputs}")
                 }
@@ -65,8 +65,51 @@ describe('Cache', () => {
     );
   });
 
+  it('should report how outputs were restored', async () => {
+    tempFs.createFileSync('dist/output.txt', 'output contents 123');
+    cache.put('123', 'output 123', ['dist'], 0);
+    tempFs.removeFileSync('dist/output.txt');
+
+    const restored = cache.restoreFilesFromCache(cache.get('123'), ['dist']);
+
+    expect(restored.size).toEqual('output contents 123'.length);
+    expect(['Reflink', 'Hardlink', 'Copy']).toContain(restored.strategy);
+    expect(
+      restored.reflinkedFiles + restored.hardlinkedFiles + restored.copiedFiles
+    ).toEqual(1);
+  });
+
+  it('should not report a restore strategy when there was nothing to restore', () => {
+    cache.put('123', 'output 123', [], 0);
+
+    const restored = cache.restoreFilesFromCache(cache.get('123'), ['dist']);
+
+    expect(restored.size).toEqual(0);
+    expect(restored.strategy).toBeUndefined();
+  });
+
   it('should handle storing hashes that already exist in the cache', async () => {
     cache.put('123', 'output 123', ['dist'], 0);
     expect(() => cache.put('123', 'output 123', ['dist'], 0)).not.toThrow();
   });
+
+  it('should keep blobs which are still referenced by other entries', async () => {
+    taskDetails.recordTaskDetails([
+      {
+        hash: '456',
+        project: 'proj',
+        target: 'build',
+        configuration: 'production',
+      },
+    ]);
+    tempFs.createFileSync('dist/output.txt', 'shared output');
+    cache.put('123', 'output 123', ['dist'], 0);
+    cache.put('456', 'output 456', ['dist'], 0);
+    tempFs.removeFileSync('dist/output.txt');
+
+    cache.removeEntries({ hashes: ['123'] });
+    cache.copyFilesFromCache(cache.get('456'), ['dist']);
+
+    expect(await tempFs.readFile('dist/output.txt')).toEqual('shared output');
+  });
 });
@@ -448,6 +448,81 @@ describe('task planner', () => {
     expect(plans).toMatchSnapshot();
   });
 
+  it('should hash executors of local plugins', async () => {
+    const projectFileMap = {
+      app: [],
+      tools: [{ file: 'tools/src/index.ts', hash: 'tools.hash' }],
+    };
+    const builder = new ProjectGraphBuilder(undefined, projectFileMap);
+    builder.addNode({
+      name: 'app',
+      type: 'app',
+      data: {
+        root: 'apps/app',
+        targets: {
+          build: {
+            executor: '@myorg/tools:build',
+          },
+        },
+      },
+    });
+    builder.addNode({
+      name: 'tools',
+      type: 'lib',
+      data: {
+        root: 'tools',
+        targets: {},
+        metadata: {
+          js: {
+            packageName: '@myorg/tools',
+          },
+        },
+      },
+    });
+    builder.addExternalNode({
+      type: 'npm',
+      name: 'npm:lodash',
+      data: {
+        packageName: 'lodash',
+        hash: 'hash1',
+        version: '1.0.0',
+      },
+    });
+    builder.addStaticDependency('tools', 'npm:lodash', 'tools/src/index.ts');
+    const projectGraph = builder.getUpdatedProjectGraph();
+    const taskGraph = createTaskGraph(
+      projectGraph,
+      {},
+      ['app'],
+      ['build'],
+      undefined,
+      {}
+    );
+
+    const planner = new HashPlanner(
+      {} as any,
+      transferProjectGraph(transformProjectGraphForRust(projectGraph))
+    );
+
+    const plans = planner.getPlans(['app:build'], taskGraph);
+    expect(plans).toMatchInlineSnapshot(`
+      {
+        "app:build": [
+          "workspace:[{workspaceRoot}/nx.json,{workspaceRoot}/.gitignore,{workspaceRoot}/.nxignore]",
+          "env:NX_CLOUD_ENCRYPTION_KEY",
+          "app:{projectRoot}/**/*",
+          "tools:{projectRoot}/**/*",
+          "app:ProjectConfiguration",
+          "tools:ProjectConfiguration",
+          "app:TsConfig",
+          "tools:TsConfig",
+          "npm:lodash",
+          "AllExternalDependencies",
+        ],
+      }
+    `);
+  });
+
   it('should build plans where the project graph has circular dependencies', async () => {
     let projectFileMap = {
       parent: [{ file: '/filea.ts', hash: 'a.hash' }],
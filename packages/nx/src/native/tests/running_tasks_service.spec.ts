@@ -28,15 +28,38 @@ describe('RunningTasksService', () => {
   });
 
   it('should record a task as running', () => {
-    runningTasksService.addRunningTask('app:build');
+    expect(runningTasksService.addRunningTask('app:build')).toEqual(true);
     expect(runningTasksService.getRunningTasks(['app:build'])).toEqual([
       'app:build',
     ]);
   });
 
+  it('should record the lease of a running task', () => {
+    const before = Date.now();
+    runningTasksService.addRunningTask('app:serve');
+
+    const lease = runningTasksService.getRunningTask('app:serve');
+    expect(lease).toEqual(
+      expect.objectContaining({
+        taskId: 'app:serve',
+        pid: process.pid,
+        owned: true,
+      })
+    );
+    expect(lease.machineId).toBeDefined();
+    expect(lease.startedAt).toBeGreaterThanOrEqual(before);
+    expect(lease.heartbeatAt).toEqual(lease.startedAt);
+
+    runningTasksService.heartbeat();
+    expect(
+      runningTasksService.getRunningTask('app:serve').heartbeatAt
+    ).toBeGreaterThanOrEqual(lease.heartbeatAt);
+  });
+
   it('should remove a task from running tasks', () => {
     runningTasksService.addRunningTask('app:build');
     runningTasksService.removeRunningTask('app:build');
     expect(runningTasksService.getRunningTasks(['app:build'])).toEqual([]);
+    expect(runningTasksService.getRunningTask('app:build')).toBeNull();
   });
 });
@@ -1,7 +1,11 @@
-import { TaskDetails, NxTaskHistory } from '../index';
+import {
+  TaskDetails,
+  NxTaskHistory,
+  TaskHistoryExportFormat,
+} from '../index';
 import { join } from 'path';
 import { TempFs } from '../../internal-testing-utils/temp-fs';
-import { rmSync } from 'fs';
+import { readFileSync, rmSync } from 'fs';
 import { getDbConnection } from '../../utils/db-connection';
 import { randomBytes } from 'crypto';
 
@@ -123,4 +127,208 @@ describe('NxTaskHistory', () => {
     ]);
     expect(r['proj:build:production']).toEqual(60 * 60 * 1000);
   });
+
+  it('should score task flakiness by target', () => {
+    const end = Date.now();
+    taskHistory.recordTaskRuns([
+      {
+        hash: '123',
+        code: 0,
+        status: 'success',
+        start: end - 4000,
+        end: end - 3000,
+      },
+      {
+        hash: '123',
+        code: 1,
+        status: 'failure',
+        start: end - 3000,
+        end: end - 2000,
+      },
+      {
+        hash: '234',
+        code: 0,
+        status: 'local-cache',
+        start: end - 2000,
+        end: end - 1000,
+      },
+      {
+        hash: '234',
+        code: 1,
+        status: 'failure',
+        start: end - 1000,
+        end,
+      },
+    ]);
+
+    const [flakiness] = taskHistory.getTaskFlakiness(
+      [{ project: 'proj', target: 'build', configuration: 'production' }],
+      { recentRuns: 2 }
+    );
+    expect(flakiness.runs).toEqual(3);
+    expect(flakiness.flakyFailures).toEqual(1);
+    expect(flakiness.consecutiveFailures).toEqual(2);
+    expect(flakiness.recentStatuses).toEqual(['failure', 'failure']);
+    expect(flakiness.recommendedRetries).toEqual(3);
+
+    const [recent] = taskHistory.getTaskFlakiness(
+      [{ project: 'proj', target: 'build', configuration: 'production' }],
+      { since: end - 1500 }
+    );
+    expect(recent.runs).toEqual(1);
+    expect(recent.flakeRate).toEqual(0);
+  });
+
+  it('should estimate the task graph from cache hits and misses', () => {
+    const end = Date.now();
+    taskHistory.recordTaskRuns([
+      {
+        hash: '123',
+        code: 0,
+        status: 'success',
+        start: end - 60_000,
+        end,
+      },
+      {
+        hash: '234',
+        code: 0,
+        status: 'local-cache',
+        start: end - 1000,
+        end,
+      },
+    ]);
+    const task = {
+      id: 'proj:build:production',
+      target: { project: 'proj', target: 'build', configuration: 'production' },
+      outputs: [],
+    };
+
+    const miss = taskHistory.getTaskGraphEstimate({
+      roots: [task.id],
+      tasks: { [task.id]: { ...task, hash: 'new' } },
+      dependencies: { [task.id]: [] },
+      continuousDependencies: {},
+    });
+    expect(miss.criticalPath).toEqual([task.id]);
+    expect(miss.eta).toEqual(60_000);
+
+    const hit = taskHistory.getTaskGraphEstimate({
+      roots: [task.id],
+      tasks: { [task.id]: { ...task, hash: '123' } },
+      dependencies: { [task.id]: [] },
+      continuousDependencies: {},
+    });
+    expect(hit.tasks[task.id].expectCacheHit).toEqual(true);
+    expect(hit.eta).toEqual(1000);
+  });
+
+  it('should compact old runs into daily aggregates and export them', () => {
+    const day = 24 * 60 * 60 * 1000;
+    const old = Date.UTC(2024, 0, 1, 12);
+    taskHistory.recordTaskRuns([
+      {
+        hash: '123',
+        code: 0,
+        status: 'success',
+        start: old - 2000,
+        end: old,
+      },
+      {
+        hash: '123',
+        code: 0,
+        status: 'success',
+        start: old - 4000,
+        end: old + 1000,
+      },
+      {
+        hash: '234',
+        code: 0,
+        status: 'success',
+        start: Date.now() - 1000,
+        end: Date.now(),
+      },
+    ]);
+
+    const compaction = taskHistory.compactTaskHistory({ maxAgeDays: 30 });
+    expect(compaction.compactedRuns).toEqual(2);
+    expect(compaction.removedHashDetails).toEqual(0);
+    expect(Date.now() - old).toBeGreaterThan(30 * day);
+
+    const runsPath = join(tempFs.tempDir, 'history.jsonl');
+    expect(
+      taskHistory.exportTaskHistory(runsPath, TaskHistoryExportFormat.JsonLines)
+    ).toEqual(1);
+    expect(JSON.parse(readFileSync(runsPath, 'utf-8'))).toMatchObject({
+      hash: '234',
+      project: 'proj',
+      status: 'success',
+    });
+
+    const dailyPath = join(tempFs.tempDir, 'daily.csv');
+    taskHistory.exportTaskHistory(dailyPath, TaskHistoryExportFormat.Csv, true);
+    expect(readFileSync(dailyPath, 'utf-8').split('\n')).toEqual([
+      'project,target,configuration,day,status,runs,total_duration,min_duration,max_duration',
+      'proj,build,production,2024-01-01,success,2,7000,2000,5000',
+      '',
+    ]);
+  });
+
+  it('should not compact runs without task details', () => {
+    const old = Date.UTC(2024, 0, 1, 12);
+    taskHistory.recordTaskRuns([
+      {
+        hash: 'unknown',
+        code: 0,
+        status: 'success',
+        start: old - 2000,
+        end: old,
+      },
+    ]);
+
+    const compaction = taskHistory.compactTaskHistory({ maxAgeDays: 30 });
+    expect(compaction.compactedRuns).toEqual(0);
+
+    const runsPath = join(tempFs.tempDir, 'history.jsonl');
+    expect(
+      taskHistory.exportTaskHistory(runsPath, TaskHistoryExportFormat.JsonLines)
+    ).toEqual(1);
+    const dailyPath = join(tempFs.tempDir, 'daily.csv');
+    expect(
+      taskHistory.exportTaskHistory(
+        dailyPath,
+        TaskHistoryExportFormat.Csv,
+        true
+      )
+    ).toEqual(0);
+  });
+
+  it('should record run metadata', () => {
+    taskHistory.recordTaskRuns([
+      {
+        hash: '123',
+        code: 0,
+        status: 'remote-cache',
+        start: Date.now() - 1000,
+        end: Date.now(),
+        cacheSource: 'remote',
+        peakRss: 1024,
+        cpuTime: 500,
+        gitCommit: 'abc123',
+        commandId: 'command',
+      },
+    ]);
+
+    const path = join(tempFs.tempDir, 'history.jsonl');
+    taskHistory.exportTaskHistory(path, TaskHistoryExportFormat.JsonLines);
+    const run = JSON.parse(readFileSync(path, 'utf-8'));
+    expect(run).toMatchObject({
+      cache_source: 'remote',
+      peak_rss: 1024,
+      cpu_time: 500,
+      git_commit: 'abc123',
+      command_id: 'command',
+    });
+    // The machine id defaults to the id of the current machine
+    expect(run.machine_id).toEqual(expect.any(String));
+  });
 });
@@ -31,6 +31,7 @@ export function transformProjectGraphForRust(
       namedInputs: projectNode.data.namedInputs,
       targets,
       tags: projectNode.data.tags,
+      packageName: projectNode.data.metadata?.js?.packageName,
     };
     if (graph.dependencies[projectName]) {
       dependencies[projectName] = [];
@@ -46,6 +47,7 @@ export function transformProjectGraphForRust(
       packageName: externalNode.data.packageName,
       hash: externalNode.data.hash,
       version: externalNode.data.version,
+      patches: externalNode.data.patches,
     };
     if (graph.dependencies[projectName]) {
       dependencies[projectName] = [];
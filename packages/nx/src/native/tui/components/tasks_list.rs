@@ -2176,6 +2176,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "task2".to_string(),
@@ -2189,6 +2190,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "task3".to_string(),
@@ -2202,6 +2204,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
         ];
         let selection_manager = Arc::new(Mutex::new(TaskSelectionManager::new(10)));
@@ -2300,6 +2303,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "task2".to_string(),
@@ -2313,6 +2317,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "task3".to_string(),
@@ -2326,6 +2331,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "task4".to_string(),
@@ -2339,6 +2345,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
         ];
 
@@ -2724,6 +2731,7 @@ mod tests {
             continuous: Some(true),
             start_time: None,
             end_time: None,
+            hash: None,
         };
 
         // Add and start the continuous task
@@ -2759,6 +2767,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             };
             tasks.push(task);
         }
@@ -2810,6 +2819,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             };
             tasks.push(task);
         }
@@ -2872,6 +2882,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             };
             tasks.push(task);
         }
@@ -2930,6 +2941,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "task2".to_string(),
@@ -2943,6 +2955,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
         ];
 
@@ -3004,6 +3017,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "another-very-long-task-name-for-testing-purposes".to_string(),
@@ -3017,6 +3031,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
         ];
 
@@ -3242,6 +3257,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             });
         }
 
@@ -3363,6 +3379,7 @@ mod tests {
             continuous: Some(false),
             start_time: None,
             end_time: None,
+            hash: None,
         }];
 
         let selection_manager = Arc::new(Mutex::new(TaskSelectionManager::new(10)));
@@ -3391,6 +3408,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "this-is-a-very-long-task-name-that-exceeds-thirty-characters".to_string(),
@@ -3404,6 +3422,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
         ];
 
@@ -3456,6 +3475,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "short2".to_string(),
@@ -3469,6 +3489,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "short3".to_string(),
@@ -3482,6 +3503,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             // Viewport 2: Long task names
             Task {
@@ -3496,6 +3518,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "another-extremely-long-task-name-for-testing-viewport-consistency-viewport2-task2".to_string(),
@@ -3509,6 +3532,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
         ];
 
@@ -3576,6 +3600,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "short2".to_string(),
@@ -3589,6 +3614,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             // Viewport 2: Long task names
             Task {
@@ -3604,6 +3630,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "another-extremely-long-task-name-for-testing-scrolling-consistency-behavior"
@@ -3618,6 +3645,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
         ];
 
@@ -3685,6 +3713,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             Task {
                 id: "short2".to_string(),
@@ -3698,6 +3727,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
             // Viewport 2: Long task names that would affect column visibility
             Task {
@@ -3712,6 +3742,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             },
         ];
 
@@ -3770,6 +3801,7 @@ mod tests {
             continuous: Some(false),
             start_time: None,
             end_time: None,
+            hash: None,
         }];
 
         let selection_manager = Arc::new(Mutex::new(TaskSelectionManager::new(10)));
@@ -3844,6 +3876,7 @@ mod tests {
                 continuous: Some(false),
                 start_time: None,
                 end_time: None,
+                hash: None,
             });
         }
 
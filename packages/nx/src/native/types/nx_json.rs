@@ -5,4 +5,6 @@ use std::collections::HashMap;
 /// Stripped version of the NxJson interface for use in rust
 pub struct NxJson {
     pub named_inputs: Option<HashMap<String, Vec<JsInputs>>>,
+    /// Hash only the external dependencies a project uses instead of all of them
+    pub scope_external_dependencies: Option<bool>,
 }
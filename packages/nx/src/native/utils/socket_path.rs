@@ -2,7 +2,7 @@ use std::env;
 use std::fs;
 use std::path::PathBuf;
 
-use crate::native::hasher::hash;
+use crate::native::hasher::HashAlgorithm;
 
 const DAEMON_DIR_FOR_CURRENT_WORKSPACE: &str = "./nx/workspace-data/d";
 
@@ -12,7 +12,7 @@ fn socket_dir_name(workspace_root: &str, unique_name: Option<&'static str>) -> P
         hashing_string.push(',');
         hashing_string.push_str(name);
     }
-    let result = hash(hashing_string.as_bytes());
+    let result = HashAlgorithm::Xxh3.hash(hashing_string.as_bytes());
     let temp_dir = std::env::temp_dir();
     temp_dir.join(result)
 }
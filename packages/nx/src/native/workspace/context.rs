@@ -5,7 +5,7 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use crate::native::glob::glob_files::glob_files;
-use crate::native::hasher::hash;
+use crate::native::hasher::{Hasher, hash};
 use crate::native::logger::enable_logger;
 use crate::native::project_graph::utils::{ProjectRootMappings, find_project_for_path};
 use crate::native::types::FileData;
@@ -19,7 +19,6 @@ use crate::native::workspace::{types::NxWorkspaceFiles, workspace_files};
 use napi::bindgen_prelude::External;
 use rayon::prelude::*;
 use tracing::{trace, warn};
-use xxhash_rust::xxh3;
 
 #[napi]
 pub struct WorkspaceContext {
@@ -265,12 +264,12 @@ impl WorkspaceContext {
             .into_iter()
             .map(|globs| {
                 let globbed_files = glob_files(files, globs, None)?.collect::<Vec<_>>();
-                let mut hasher = xxh3::Xxh3::new();
+                let mut hasher = Hasher::new();
                 for file in globbed_files {
                     hasher.update(file.file.as_bytes());
                     hasher.update(file.hash.as_bytes());
                 }
-                Ok(hasher.digest().to_string())
+                Ok(hasher.digest())
             })
             .collect::<napi::Result<Vec<_>>>()?;
 
@@ -286,13 +285,13 @@ impl WorkspaceContext {
         let files = &self.all_file_data();
         let globbed_files = glob_files(files, globs, exclude)?.collect::<Vec<_>>();
 
-        let mut hasher = xxh3::Xxh3::new();
+        let mut hasher = Hasher::new();
         for file in globbed_files {
             hasher.update(file.file.as_bytes());
             hasher.update(file.hash.as_bytes());
         }
 
-        Ok(hasher.digest().to_string())
+        Ok(hasher.digest())
     }
 
     #[napi]
@@ -6,6 +6,8 @@ use std::path::Path;
 
 use tracing::trace;
 
+use crate::native::hasher::HashAlgorithm;
+
 const NX_FILES_ARCHIVE: &str = "nx_files.nxt";
 
 #[derive(Archive, Serialize, Deserialize, PartialEq, Debug)]
@@ -16,6 +18,14 @@ pub struct NxFileHashed(pub String, pub i64);
 #[archive(check_bytes)]
 pub struct NxFileHashes(HashMap<String, NxFileHashed>);
 
+/// The file hashes along with the algorithm which produced them
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(check_bytes)]
+pub struct NxFilesArchive {
+    pub hash_algorithm: String,
+    pub files: NxFileHashes,
+}
+
 impl Deref for NxFileHashes {
     type Target = HashMap<String, NxFileHashed>;
 
@@ -49,9 +59,9 @@ pub fn read_files_archive<P: AsRef<Path>>(cache_dir: P) -> Option<NxFileHashes>
         .map_err(anyhow::Error::from)
         .and_then(|bytes| {
             // let archived = unsafe { rkyv::archived_root::<NxFilesArchive>(&bytes) };
-            let archived = rkyv::check_archived_root::<NxFileHashes>(&bytes)
+            let archived = rkyv::check_archived_root::<NxFilesArchive>(&bytes)
                 .map_err(|_| anyhow!("invalid archive file"))?;
-            <ArchivedNxFileHashes as Deserialize<NxFileHashes, Infallible>>::deserialize(
+            <ArchivedNxFilesArchive as Deserialize<NxFilesArchive, Infallible>>::deserialize(
                 archived,
                 &mut rkyv::Infallible,
             )
@@ -59,9 +69,17 @@ pub fn read_files_archive<P: AsRef<Path>>(cache_dir: P) -> Option<NxFileHashes>
         });
 
     match bytes {
+        Ok(archive) if archive.hash_algorithm != HashAlgorithm::current().name() => {
+            // Hashes of another algorithm can not be reused, so every file is hashed again
+            trace!(
+                "files archive was hashed with {}, ignoring it",
+                archive.hash_algorithm
+            );
+            None
+        }
         Ok(archive) => {
             trace!("read archive in {:?}", now.elapsed());
-            Some(archive)
+            Some(archive.files)
         }
         Err(e) => {
             trace!("could not read files archive: {:?}", e);
@@ -73,7 +91,11 @@ pub fn read_files_archive<P: AsRef<Path>>(cache_dir: P) -> Option<NxFileHashes>
 pub fn write_files_archive<P: AsRef<Path>>(cache_dir: P, files: NxFileHashes) {
     let now = std::time::Instant::now();
     let archive_path = cache_dir.as_ref().join(NX_FILES_ARCHIVE);
-    let result = rkyv::to_bytes::<_, 2048>(&files)
+    let archive = NxFilesArchive {
+        hash_algorithm: HashAlgorithm::current().name().to_string(),
+        files,
+    };
+    let result = rkyv::to_bytes::<_, 2048>(&archive)
         .map_err(anyhow::Error::from)
         .and_then(|encoded| {
             std::fs::write(archive_path, encoded)?;
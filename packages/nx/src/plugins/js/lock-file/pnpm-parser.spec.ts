@@ -1765,4 +1765,46 @@ snapshots:
       ).not.toThrow();
     });
   });
+
+  describe('patched dependencies', () => {
+    beforeEach(() => {
+      const fileSys = {
+        'node_modules/.modules.yaml': `hoistedDependencies: {}`,
+        'node_modules/lodash/package.json': '{"version": "4.17.21"}',
+      };
+      vol.fromJSON(fileSys, '/root');
+    });
+
+    it('should record the patches of external nodes', () => {
+      const lockFile = `lockfileVersion: '9.0'
+
+patchedDependencies:
+  lodash@4.17.21:
+    hash: 2a5cd1f0c8e0d8ae0e4d5d4bd1e5a6ab
+    path: patches/lodash@4.17.21.patch
+
+importers:
+
+  .:
+    dependencies:
+      lodash:
+        specifier: ^4.17.21
+        version: 4.17.21(patch_hash=2a5cd1f0c8e0d8ae0e4d5d4bd1e5a6ab)
+
+packages:
+
+  lodash@4.17.21:
+    resolution: {integrity: sha512-v2kDEe57lecTulaDIuNTPy3Ry4gLGJ6Z1O3vE1krgXZNrsQ+LFTGHVxVjcXPs17LhbZVGedAJv8XZ1tvj5FvSg==}
+
+snapshots:
+
+  lodash@4.17.21(patch_hash=2a5cd1f0c8e0d8ae0e4d5d4bd1e5a6ab): {}`;
+
+      const externalNodes = getPnpmLockfileNodes(lockFile, 'patched-hash');
+
+      expect(externalNodes['npm:lodash'].data.patches).toEqual([
+        'patches/lodash@4.17.21.patch',
+      ]);
+    });
+  });
 });
@@ -123,6 +123,17 @@ function createHashFromSnapshot(snapshot: PackageSnapshot) {
   );
 }
 
+function findPatch(
+  data: Lockfile,
+  packageName: string,
+  version: string
+): string | undefined {
+  const patch =
+    data.patchedDependencies?.[`${packageName}@${version}`] ??
+    data.patchedDependencies?.[packageName];
+  return patch?.path;
+}
+
 function isAliasVersion(depVersion: string) {
   return depVersion.startsWith('/') || depVersion.includes('@');
 }
@@ -296,6 +307,10 @@ function getNodes(
           hash: hash ?? hashArray([packageName, version]),
         },
       };
+      const patch = findPatch(data, packageName, version);
+      if (patch) {
+        node.data.patches = [patch];
+      }
       nodes.get(packageName).set(version, node);
       if (!keyMap.has(key)) {
         keyMap.set(key, new Set([node]));
@@ -7,17 +7,20 @@ import {
   RemoteCacheV2,
 } from './default-tasks-runner';
 import { spawn } from 'child_process';
-import { existsSync, mkdirSync } from 'node:fs';
+import { existsSync, mkdirSync, rmSync } from 'node:fs';
 import { mkdir, readFile, writeFile } from 'node:fs/promises';
 import { cacheDir } from '../utils/cache-directory';
-import { Task } from '../config/task-graph';
+import { Task, TaskGraph } from '../config/task-graph';
 import { machineId } from 'node-machine-id';
 import {
   NxCache,
   CachedResult as NativeCacheResult,
+  CacheEntryFilter,
+  CacheEvictionOptions,
   IS_WASM,
   getDefaultMaxCacheSize,
-  HttpRemoteCache,
+  RestoreResult,
+  SelfHostedRemoteCache,
 } from '../native';
 import { getDbConnection } from '../utils/db-connection';
 import { isNxCloudUsed } from '../utils/nx-cloud-utils';
@@ -79,12 +82,16 @@ export class DbCache {
     cacheDir,
     getDbConnection(),
     undefined,
-    resolveMaxCacheSize(this.nxJson)
+    resolveMaxCacheSize(this.nxJson),
+    resolveCacheEvictionOptions(this.nxJson)
   );
 
   private remoteCache: RemoteCacheV2 | null;
   private remoteCachePromise: Promise<RemoteCacheV2>;
 
+  private prefetching: Promise<void> | null = null;
+  private prefetchedHashes = new Set<string>();
+
   private isVerbose = process.env.NX_VERBOSE_LOGGING === 'true';
 
   constructor(
@@ -119,6 +126,7 @@ export class DbCache {
         task.hash,
         this.cache.cacheDirectory
       );
+      this.prefetchedHashes.delete(task.hash);
 
       if (res) {
         this.applyRemoteCacheResults(task.hash, res, task.outputs);
@@ -136,6 +144,67 @@ export class DbCache {
     }
   }
 
+  /**
+   * Downloads the remote cache artifacts of the tasks which are already hashed and not
+   * cached locally in the background, so that retrieving them does not wait on the network
+   */
+  prefetch(taskGraph: TaskGraph) {
+    if (IS_WASM || !(this.remoteCache instanceof SelfHostedRemoteCache)) {
+      return;
+    }
+    const hashes = Object.values(taskGraph.tasks)
+      .map((task) => task.hash)
+      .filter(Boolean);
+    if (hashes.length === 0) {
+      return;
+    }
+    const cachedHashes = new Set(
+      this.cache.listEntries({ hashes }).map((entry) => entry.hash)
+    );
+    const tasks = Object.fromEntries(
+      Object.entries(taskGraph.tasks).filter(
+        ([, task]) => task.hash && !cachedHashes.has(task.hash)
+      )
+    );
+    this.prefetching = this.remoteCache
+      .prefetch({ ...taskGraph, tasks }, this.cache.cacheDirectory)
+      .then((prefetched) => {
+        for (const hash of prefetched) {
+          this.prefetchedHashes.add(hash);
+        }
+      })
+      .catch((e) => {
+        // Prefetching is best effort, retrieving the artifacts reports errors again
+        if (this.isVerbose) {
+          console.error('Unable to prefetch from the remote cache', e);
+        }
+      });
+  }
+
+  /**
+   * Removes the prefetched artifacts which were never retrieved, since they are not
+   * recorded in the cache and would never be evicted
+   */
+  async removeUnusedPrefetches() {
+    await this.prefetching;
+    if (this.prefetchedHashes.size === 0) {
+      return;
+    }
+    const hashes = Array.from(this.prefetchedHashes);
+    this.prefetchedHashes.clear();
+    const cachedHashes = new Set(
+      this.cache.listEntries({ hashes }).map((entry) => entry.hash)
+    );
+    for (const hash of hashes) {
+      if (!cachedHashes.has(hash)) {
+        rmSync(join(this.cache.cacheDirectory, hash), {
+          recursive: true,
+          force: true,
+        });
+      }
+    }
+  }
+
   getUsedCacheSpace() {
     return this.cache.getCacheSize();
   }
@@ -168,9 +237,13 @@ export class DbCache {
     });
   }
 
-  copyFilesFromCache(_: string, cachedResult: CachedResult, outputs: string[]) {
+  copyFilesFromCache(
+    _: string,
+    cachedResult: CachedResult,
+    outputs: string[]
+  ): Promise<RestoreResult> {
     return tryAndRetry(async () =>
-      this.cache.copyFilesFromCache(cachedResult, outputs)
+      this.cache.restoreFilesFromCache(cachedResult, outputs)
     );
   }
 
@@ -178,6 +251,24 @@ export class DbCache {
     return this.cache.removeOldCacheRecords();
   }
 
+  /**
+   * Evicts entries according to the eviction policies, or only lists them with `dryRun`
+   */
+  evict(dryRun = false) {
+    return this.cache.evict(dryRun);
+  }
+
+  /**
+   * Writes the entries matching `filter` into a bundle which can be imported into another workspace
+   */
+  exportBundle(bundlePath: string, filter?: CacheEntryFilter) {
+    return this.cache.exportBundle(bundlePath, filter);
+  }
+
+  importBundle(bundlePath: string) {
+    return this.cache.importBundle(bundlePath);
+  }
+
   temporaryOutputPath(task: Task) {
     return this.cache.getTaskOutputsPath(task.hash);
   }
@@ -265,14 +356,18 @@ export class DbCache {
   }
 
   private getHttpCache(): RemoteCacheV2 | null {
-    if (process.env.NX_SELF_HOSTED_REMOTE_CACHE_SERVER) {
+    if (
+      process.env.NX_SELF_HOSTED_REMOTE_CACHE_SERVER ||
+      process.env.NX_SELF_HOSTED_REMOTE_CACHE_DIRECTORY ||
+      process.env.NX_SELF_HOSTED_REMOTE_CACHE_S3_BUCKET
+    ) {
       if (IS_WASM) {
         logger.warn(
-          'The HTTP remote cache is not yet supported in the wasm build of Nx.'
+          'The self-hosted remote cache is not yet supported in the wasm build of Nx.'
         );
         return null;
       }
-      return new HttpRemoteCache();
+      return new SelfHostedRemoteCache();
     }
     return null;
   }
@@ -445,11 +540,15 @@ export class Cache {
     });
   }
 
+  /**
+   * Restores the outputs with full copies. Unlike the db cache, this does not report
+   * a {@link RestoreResult}.
+   */
   async copyFilesFromCache(
     hash: string,
     cachedResult: CachedResult,
     outputs: string[]
-  ) {
+  ): Promise<RestoreResult | undefined> {
     return tryAndRetry(async () => {
       const expandedOutputs = await this.expandOutputsInCache(
         outputs,
@@ -465,6 +564,7 @@ export class Cache {
           }
         })
       );
+      return undefined;
     });
   }
 
@@ -628,6 +728,32 @@ export function resolveMaxCacheSize(nxJson: NxJsonConfiguration): number {
     : getDefaultMaxCacheSize(cacheDir);
 }
 
+/**
+ * Resolves the eviction policies of the local cache from nx.json
+ *
+ * @param nxJson The nx.json configuration object
+ */
+export function resolveCacheEvictionOptions(
+  nxJson: NxJsonConfiguration
+): CacheEvictionOptions | undefined {
+  const config = nxJson.cacheEviction;
+  if (!config) {
+    return undefined;
+  }
+  return {
+    projectQuotas: config.projectQuotas
+      ? Object.fromEntries(
+          Object.entries(config.projectQuotas).map(([project, quota]) => [
+            project,
+            parseMaxCacheSize(quota),
+          ])
+        )
+      : undefined,
+    keepPerTarget: config.keepPerTarget,
+    costAware: config.costAware,
+  };
+}
+
 /**
  * Converts a string representation of a max cache size to a number.
  *
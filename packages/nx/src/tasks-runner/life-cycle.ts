@@ -1,5 +1,10 @@
 import { Task } from '../config/task-graph';
-import { ExternalObject, TaskStatus as NativeTaskStatus } from '../native';
+import {
+  ExternalObject,
+  ProcessTreeUsage,
+  RestoreStrategy,
+  TaskStatus as NativeTaskStatus,
+} from '../native';
 import { RunningTask } from './running-tasks/running-task';
 import { TaskStatus } from './tasks-runner';
 
@@ -11,6 +16,14 @@ export interface TaskResult {
   status: TaskStatus;
   code: number;
   terminalOutput?: string;
+  /**
+   * How the outputs were restored when they came from the cache
+   */
+  restoreStrategy?: RestoreStrategy;
+  /**
+   * The memory and CPU time used by the processes of the task when it was executed
+   */
+  resourceUsage?: ProcessTreeUsage;
 }
 
 /**
@@ -1,12 +1,15 @@
+import { randomUUID } from 'crypto';
 import { readNxJson } from '../../config/nx-json';
 import { Task } from '../../config/task-graph';
 import { IS_WASM, type TaskRun as NativeTaskRun } from '../../native';
+import { getLatestCommitSha } from '../../utils/git-utils';
 import { isNxCloudUsed } from '../../utils/nx-cloud-utils';
 import { output } from '../../utils/output';
 import { serializeTarget } from '../../utils/serialize-target';
 import { getTaskHistory, TaskHistory } from '../../utils/task-history';
 import { isTuiEnabled } from '../is-tui-enabled';
 import { LifeCycle, TaskResult } from '../life-cycle';
+import type { TaskStatus } from '../tasks-runner';
 import { LegacyTaskHistoryLifeCycle } from './task-history-life-cycle-old';
 
 interface TaskRun extends NativeTaskRun {
@@ -32,6 +35,8 @@ export class TaskHistoryLifeCycle implements LifeCycle {
   private taskRuns = new Map<string, TaskRun>();
   private taskHistory: TaskHistory | null = getTaskHistory();
   private flakyTasks: string[];
+  private commandId = randomUUID();
+  private gitCommit: string | null | undefined;
 
   constructor() {
     if (tasksHistoryLifeCycle) {
@@ -58,6 +63,11 @@ export class TaskHistoryLifeCycle implements LifeCycle {
         start:
           taskResult.task.startTime ?? this.startTimings[taskResult.task.id],
         end: taskResult.task.endTime ?? Date.now(),
+        cacheSource: getCacheSource(taskResult.status),
+        peakRss: taskResult.resourceUsage?.peakRss,
+        cpuTime: taskResult.resourceUsage?.cpuTime,
+        gitCommit: this.getGitCommit(),
+        commandId: this.commandId,
       }))
       .forEach((taskRun) => {
         this.taskRuns.set(taskRun.hash, taskRun);
@@ -70,6 +80,10 @@ export class TaskHistoryLifeCycle implements LifeCycle {
       return;
     }
     await this.taskHistory.recordTaskRuns(entries.map(([_, v]) => v));
+    const retention = readNxJson().taskHistory;
+    if (retention?.maxAgeDays || retention?.maxRunsPerTarget) {
+      await this.taskHistory.compactTaskHistory(retention);
+    }
     this.flakyTasks = await this.taskHistory.getFlakyTasks(
       entries.map(([hash]) => hash)
     );
@@ -80,6 +94,13 @@ export class TaskHistoryLifeCycle implements LifeCycle {
     this.printFlakyTasksMessage();
   }
 
+  private getGitCommit(): string | undefined {
+    if (this.gitCommit === undefined) {
+      this.gitCommit = getLatestCommitSha();
+    }
+    return this.gitCommit ?? undefined;
+  }
+
   printFlakyTasksMessage() {
     if (this.flakyTasks?.length > 0) {
       output.warn({
@@ -107,3 +128,17 @@ export class TaskHistoryLifeCycle implements LifeCycle {
     }
   }
 }
+
+function getCacheSource(status: TaskStatus): TaskRun['cacheSource'] {
+  switch (status) {
+    case 'local-cache':
+    case 'local-cache-kept-existing':
+      return 'local';
+    case 'remote-cache':
+      return 'remote';
+    case 'skipped':
+      return undefined;
+    default:
+      return 'miss';
+  }
+}
@@ -1,8 +1,15 @@
 import { Serializable } from 'child_process';
 import * as os from 'os';
 import { getForkedProcessOsSocketPath } from '../daemon/socket-utils';
-import { ChildProcess, IS_WASM, RustPseudoTerminal } from '../native';
+import {
+  ChildProcess,
+  IS_WASM,
+  ProcessTreeMonitor,
+  ProcessTreeUsage,
+  RustPseudoTerminal,
+} from '../native';
 import { PseudoIPCServer } from './pseudo-ipc';
+import { monitorProcessTree } from './running-tasks/process-tree-usage';
 import { RunningTask } from './running-tasks/running-task';
 import { codeToSignal } from '../utils/exit-codes';
 
@@ -148,11 +155,14 @@ export class PseudoTtyProcess implements RunningTask {
   private outputCallbacks: Array<(output: string) => void> = [];
 
   private terminalOutput = '';
+  private processTreeMonitor: ProcessTreeMonitor | undefined;
 
   constructor(
     public rustPseudoTerminal: RustPseudoTerminal,
     private childProcess: ChildProcess
   ) {
+    this.processTreeMonitor = monitorProcessTree(childProcess.pid);
+
     childProcess.onOutput((output) => {
       this.terminalOutput += output;
       this.outputCallbacks.forEach((cb) => cb(output));
@@ -163,6 +173,7 @@ export class PseudoTtyProcess implements RunningTask {
 
       const code = messageToCode(message);
       childProcess.cleanup();
+      this.processTreeMonitor?.stop();
 
       this.exitCallbacks.forEach((cb) => cb(code));
     });
@@ -184,6 +195,10 @@ export class PseudoTtyProcess implements RunningTask {
     this.outputCallbacks.push(callback);
   }
 
+  getResourceUsage(): ProcessTreeUsage | undefined {
+    return this.processTreeMonitor?.stop() ?? undefined;
+  }
+
   kill(s?: NodeJS.Signals): void {
     if (this.isAlive) {
       try {
@@ -1,5 +1,7 @@
 import { ChildProcess, Serializable } from 'child_process';
+import type { ProcessTreeMonitor, ProcessTreeUsage } from '../../native';
 import { signalToCode } from '../../utils/exit-codes';
+import { monitorProcessTree } from './process-tree-usage';
 import { RunningTask } from './running-task';
 import { Transform } from 'stream';
 import * as chalk from 'chalk';
@@ -12,11 +14,13 @@ export class NodeChildProcessWithNonDirectOutput implements RunningTask {
   private outputCallbacks: Array<(output: string) => void> = [];
 
   private exitCode: number;
+  private processTreeMonitor: ProcessTreeMonitor | undefined;
 
   constructor(
     private childProcess: ChildProcess,
     { streamOutput, prefix }: { streamOutput: boolean; prefix: string }
   ) {
+    this.processTreeMonitor = monitorProcessTree(this.childProcess.pid);
     if (streamOutput) {
       if (process.env.NX_PREFIX_OUTPUT === 'true') {
         const color = getColor(prefix);
@@ -43,6 +47,7 @@ export class NodeChildProcessWithNonDirectOutput implements RunningTask {
     this.childProcess.on('exit', (code, signal) => {
       if (code === null) code = signalToCode(signal);
       this.exitCode = code;
+      this.processTreeMonitor?.stop();
       for (const cb of this.exitCallbacks) {
         cb(code, this.terminalOutput);
       }
@@ -79,6 +84,10 @@ export class NodeChildProcessWithNonDirectOutput implements RunningTask {
     this.outputCallbacks.push(cb);
   }
 
+  getResourceUsage(): ProcessTreeUsage | undefined {
+    return this.processTreeMonitor?.stop() ?? undefined;
+  }
+
   async getResults(): Promise<{ code: number; terminalOutput: string }> {
     if (typeof this.exitCode === 'number') {
       return {
@@ -168,11 +177,14 @@ export class NodeChildProcessWithDirectOutput implements RunningTask {
 
   private exited = false;
   private exitCode: number;
+  private processTreeMonitor: ProcessTreeMonitor | undefined;
 
   constructor(
     private childProcess: ChildProcess,
     private temporaryOutputPath: string
   ) {
+    this.processTreeMonitor = monitorProcessTree(this.childProcess.pid);
+
     // Re-emit any messages from the task process
     this.childProcess.on('message', (message) => {
       if (process.send) {
@@ -185,6 +197,7 @@ export class NodeChildProcessWithDirectOutput implements RunningTask {
 
       this.exited = true;
       this.exitCode = code;
+      this.processTreeMonitor?.stop();
 
       for (const cb of this.exitCallbacks) {
         cb(code, signal);
@@ -210,6 +223,10 @@ export class NodeChildProcessWithDirectOutput implements RunningTask {
     return { code: this.exitCode, terminalOutput };
   }
 
+  getResourceUsage(): ProcessTreeUsage | undefined {
+    return this.processTreeMonitor?.stop() ?? undefined;
+  }
+
   waitForExit() {
     return new Promise<void>((res) => {
       this.onExit(() => res());
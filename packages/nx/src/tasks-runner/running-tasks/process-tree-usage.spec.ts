@@ -0,0 +1,27 @@
+import { combineProcessTreeUsage } from './process-tree-usage';
+
+describe('combineProcessTreeUsage', () => {
+  const usages = [
+    { peakRss: 100, cpuTime: 10 },
+    undefined,
+    { peakRss: 300, cpuTime: 20 },
+  ];
+
+  it('should add up the memory of process trees which ran in parallel', () => {
+    expect(combineProcessTreeUsage(usages, true)).toEqual({
+      peakRss: 400,
+      cpuTime: 30,
+    });
+  });
+
+  it('should take the highest memory of process trees which ran one after another', () => {
+    expect(combineProcessTreeUsage(usages, false)).toEqual({
+      peakRss: 300,
+      cpuTime: 30,
+    });
+  });
+
+  it('should not report usage when no process tree was sampled', () => {
+    expect(combineProcessTreeUsage([undefined], true)).toBeUndefined();
+  });
+});
@@ -0,0 +1,42 @@
+import {
+  IS_WASM,
+  ProcessTreeMonitor,
+  type ProcessTreeUsage,
+} from '../../native';
+
+/**
+ * Starts sampling the memory and CPU time of the process with the given `pid` and its
+ * descendants. Returns undefined when process trees cannot be sampled.
+ */
+export function monitorProcessTree(
+  pid: number | undefined
+): ProcessTreeMonitor | undefined {
+  if (IS_WASM || pid === undefined) {
+    return undefined;
+  }
+  try {
+    return new ProcessTreeMonitor(pid);
+  } catch {
+    return undefined;
+  }
+}
+
+/**
+ * Combines the usage of process trees which ran in parallel, or one after another
+ */
+export function combineProcessTreeUsage(
+  usages: (ProcessTreeUsage | undefined)[],
+  parallel: boolean
+): ProcessTreeUsage | undefined {
+  const sampled = usages.filter((usage): usage is ProcessTreeUsage => !!usage);
+  if (sampled.length === 0) {
+    return undefined;
+  }
+  const peakRss = sampled.map((usage) => usage.peakRss);
+  return {
+    peakRss: parallel
+      ? peakRss.reduce((total, rss) => total + rss, 0)
+      : Math.max(...peakRss),
+    cpuTime: sampled.reduce((total, usage) => total + usage.cpuTime, 0),
+  };
+}
@@ -1,4 +1,5 @@
 import type { Serializable } from 'child_process';
+import type { ProcessTreeUsage } from '../../native';
 
 export abstract class RunningTask {
   abstract getResults(): Promise<{ code: number; terminalOutput: string }>;
@@ -10,4 +11,9 @@ export abstract class RunningTask {
   abstract onOutput?(cb: (output: string) => void): void;
 
   abstract send?(message: Serializable): void;
+
+  /**
+   * The memory and CPU time used by the processes of the task, once it exited
+   */
+  abstract getResourceUsage?(): ProcessTreeUsage | undefined;
 }
@@ -1,16 +1,41 @@
 import { RunningTask } from './running-task';
 import { RunningTasksService } from '../../native';
 
+const POLL_INTERVAL = 100;
+
+/**
+ * Waits until no live process holds the lease of `taskId` anymore.
+ * Resolves to `false` if the wait times out or is aborted first.
+ */
+export async function waitForRunningTask(
+  runningTasksService: RunningTasksService,
+  taskId: string,
+  options: { timeout?: number; signal?: AbortSignal } = {}
+): Promise<boolean> {
+  const deadline =
+    options.timeout === undefined ? Infinity : Date.now() + options.timeout;
+  while (runningTasksService.getRunningTask(taskId)) {
+    if (options.signal?.aborted || Date.now() >= deadline) {
+      return false;
+    }
+    await new Promise((resolve) => setTimeout(resolve, POLL_INTERVAL));
+  }
+  return true;
+}
+
 export class SharedRunningTask implements RunningTask {
   private exitCallbacks: ((code: number) => void)[] = [];
+  private abortController = new AbortController();
 
   constructor(
     private runningTasksService: RunningTasksService,
     taskId: string
   ) {
-    this.waitForTaskToFinish(taskId).then(() => {
-      // notify exit callbacks
-      this.exitCallbacks.forEach((cb) => cb(0));
+    this.waitForTaskToFinish(taskId).then((finished) => {
+      if (finished) {
+        // notify exit callbacks
+        this.exitCallbacks.forEach((cb) => cb(0));
+      }
     });
   }
 
@@ -19,6 +44,7 @@ export class SharedRunningTask implements RunningTask {
   }
 
   kill(): void {
+    this.abortController.abort();
     this.exitCallbacks.forEach((cb) => cb(0));
   }
 
@@ -26,11 +52,15 @@ export class SharedRunningTask implements RunningTask {
     this.exitCallbacks.push(cb);
   }
 
-  private async waitForTaskToFinish(taskId: string) {
-    console.log(`Waiting for ${taskId} in another nx process`);
-    // wait for the running task to finish
-    do {
-      await new Promise((resolve) => setTimeout(resolve, 100));
-    } while (this.runningTasksService.getRunningTasks([taskId]).length);
+  private waitForTaskToFinish(taskId: string) {
+    const lease = this.runningTasksService.getRunningTask(taskId);
+    console.log(
+      lease
+        ? `Waiting for ${taskId} in another nx process (pid ${lease.pid})`
+        : `Waiting for ${taskId} in another nx process`
+    );
+    return waitForRunningTask(this.runningTasksService, taskId, {
+      signal: this.abortController.signal,
+    });
   }
 }
@@ -12,6 +12,7 @@ import { TaskHasher } from '../hasher/task-hasher';
 import {
   IS_WASM,
   parseTaskStatus,
+  RestoreStrategy,
   RunningTasksService,
   TaskDetails,
   TaskStatus as NativeTaskStatus,
@@ -47,6 +48,9 @@ import {
 } from './utils';
 import { SharedRunningTask } from './running-tasks/shared-running-task';
 
+// Leases of continuous tasks expire 30 seconds after the last heartbeat
+const RUNNING_TASKS_HEARTBEAT_INTERVAL = 10_000;
+
 export class TaskOrchestrator {
   private taskDetails: TaskDetails | null = getTaskDetails();
   private cache: DbCache | Cache = getCache(this.options);
@@ -87,6 +91,7 @@ export class TaskOrchestrator {
   private bailed = false;
 
   private runningContinuousTasks = new Map<string, RunningTask>();
+  private runningTasksHeartbeat: NodeJS.Timeout | undefined;
   private runningRunCommandsTasks = new Map<string, RunningTask>();
 
   // endregion internal state
@@ -112,7 +117,7 @@ export class TaskOrchestrator {
       this.tasksSchedule.init().then(() => {
         return this.tasksSchedule.scheduleNextTasks();
       }),
-      'init' in this.cache ? this.cache.init() : null,
+      'init' in this.cache ? this.initCache(this.cache) : null,
     ]);
 
     // Pass estimated timings to TUI after TasksSchedule is initialized
@@ -122,6 +127,12 @@ export class TaskOrchestrator {
     }
   }
 
+  private async initCache(cache: DbCache) {
+    await cache.init();
+    // Tasks which were hashed up front can be downloaded while others run
+    cache.prefetch(this.taskGraph);
+  }
+
   async run() {
     await this.init();
 
@@ -160,6 +171,9 @@ export class TaskOrchestrator {
       'task-execution:end'
     );
     this.cache.removeOldCacheRecords();
+    if ('removeUnusedPrefetches' in this.cache) {
+      await this.cache.removeUnusedPrefetches();
+    }
 
     await this.cleanup();
 
@@ -288,6 +302,7 @@ export class TaskOrchestrator {
     task: Task;
     code: number;
     status: 'local-cache' | 'local-cache-kept-existing' | 'remote-cache';
+    restoreStrategy?: RestoreStrategy;
   }> {
     const cachedResult = await this.cache.get(task);
     if (!cachedResult || cachedResult.code !== 0) return null;
@@ -300,8 +315,14 @@ export class TaskOrchestrator {
       (!cachedResult.remote || !dbCacheEnabled()) &&
       // Output files have not been touched since last run
       (await this.shouldCopyOutputsFromCache(outputs, task.hash));
+    let restoreStrategy: RestoreStrategy | undefined;
     if (shouldCopyOutputsFromCache) {
-      await this.cache.copyFilesFromCache(task.hash, cachedResult, outputs);
+      const restored = await this.cache.copyFilesFromCache(
+        task.hash,
+        cachedResult,
+        outputs
+      );
+      restoreStrategy = restored?.strategy;
     }
     const status = cachedResult.remote
       ? 'remote-cache'
@@ -317,6 +338,7 @@ export class TaskOrchestrator {
       code: cachedResult.code,
       task,
       status,
+      restoreStrategy,
     };
   }
 
@@ -479,12 +501,9 @@ export class TaskOrchestrator {
           streamOutput
         );
 
-    let results: {
-      task: Task;
-      code: number;
-      status: TaskStatus;
-      terminalOutput?: string;
-    }[] = doNotSkipCache ? await this.applyCachedResults([task]) : [];
+    let results: TaskResult[] = doNotSkipCache
+      ? await this.applyCachedResults([task])
+      : [];
 
     // the task wasn't cached
     if (results.length === 0) {
@@ -503,6 +522,7 @@ export class TaskOrchestrator {
         code,
         status: code === 0 ? 'success' : 'failure',
         terminalOutput,
+        resourceUsage: childProcess.getResourceUsage?.(),
       });
     }
     await this.postRunSteps([task], results, doNotSkipCache, { groupId });
@@ -714,9 +734,10 @@ export class TaskOrchestrator {
   }
 
   async startContinuousTask(task: Task, groupId: number) {
+    // Another process which already runs the task keeps its lease
     if (
       this.runningTasksService &&
-      this.runningTasksService.getRunningTasks([task.id]).length
+      !this.runningTasksService.addRunningTask(task.id)
     ) {
       await this.preRunSteps([task], { groupId });
 
@@ -758,46 +779,53 @@ export class TaskOrchestrator {
       return runningTask;
     }
 
-    const taskSpecificEnv = await this.processedTasks.get(task.id);
-    await this.preRunSteps([task], { groupId });
+    let childProcess: RunningTask;
+    try {
+      const taskSpecificEnv = await this.processedTasks.get(task.id);
+      await this.preRunSteps([task], { groupId });
 
-    const pipeOutput = await this.pipeOutputCapture(task);
-    // obtain metadata
-    const temporaryOutputPath = this.cache.temporaryOutputPath(task);
-    const streamOutput =
-      this.outputStyle === 'static'
-        ? false
-        : shouldStreamOutput(task, this.initiatingProject);
+      const pipeOutput = await this.pipeOutputCapture(task);
+      // obtain metadata
+      const temporaryOutputPath = this.cache.temporaryOutputPath(task);
+      const streamOutput =
+        this.outputStyle === 'static'
+          ? false
+          : shouldStreamOutput(task, this.initiatingProject);
 
-    let env = pipeOutput
-      ? getEnvVariablesForTask(
-          task,
-          taskSpecificEnv,
-          process.env.FORCE_COLOR === undefined
-            ? 'true'
-            : process.env.FORCE_COLOR,
-          this.options.skipNxCache,
-          this.options.captureStderr,
-          null,
-          null
-        )
-      : getEnvVariablesForTask(
-          task,
-          taskSpecificEnv,
-          undefined,
-          this.options.skipNxCache,
-          this.options.captureStderr,
-          temporaryOutputPath,
-          streamOutput
-        );
-    const childProcess = await this.runTask(
-      task,
-      streamOutput,
-      env,
-      temporaryOutputPath,
-      pipeOutput
-    );
-    this.runningTasksService.addRunningTask(task.id);
+      let env = pipeOutput
+        ? getEnvVariablesForTask(
+            task,
+            taskSpecificEnv,
+            process.env.FORCE_COLOR === undefined
+              ? 'true'
+              : process.env.FORCE_COLOR,
+            this.options.skipNxCache,
+            this.options.captureStderr,
+            null,
+            null
+          )
+        : getEnvVariablesForTask(
+            task,
+            taskSpecificEnv,
+            undefined,
+            this.options.skipNxCache,
+            this.options.captureStderr,
+            temporaryOutputPath,
+            streamOutput
+          );
+      childProcess = await this.runTask(
+        task,
+        streamOutput,
+        env,
+        temporaryOutputPath,
+        pipeOutput
+      );
+    } catch (e) {
+      // Release the lease so the task can be started again
+      this.runningTasksService?.removeRunningTask(task.id);
+      throw e;
+    }
+    this.startRunningTasksHeartbeat();
     this.runningContinuousTasks.set(task.id, childProcess);
 
     childProcess.onExit(() => {
@@ -1022,7 +1050,21 @@ export class TaskOrchestrator {
 
   // endregion utils
 
+  private startRunningTasksHeartbeat() {
+    if (!this.runningTasksService) {
+      return;
+    }
+    this.runningTasksHeartbeat ??= setInterval(() => {
+      try {
+        this.runningTasksService.heartbeat();
+      } catch {
+        // A missed heartbeat is retried on the next interval
+      }
+    }, RUNNING_TASKS_HEARTBEAT_INTERVAL).unref();
+  }
+
   private async cleanup() {
+    clearInterval(this.runningTasksHeartbeat);
     this.forkedProcessTaskRunner.cleanup();
     await Promise.all([
       ...Array.from(this.runningContinuousTasks).map(async ([taskId, t]) => {
@@ -1,7 +1,19 @@
 import { daemonClient } from '../daemon/client/client';
 import { isOnDaemon } from '../daemon/is-on-daemon';
-import { IS_WASM, NxTaskHistory, TaskRun, TaskTarget } from '../native';
+import {
+  FlakinessOptions,
+  IS_WASM,
+  NxTaskHistory,
+  TaskFlakiness,
+  TaskGraphEstimate,
+  TaskHistoryCompaction,
+  TaskHistoryExportFormat,
+  TaskHistoryRetention,
+  TaskRun,
+  TaskTarget,
+} from '../native';
 import { getDbConnection } from './db-connection';
+import type { TaskGraph } from '../config/task-graph';
 
 export class TaskHistory {
   taskHistory = new NxTaskHistory(getDbConnection());
@@ -27,12 +39,71 @@ export class TaskHistory {
     return await daemonClient.getFlakyTasks(hashes);
   }
 
+  /**
+   * This function scores how flaky each target has been
+   * @param targets
+   * @returns the flake rate, failure streak, recent statuses and recommended retries per target
+   */
+  async getTaskFlakiness(
+    targets: TaskTarget[],
+    options?: FlakinessOptions
+  ): Promise<TaskFlakiness[]> {
+    if (isOnDaemon() || !daemonClient.enabled()) {
+      return this.taskHistory.getTaskFlakiness(targets, options);
+    }
+    return await daemonClient.getTaskFlakiness(targets, options);
+  }
+
+  /**
+   * This function predicts how long the task graph takes to run
+   * @param taskGraph
+   * @param parallelism how many tasks run at the same time
+   * @returns the critical path, the overall ETA and p50/p90 durations per task
+   */
+  async getTaskGraphEstimate(
+    taskGraph: TaskGraph,
+    parallelism?: number
+  ): Promise<TaskGraphEstimate> {
+    if (isOnDaemon() || !daemonClient.enabled()) {
+      return this.taskHistory.getTaskGraphEstimate(taskGraph, parallelism);
+    }
+    return await daemonClient.getTaskGraphEstimate(taskGraph, parallelism);
+  }
+
   async recordTaskRuns(taskRuns: TaskRun[]) {
     if (isOnDaemon() || !daemonClient.enabled()) {
       return this.taskHistory.recordTaskRuns(taskRuns);
     }
     return daemonClient.recordTaskRuns(taskRuns);
   }
+
+  /**
+   * This function rolls runs beyond the retention settings up into daily aggregates
+   * @param retention maximum age and maximum number of runs per target to keep
+   */
+  async compactTaskHistory(
+    retention: TaskHistoryRetention
+  ): Promise<TaskHistoryCompaction> {
+    if (isOnDaemon() || !daemonClient.enabled()) {
+      return this.taskHistory.compactTaskHistory(retention);
+    }
+    return await daemonClient.compactTaskHistory(retention);
+  }
+
+  /**
+   * This function writes the task history to a file for outside analysis
+   * @param path the file to write
+   * @param format JSON Lines or CSV
+   * @param daily whether to export the daily aggregates of compacted runs instead of runs
+   * @returns the number of rows written
+   */
+  exportTaskHistory(
+    path: string,
+    format: TaskHistoryExportFormat,
+    daily?: boolean
+  ): number {
+    return this.taskHistory.exportTaskHistory(path, format, daily);
+  }
 }
 
 let taskHistory: TaskHistory;